@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// How long a tool call waits for the user before it is treated as rejected.
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Tool calls waiting for the user to approve or reject them.
+///
+/// The agent loop registers a call, emits an `agent-tool-approval` event and
+/// waits; the UI answers through `agent_respond_tool_approval`.
+#[derive(Default)]
+pub struct ApprovalRegistry {
+    pending: Mutex<HashMap<String, oneshot::Sender<bool>>>,
+}
+
+impl ApprovalRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `call_id` before the approval event is emitted, so a fast answer is not lost.
+    pub fn register(&self, call_id: &str) -> oneshot::Receiver<bool> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(call_id.to_string(), tx);
+        rx
+    }
+
+    /// Waits for the answer; a timeout or a dropped request counts as rejection.
+    pub async fn wait(&self, call_id: &str, rx: oneshot::Receiver<bool>) -> bool {
+        let approved = matches!(tokio::time::timeout(APPROVAL_TIMEOUT, rx).await, Ok(Ok(true)));
+        self.pending.lock().unwrap().remove(call_id);
+        approved
+    }
+
+    /// Returns `false` if nothing was waiting on `call_id`.
+    pub fn respond(&self, call_id: &str, approved: bool) -> bool {
+        match self.pending.lock().unwrap().remove(call_id) {
+            Some(tx) => tx.send(approved).is_ok(),
+            None => false,
+        }
+    }
+}
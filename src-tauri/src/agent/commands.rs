@@ -1,12 +1,21 @@
-use tauri::{State, Window, Emitter};
+use tauri::{Emitter, State, Window};
 use crate::agent::rag::RagEngine;
+use crate::agent::approval::ApprovalRegistry;
+use crate::agent::completion::{InlineCompletionRequest, InlineCompletionResult, InlineCompletionTracker};
+use crate::agent::commit_message::GeneratedCommitMessage;
+use crate::agent::review::CodeReviewResult;
+use crate::agent::edit::{EditSelectionRequest, EditSelectionResult};
+use crate::agent::trace::{self, RunSummary};
+use crate::mcp::McpManager;
 use std::sync::{Arc, Mutex};
 use crate::agent::openai::{OpenAIClient, ChatRequest, ChatMessage};
-use crate::agent::gemini::{GeminiClient, GeminiRequest, GeminiContent, GeminiPart, GeminiConfig};
-use crate::agent::ollama::{OllamaClient, OllamaChatRequest, OllamaOptions, OllamaModel};
+use crate::agent::gemini::{GeminiRequest, GeminiContent, GeminiPart, GeminiConfig};
+use crate::agent::ollama::{OllamaClient, OllamaChatRequest, OllamaOptions, OllamaModel, OllamaModelDetails, OllamaRunningModel};
 use crate::agent::system_prompt::{generate_system_prompt, SystemPromptContext};
-use crate::agent::tools::{ToolExecutor, parse_tool_calls};
-use futures_util::StreamExt;
+use crate::agent::tools::ToolExecutor;
+use crate::agent::provider::{stream_completion, ProviderConfig};
+use crate::settings::{ProviderProfile, SettingsState, WebFetchSettings};
+use crate::agent::runner::{AgentRun, ApprovalPolicy, DEFAULT_MAX_ITERATIONS};
 use std::path::PathBuf;
 
 pub struct AgentState {
@@ -16,6 +25,9 @@ pub struct AgentState {
     pub ollama_base_url: Mutex<Option<String>>,
     pub workspace_path: Mutex<Option<PathBuf>>,
     pub rag_engine: Arc<RagEngine>,
+    pub mcp: Arc<McpManager>,
+    pub approvals: Arc<ApprovalRegistry>,
+    pub completions: Arc<InlineCompletionTracker>,
 }
 
 impl Default for AgentState {
@@ -27,6 +39,9 @@ impl Default for AgentState {
             ollama_base_url: Mutex::new(None),
             workspace_path: Mutex::new(None),
             rag_engine: Arc::new(RagEngine::new()),
+            mcp: Arc::new(McpManager::new()),
+            approvals: Arc::new(ApprovalRegistry::new()),
+            completions: Arc::new(InlineCompletionTracker::default()),
         }
     }
 }
@@ -67,14 +82,24 @@ pub async fn agentrouter_set_workspace(
         
         // Trigger indexing in the background with cold start optimization
         let rag_engine = state.rag_engine.clone();
+        let index_path = path.clone();
         tokio::spawn(async move {
-            if let Err(_e) = rag_engine.load_or_index(&path).await {
+            if let Err(_e) = rag_engine.load_or_index(&index_path).await {
                 // Failed to index workspace
             }
         });
+
+        // Connect the workspace's MCP servers; failures are reported per server
+        let mcp = state.mcp.clone();
+        tokio::spawn(async move {
+            let _ = mcp.load_workspace(&path).await;
+        });
     } else {
-        let mut w_path = state.workspace_path.lock().unwrap();
-        *w_path = None;
+        {
+            let mut w_path = state.workspace_path.lock().unwrap();
+            *w_path = None;
+        }
+        state.mcp.clear().await;
     }
     Ok(())
 }
@@ -88,12 +113,135 @@ pub async fn agentrouter_list_ollama_models(
     client.list_models().await.map_err(|e| e.to_string())
 }
 
+/// Pulls a model, emitting `ollama-pull-progress` events until the download finishes.
+#[tauri::command]
+pub async fn agentrouter_pull_ollama_model(
+    window: Window,
+    state: State<'_, AgentState>,
+    name: String,
+) -> Result<(), String> {
+    use futures_util::StreamExt;
+
+    let ollama_base_url = state.ollama_base_url.lock().unwrap().clone();
+    let client = OllamaClient::new(ollama_base_url);
+    let mut stream = client.pull_model(&name).await.map_err(|e| e.to_string())?;
+    while let Some(updates) = stream.next().await {
+        for progress in updates.map_err(|e| e.to_string())? {
+            window.emit("ollama-pull-progress", serde_json::json!({
+                "name": name,
+                "status": progress.status,
+                "digest": progress.digest,
+                "total": progress.total,
+                "completed": progress.completed
+            })).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn agentrouter_delete_ollama_model(
+    state: State<'_, AgentState>,
+    name: String,
+) -> Result<(), String> {
+    let ollama_base_url = state.ollama_base_url.lock().unwrap().clone();
+    let client = OllamaClient::new(ollama_base_url);
+    client.delete_model(&name).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn agentrouter_show_ollama_model(
+    state: State<'_, AgentState>,
+    name: String,
+) -> Result<OllamaModelDetails, String> {
+    let ollama_base_url = state.ollama_base_url.lock().unwrap().clone();
+    let client = OllamaClient::new(ollama_base_url);
+    client.show_model(&name).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn agentrouter_list_running_ollama_models(
+    state: State<'_, AgentState>,
+) -> Result<Vec<OllamaRunningModel>, String> {
+    let ollama_base_url = state.ollama_base_url.lock().unwrap().clone();
+    let client = OllamaClient::new(ollama_base_url);
+    client.running_models().await.map_err(|e| e.to_string())
+}
+
+/// Loads a model for `keep_alive` (e.g. `"30m"`, `-1`), or unloads it with `0`.
+#[tauri::command]
+pub async fn agentrouter_set_ollama_keep_alive(
+    state: State<'_, AgentState>,
+    name: String,
+    keep_alive: serde_json::Value,
+) -> Result<(), String> {
+    let ollama_base_url = state.ollama_base_url.lock().unwrap().clone();
+    let client = OllamaClient::new(ollama_base_url);
+    client.set_keep_alive(&name, keep_alive).await.map_err(|e| e.to_string())
+}
+
+/// Looks up an OpenAI-compatible profile from the merged settings.
+fn resolve_profile(settings: &SettingsState, profile_id: Option<String>) -> Result<Option<ProviderProfile>, String> {
+    let id = match profile_id {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    let store = settings.store.lock().unwrap();
+    store.get_settings().ai.provider_profiles
+        .into_iter()
+        .find(|p| p.id == id)
+        .map(Some)
+        .ok_or_else(|| format!("Unknown provider profile: {}", id))
+}
+
+fn web_fetch_settings(settings: &SettingsState) -> WebFetchSettings {
+    settings.store.lock().unwrap().get_settings().ai.web_fetch
+}
+
+/// Models offered by a profile: its configured list plus whatever `/models` reports.
+#[tauri::command]
+pub async fn agentrouter_list_profile_models(
+    settings: State<'_, SettingsState>,
+    profile_id: String,
+) -> Result<Vec<String>, String> {
+    let profile = resolve_profile(&settings, Some(profile_id))?.expect("profile id was given");
+    let mut models = profile.models.clone();
+
+    if profile.discover_models {
+        let client = OpenAIClient::new(profile.resolved_api_key().unwrap_or_default(), Some(profile.base_url.clone()))
+            .with_headers(profile.headers.clone());
+        match client.list_models().await {
+            Ok(discovered) => {
+                for model in discovered {
+                    if !models.contains(&model) {
+                        models.push(model);
+                    }
+                }
+            }
+            Err(e) if models.is_empty() => return Err(format!("Model discovery failed for {}: {}", profile.name, e)),
+            Err(_) => {}
+        }
+    }
+    Ok(models)
+}
+
 #[tauri::command]
 pub async fn agentrouter_chat_complete(
     state: State<'_, AgentState>,
+    settings: State<'_, SettingsState>,
     model: String,
     messages: Vec<ChatMessage>,
+    keep_alive: Option<serde_json::Value>,
+    profile_id: Option<String>,
 ) -> Result<String, String> {
+    if let Some(profile) = resolve_profile(&settings, profile_id)? {
+        let config = ProviderConfig {
+            profile: Some(profile),
+            ..ProviderConfig::from_state(&state)
+        };
+        return stream_completion(&config, &model, &messages, |_| Ok(())).await;
+    }
+
     let openai_key = state.openai_api_key.lock().unwrap().clone();
     let gemini_key = state.gemini_api_key.lock().unwrap().clone();
     let base_url = state.base_url.lock().unwrap().clone();
@@ -135,7 +283,7 @@ pub async fn agentrouter_chat_complete(
 
         let gemini_messages: Vec<GeminiContent> = messages.into_iter().map(|m| GeminiContent {
             role: if m.role == "user" { "user".to_string() } else { "model".to_string() },
-            parts: vec![GeminiPart { text: m.content }],
+            parts: vec![GeminiPart::text(m.content)],
         }).collect();
 
         let request = GeminiRequest {
@@ -144,6 +292,7 @@ pub async fn agentrouter_chat_complete(
             generation_config: Some(GeminiConfig {
                 temperature: None,
                 max_output_tokens: None,
+                thinking_config: None,
             }),
         };
 
@@ -161,6 +310,7 @@ pub async fn agentrouter_chat_complete(
         let body: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
         Ok(body["candidates"][0]["content"]["parts"][0]["text"].as_str().unwrap_or_default().to_string())
     } else {
+        let think = OllamaClient::new(ollama_base_url.clone()).think_for(&model).await;
         let url = format!("{}/api/chat", ollama_base_url.unwrap_or_else(|| "http://localhost:11434".to_string()));
         let request = OllamaChatRequest {
             model,
@@ -169,7 +319,10 @@ pub async fn agentrouter_chat_complete(
             options: Some(OllamaOptions {
                 temperature: None,
                 num_predict: None,
+                stop: None,
             }),
+            keep_alive,
+            think,
         };
 
         let response = reqwest::Client::new()
@@ -202,11 +355,13 @@ pub async fn agentrouter_index_codebase(
 #[tauri::command]
 pub async fn agent_execute_tool(
     state: State<'_, AgentState>,
+    settings: State<'_, SettingsState>,
     tool: String,
     args: serde_json::Value,
 ) -> Result<String, String> {
     let workspace_path = state.workspace_path.lock().unwrap().clone();
-    let executor = ToolExecutor::new(workspace_path, state.rag_engine.clone());
+    let executor = ToolExecutor::new(workspace_path, state.rag_engine.clone())
+        .with_web_fetch(web_fetch_settings(&settings));
     
     let call = crate::agent::tools::ToolCall {
         name: tool,
@@ -226,276 +381,149 @@ pub fn agentrouter_get_system_prompt(
         user_os: std::env::consts::OS.to_string(),
         user_query,
         workspace: workspace_path.as_ref().and_then(|p| p.to_str()).map(|s| s.to_string()),
+        external_tools: Vec::new(),
+        git_writes: false,
     };
     generate_system_prompt(context)
 }
 
+#[tauri::command]
+pub fn agent_respond_tool_approval(
+    state: State<'_, AgentState>,
+    id: String,
+    approved: bool,
+) -> Result<(), String> {
+    if state.approvals.respond(&id, approved) {
+        Ok(())
+    } else {
+        Err(format!("No tool call is waiting for approval: {}", id))
+    }
+}
+
+#[tauri::command]
+pub async fn ai_inline_complete(
+    state: State<'_, AgentState>,
+    settings: State<'_, SettingsState>,
+    request: InlineCompletionRequest,
+    profile_id: Option<String>,
+) -> Result<InlineCompletionResult, String> {
+    let config = ProviderConfig {
+        profile: resolve_profile(&settings, profile_id)?,
+        ..ProviderConfig::from_state(&state)
+    };
+    crate::agent::completion::complete(&config, &state.rag_engine, &state.completions, request).await
+}
+
 #[tauri::command]
 pub async fn agentrouter_chat_stream(
     window: Window,
     state: State<'_, AgentState>,
+    settings: State<'_, SettingsState>,
     model: String,
     messages: Vec<ChatMessage>,
-) -> Result<(), String> {
-    let openai_key = state.openai_api_key.lock().unwrap().clone();
-    let gemini_key = state.gemini_api_key.lock().unwrap().clone();
-    let base_url = state.base_url.lock().unwrap().clone();
-    let ollama_base_url = state.ollama_base_url.lock().unwrap().clone();
+    keep_alive: Option<serde_json::Value>,
+    profile_id: Option<String>,
+) -> Result<Option<String>, String> {
     let workspace_path = state.workspace_path.lock().unwrap().clone();
-
-    let context = SystemPromptContext {
-        user_os: std::env::consts::OS.to_string(),
-        user_query: messages.last().map(|m| m.content.clone()),
-        workspace: workspace_path.as_ref().and_then(|p| p.to_str()).map(|s| s.to_string()),
+    let trace_dir = workspace_path.as_deref().map(trace::traces_dir);
+    let provider = ProviderConfig {
+        ollama_keep_alive: keep_alive,
+        profile: resolve_profile(&settings, profile_id)?,
+        ..ProviderConfig::from_state(&state)
+    };
+    let run = AgentRun {
+        sink: &window,
+        provider: Arc::new(provider),
+        model,
+        workspace_path,
+        rag_engine: state.rag_engine.clone(),
+        mcp: state.mcp.clone(),
+        approval: ApprovalPolicy::Interactive(state.approvals.clone()),
+        max_iterations: DEFAULT_MAX_ITERATIONS,
+        web_fetch: web_fetch_settings(&settings),
+        trace_dir,
+        git_writes: settings.store.lock().unwrap().get_settings().ai.agent_git_writes,
     };
-    let system_prompt = generate_system_prompt(context);
-
-    let mut full_messages = vec![ChatMessage {
-        role: "system".to_string(),
-        content: system_prompt.clone(),
-    }];
-    full_messages.extend(messages);
-
-    let mut current_iteration = 0;
-    let max_iterations = 10;
-
-    loop {
-        current_iteration += 1;
-        if current_iteration > max_iterations {
-            break;
-        }
-
-        let mut full_response = String::new();
-
-        if model.contains("gpt") {
-            let key = openai_key.clone().ok_or("OpenAI API key not configured")?;
-            let client = OpenAIClient::new(key, base_url.clone());
-            let request = ChatRequest {
-                model: model.clone(),
-                messages: full_messages.clone(),
-                stream: true,
-                temperature: None,
-                max_tokens: None,
-            };
-
-            let mut stream = client.chat_stream(request).await.map_err(|e| e.to_string())?;
-            while let Some(chunk_res) = stream.next().await {
-                match chunk_res {
-                    Ok(content) => {
-                        full_response.push_str(&content);
-                        window.emit("agent-event", serde_json::json!({ "type": "chunk", "payload": content })).map_err(|e| e.to_string())?;
-                    }
-                    Err(e) => {
-                        return Err(format!("OpenAI stream error: {}", e));
-                    }
-                }
-            }
-        } else if model.contains("gemini") {
-            let key = gemini_key.clone().ok_or("Gemini API key not configured")?;
-            let client = GeminiClient::new(key);
-            
-            // Skip the first message (system prompt) as it is passed via system_instruction
-            let gemini_messages: Vec<GeminiContent> = full_messages.iter().skip(1).map(|m| GeminiContent {
-                role: if m.role == "user" { "user".to_string() } else { "model".to_string() },
-                parts: vec![GeminiPart { text: m.content.clone() }],
-            }).collect();
-
-            let request = GeminiRequest {
-                contents: gemini_messages,
-                system_instruction: Some(GeminiContent {
-                    role: "user".to_string(), // Role doesn't matter much for system instruction in API
-                    parts: vec![GeminiPart { text: system_prompt.clone() }], // Use clone as system_prompt is moved? No, it's String.
-                }),
-                generation_config: Some(GeminiConfig {
-                    temperature: None,
-                    max_output_tokens: None,
-                }),
-            };
-
-            let mut stream = client.chat_stream(&model, request).await.map_err(|e| e.to_string())?;
-            while let Some(chunk_res) = stream.next().await {
-                match chunk_res {
-                    Ok(content) => {
-                        full_response.push_str(&content);
-                        window.emit("agent-event", serde_json::json!({ "type": "chunk", "payload": content })).map_err(|e| e.to_string())?;
-                    }
-                    Err(e) => {
-                        return Err(format!("Gemini stream error: {}", e));
-                    }
-                }
-            }
-        } else {
-            // Assume Ollama
-            let client = OllamaClient::new(ollama_base_url.clone());
-            let request = OllamaChatRequest {
-                model: model.clone(),
-                messages: full_messages.clone(),
-                stream: true,
-                options: Some(OllamaOptions {
-                    temperature: None,
-                    num_predict: None,
-                }),
-            };
-
-            let mut stream = client.chat_stream(request).await.map_err(|e| e.to_string())?;
-            while let Some(chunk_res) = stream.next().await {
-                match chunk_res {
-                    Ok(content) => {
-                        full_response.push_str(&content);
-                        window.emit("agent-event", serde_json::json!({ "type": "chunk", "payload": content })).map_err(|e| e.to_string())?;
-                    }
-                    Err(e) => {
-                        return Err(format!("Ollama stream error: {}", e));
-                    }
-                }
-            }
-        }
 
-        // Add assistant response to history
-        full_messages.push(ChatMessage {
-            role: "assistant".to_string(),
-            content: full_response.clone(),
-        });
+    let outcome = run.run(messages).await?;
+    Ok(outcome.trace_id)
+}
 
-        // Parse and execute tools
-        let tool_calls = parse_tool_calls(&full_response);
-        
-        // Check if we should break the loop
-        if tool_calls.is_empty() {
-            // If the response contains a final answer marker, we're definitely done
-            if full_response.contains("## FINAL ANSWER") {
-                break;
-            }
-            
-            // If no tool calls and no final answer, nudge the model to continue
-            if current_iteration < max_iterations {
-                full_messages.push(ChatMessage {
-                    role: "user".to_string(),
-                    content: "Your response did not include any tool calls or a ## FINAL ANSWER. If you are finished, please provide the ## FINAL ANSWER. If not, please use the appropriate tool to proceed.".to_string(),
-                });
-                continue;
-            }
+/// Stored agent run traces of the open workspace, newest first.
+#[tauri::command]
+pub fn agent_list_runs(state: State<'_, AgentState>) -> Result<Vec<RunSummary>, String> {
+    let workspace = state.workspace_path.lock().unwrap().clone().ok_or("No workspace open")?;
+    Ok(trace::list_runs(&trace::traces_dir(&workspace)))
+}
 
-            break;
-        }
+/// Returns the full trace of a run as JSON, also writing it to `destination` when given.
+#[tauri::command]
+pub fn agent_export_run(
+    state: State<'_, AgentState>,
+    run_id: String,
+    destination: Option<String>,
+) -> Result<String, String> {
+    let workspace = state.workspace_path.lock().unwrap().clone().ok_or("No workspace open")?;
+    let trace = trace::load_trace(&trace::traces_dir(&workspace), &run_id)?;
+    let json = serde_json::to_string_pretty(&trace).map_err(|e| e.to_string())?;
+    if let Some(destination) = destination {
+        std::fs::write(&destination, &json).map_err(|e| format!("Failed to write {}: {}", destination, e))?;
+    }
+    Ok(json)
+}
 
-        let executor = ToolExecutor::new(workspace_path.clone(), state.rag_engine.clone());
-        let mut tool_outputs = Vec::new();
-
-        for call in tool_calls {
-            let call_id = uuid::Uuid::new_v4().to_string();
-            let tool_name = call.name.clone();
-            
-            // Emit tool call started event
-            let tool_start = serde_json::json!({
-                "type": "agent-tool-start",
-                "payload": {
-                    "id": call_id,
-                    "name": tool_name,
-                    "parameters": call.parameters,
-                    "status": "executing",
-                    "timestamp": chrono::Utc::now().timestamp()
-                }
-            });
-            window.emit("agent-event", tool_start).map_err(|e| e.to_string())?;
-            
-            match executor.execute(call).await {
-                Ok(result) => {
-                    // Emit tool result event
-                    let tool_success = serde_json::json!({
-                        "type": "agent-tool-res",
-                        "payload": {
-                            "id": call_id,
-                            "name": tool_name,
-                            "result": result,
-                            "status": "completed"
-                        }
-                    });
-                    window.emit("agent-event", tool_success).map_err(|e| e.to_string())?;
-                    
-                    // Extract line count info if present to show in tool outputs
-                    let _display_result = if result.starts_with("[File:") {
-                        result.splitn(2, ']').next().unwrap_or("").to_string() + "]"
-                    } else {
-                        "Completed".to_string()
-                    };
-                    
-                    // Limit the result size for the LLM context to avoid hitting limits
-                    let history_result = if result.len() > 100000 {
-                        format!("{}... (truncated, total length: {})", &result[..100000], result.len())
-                    } else {
-                        result.clone()
-                    };
-                    
-                    // Format output more naturally for the LLM
-                    let formatted_output = if tool_name == "search_files" || tool_name == "find_by_name" {
-                        if let Ok(files) = serde_json::from_str::<Vec<serde_json::Value>>(&history_result) {
-                            if files.is_empty() {
-                                "No files found matching the pattern.".to_string()
-                            } else {
-                                let file_list = files.iter()
-                                    .filter_map(|f| f.get("path").and_then(|p| p.as_str()))
-                                    .collect::<Vec<_>>()
-                                    .join("\n");
-                                format!("Found files:\n{}", file_list)
-                            }
-                        } else {
-                            history_result
-                        }
-                    } else if tool_name == "search_codebase" {
-                        if let Ok(symbols) = serde_json::from_str::<Vec<serde_json::Value>>(&history_result) {
-                            if symbols.is_empty() {
-                                "No symbols found matching the query.".to_string()
-                            } else {
-                                let symbol_list = symbols.iter()
-                                    .take(15) // Limit to top 15 for brevity
-                                    .map(|s| {
-                                        let name = s.get("name").and_then(|v| v.as_str()).unwrap_or("?");
-                                        let kind = s.get("kind").and_then(|v| v.as_str()).unwrap_or("?");
-                                        let path = s.get("file_path").and_then(|v| v.as_str()).unwrap_or("?");
-                                        let line = s.get("start_line").and_then(|v| v.as_u64()).unwrap_or(0);
-                                        format!("{} ({}) in {} (line {})", name, kind, path, line)
-                                    })
-                                    .collect::<Vec<_>>()
-                                    .join("\n");
-                                format!("Found symbols:\n{}", symbol_list)
-                            }
-                        } else {
-                            history_result
-                        }
-                    } else {
-                        history_result
-                    };
-                    
-                    tool_outputs.push(format!("[{}] result:\n{}", tool_name, formatted_output));
-                }
-                Err(e) => {
-                    let err_msg = e.to_string();
-                    // Emit tool error event
-                    let tool_error = serde_json::json!({
-                        "type": "agent-tool-error",
-                        "payload": {
-                            "id": call_id,
-                            "name": tool_name,
-                            "error": err_msg,
-                            "status": "error"
-                        }
-                    });
-                    window.emit("agent-event", tool_error).map_err(|e| e.to_string())?;
-                    tool_outputs.push(format!("Tool '{}' error: {}", tool_name, err_msg));
-                }
-            }
-        }
+#[tauri::command]
+pub async fn ai_generate_commit_message(
+    state: State<'_, AgentState>,
+    settings: State<'_, SettingsState>,
+    repo_path: String,
+    model: String,
+    profile_id: Option<String>,
+) -> Result<GeneratedCommitMessage, String> {
+    let config = ProviderConfig {
+        profile: resolve_profile(&settings, profile_id)?,
+        ..ProviderConfig::from_state(&state)
+    };
+    crate::agent::commit_message::generate(&config, &model, &repo_path).await
+}
 
-        // Add tool outputs to history as a user message to prompt the model to continue
-        if !tool_outputs.is_empty() {
-            let tool_response_content = tool_outputs.join("\n\n");
-            full_messages.push(ChatMessage {
-                role: "user".to_string(), 
-                content: format!("Tool execution results:\n{}\n\nPlease analyze these results and take the next step.", tool_response_content),
-            });
-        }
-    }
+/// Reviews `base...HEAD`, or the working tree against HEAD when no base is given,
+/// and publishes the findings to the problems panel.
+#[tauri::command]
+pub async fn ai_review_changes(
+    state: State<'_, AgentState>,
+    settings: State<'_, SettingsState>,
+    repo_path: String,
+    model: String,
+    base: Option<String>,
+    profile_id: Option<String>,
+) -> Result<CodeReviewResult, String> {
+    let config = ProviderConfig {
+        profile: resolve_profile(&settings, profile_id)?,
+        ..ProviderConfig::from_state(&state)
+    };
+    let result = crate::agent::review::review(&config, &model, &state.rag_engine, &repo_path, base.as_deref()).await?;
+
+    let problems = result.findings.iter()
+        .enumerate()
+        .map(|(i, finding)| finding.to_problem(i as u32 + 1))
+        .collect();
+    crate::problems::set_review_problems(&repo_path, problems);
+    Ok(result)
+}
 
-    Ok(())
+/// Single-turn "edit selection with instruction"; streams `edit-chunk` agent events.
+#[tauri::command]
+pub async fn ai_edit_selection(
+    window: Window,
+    state: State<'_, AgentState>,
+    settings: State<'_, SettingsState>,
+    request: EditSelectionRequest,
+    profile_id: Option<String>,
+) -> Result<EditSelectionResult, String> {
+    let config = ProviderConfig {
+        profile: resolve_profile(&settings, profile_id)?,
+        ..ProviderConfig::from_state(&state)
+    };
+    crate::agent::edit::edit_selection(&window, &config, request).await
 }
@@ -0,0 +1,283 @@
+use serde::Serialize;
+use crate::agent::openai::ChatMessage;
+use crate::agent::provider::{stream_completion, ProviderConfig};
+use git2::Repository;
+use crate::git;
+
+/// Character budget for the diff sent to the model.
+const DIFF_BUDGET: usize = 12000;
+const STYLE_EXAMPLES: usize = 10;
+const SUMMARY_MAX_CHARS: usize = 72;
+
+/// Files whose diffs carry no useful signal for a commit message.
+const NOISY_FILES: &[&str] = &["Cargo.lock", "package-lock.json", "yarn.lock", "pnpm-lock.yaml", "bun.lockb", "poetry.lock", "Gemfile.lock", "go.sum"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeneratedCommitMessage {
+    /// Summary line and body joined the way `git_commit` expects them.
+    pub message: String,
+    pub summary: String,
+    pub body: String,
+    pub files_changed: usize,
+    /// Some of the diff was left out to fit the prompt.
+    pub truncated: bool,
+}
+
+struct RenderedDiff {
+    path: String,
+    additions: usize,
+    deletions: usize,
+    lines: Vec<String>,
+    noisy: bool,
+}
+
+fn is_noisy(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    NOISY_FILES.contains(&name) || name.ends_with(".min.js") || name.ends_with(".min.css") || name.ends_with(".map")
+}
+
+/// Renders every staged file from a single index-against-HEAD diff.
+fn staged_diffs(repo_path: &str) -> Result<Vec<RenderedDiff>, String> {
+    let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+    let head = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+    let diff = repo.diff_tree_to_index(head.as_ref(), None, None).map_err(|e| e.to_string())?;
+
+    let mut diffs: Vec<RenderedDiff> = Vec::new();
+    diff.print(git2::DiffFormat::Patch, |delta, _hunk, line| {
+        let path = match delta.new_file().path().or(delta.old_file().path()) {
+            Some(path) => path.to_string_lossy().replace('\\', "/"),
+            None => return true,
+        };
+        if diffs.last().map(|d| d.path != path).unwrap_or(true) {
+            diffs.push(RenderedDiff { noisy: is_noisy(&path), path, additions: 0, deletions: 0, lines: Vec::new() });
+        }
+        let rendered = diffs.last_mut().unwrap();
+        let content = String::from_utf8_lossy(line.content());
+        let content = content.trim_end_matches('\n');
+
+        match line.origin() {
+            '+' => {
+                rendered.additions += 1;
+                rendered.lines.push(format!("+{}", content));
+            }
+            '-' => {
+                rendered.deletions += 1;
+                rendered.lines.push(format!("-{}", content));
+            }
+            'F' | 'H' => rendered.lines.extend(content.lines().map(|l| l.to_string())),
+            _ => rendered.lines.push(format!(" {}", content)),
+        }
+        true
+    }).map_err(|e| e.to_string())?;
+    Ok(diffs)
+}
+
+fn diff_len(lines: &[String]) -> usize {
+    lines.iter().map(|l| l.len() + 1).sum()
+}
+
+/// Fits the rendered diffs into `budget` characters.
+///
+/// Noisy files are reduced to their stat line. The rest of the budget is
+/// shared fairly: small diffs are kept whole and the space they leave is handed
+/// to the larger ones, which are cut at a line boundary.
+fn fit_diffs(diffs: &[RenderedDiff], budget: usize) -> (String, bool) {
+    let mut truncated = false;
+    let mut stat = String::from("Changed files:\n");
+    for d in diffs {
+        stat.push_str(&format!("  {} (+{} -{})\n", d.path, d.additions, d.deletions));
+    }
+
+    let mut candidates: Vec<(usize, usize)> = diffs.iter()
+        .enumerate()
+        .filter(|(_, d)| !d.noisy)
+        .map(|(i, d)| (i, diff_len(&d.lines)))
+        .collect();
+    truncated |= candidates.len() < diffs.len();
+
+    let mut allowance = vec![0usize; diffs.len()];
+    let mut remaining = budget.saturating_sub(stat.len());
+    candidates.sort_by_key(|(_, len)| *len);
+    let mut left = candidates.len();
+    for (i, len) in candidates {
+        let share = remaining / left.max(1);
+        let take = len.min(share);
+        allowance[i] = take;
+        remaining -= take;
+        left -= 1;
+    }
+
+    let mut out = stat;
+    for (i, d) in diffs.iter().enumerate() {
+        if d.noisy {
+            continue;
+        }
+        out.push('\n');
+        let mut used = 0;
+        let mut kept = 0;
+        for line in &d.lines {
+            if used + line.len() + 1 > allowance[i] {
+                break;
+            }
+            used += line.len() + 1;
+            out.push_str(line);
+            out.push('\n');
+            kept += 1;
+        }
+        if kept < d.lines.len() {
+            truncated = true;
+            out.push_str(&format!("... ({} more lines in {} omitted)\n", d.lines.len() - kept, d.path));
+        }
+    }
+
+    (out, truncated)
+}
+
+/// Splits a model reply into summary and body, dropping fences and quotes.
+fn parse_message(raw: &str) -> (String, String) {
+    let mut text = raw.trim();
+    if text.starts_with("```") {
+        text = text.split_once('\n').map(|(_, rest)| rest).unwrap_or("");
+        text = text.rsplit_once("```").map(|(body, _)| body).unwrap_or(text).trim();
+    }
+
+    let mut lines = text.lines();
+    let summary = lines.next().unwrap_or("")
+        .trim()
+        .trim_matches(|c| c == '"' || c == '`')
+        .to_string();
+    let summary = match summary.char_indices().nth(SUMMARY_MAX_CHARS) {
+        Some((idx, _)) => summary[..idx].trim_end().to_string(),
+        None => summary,
+    };
+    let body = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+    (summary, body)
+}
+
+fn style_examples(repo_path: &str) -> Vec<String> {
+    git::git_log(repo_path.to_string(), Some(STYLE_EXAMPLES))
+        .map(|commits| commits.into_iter()
+            .filter_map(|c| c.message.lines().next().map(|l| l.trim().to_string()))
+            .filter(|l| !l.is_empty())
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Writes a conventional commit message for the staged changes in `repo_path`.
+pub async fn generate(config: &ProviderConfig, model: &str, repo_path: &str) -> Result<GeneratedCommitMessage, String> {
+    let diffs = staged_diffs(repo_path)?;
+    if diffs.is_empty() {
+        return Err("No staged changes to describe".to_string());
+    }
+    let (diff_text, truncated) = fit_diffs(&diffs, DIFF_BUDGET);
+
+    let examples = style_examples(repo_path);
+    let style = if examples.is_empty() {
+        String::new()
+    } else {
+        format!("\nRecent commit subjects in this repository, for tone and scope naming:\n{}\n", examples.iter().map(|e| format!("- {}", e)).collect::<Vec<_>>().join("\n"))
+    };
+
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: format!(
+                "You write git commit messages in the Conventional Commits format.\n\
+                 Reply with the message only, no code fences or commentary:\n\
+                 - First line: `type(scope): summary`, imperative mood, at most {} characters. \
+                 Types: feat, fix, refactor, perf, docs, test, build, ci, chore, style.\n\
+                 - Then a blank line and a short body explaining what changed and why, wrapped at 72 columns. \
+                 Omit the body for trivial changes.",
+                SUMMARY_MAX_CHARS
+            ),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!("{}\nStaged diff{}:\n{}", style, if truncated { " (partially omitted)" } else { "" }, diff_text),
+        },
+    ];
+
+    let reply = stream_completion(config, model, &messages, |_| Ok(())).await?;
+    let (summary, body) = parse_message(&reply);
+    if summary.is_empty() {
+        return Err("The model returned an empty commit message".to_string());
+    }
+
+    let message = if body.is_empty() { summary.clone() } else { format!("{}\n\n{}", summary, body) };
+    Ok(GeneratedCommitMessage {
+        message,
+        summary,
+        body,
+        files_changed: diffs.len(),
+        truncated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered(path: &str, lines: usize) -> RenderedDiff {
+        RenderedDiff {
+            path: path.to_string(),
+            additions: lines,
+            deletions: 0,
+            lines: (0..lines).map(|i| format!("+line {}", i)).collect(),
+            noisy: is_noisy(path),
+        }
+    }
+
+    #[test]
+    fn splits_the_staged_diff_per_file() {
+        let dir = std::env::temp_dir().join(format!("cognitive-commit-{}", uuid::Uuid::new_v4()));
+        let repo = Repository::init(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "one\ntwo\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(std::path::Path::new("a.txt")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[]).unwrap();
+
+        std::fs::write(dir.join("a.txt"), "one\n2\n").unwrap();
+        std::fs::write(dir.join("b.txt"), "new\n").unwrap();
+        std::fs::write(dir.join("unstaged.txt"), "x\n").unwrap();
+        index.add_path(std::path::Path::new("a.txt")).unwrap();
+        index.add_path(std::path::Path::new("b.txt")).unwrap();
+        index.write().unwrap();
+
+        let diffs = staged_diffs(&dir.to_string_lossy()).unwrap();
+        let summary: Vec<(&str, usize, usize)> = diffs.iter().map(|d| (d.path.as_str(), d.additions, d.deletions)).collect();
+        assert_eq!(summary, vec![("a.txt", 1, 1), ("b.txt", 1, 0)]);
+        assert!(diffs[0].lines.iter().any(|l| l == "-two"));
+        assert!(diffs[0].lines.iter().any(|l| l.starts_with("@@")));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn small_diffs_are_kept_whole() {
+        let (text, truncated) = fit_diffs(&[rendered("src/a.rs", 3)], 1000);
+        assert!(!truncated);
+        assert!(text.contains("+line 2"));
+    }
+
+    #[test]
+    fn large_diffs_share_the_budget() {
+        let diffs = [rendered("src/small.rs", 2), rendered("src/big.rs", 500), rendered("Cargo.lock", 800)];
+        let (text, truncated) = fit_diffs(&diffs, 2000);
+        assert!(truncated);
+        assert!(text.len() < 2200);
+        assert!(text.contains("Cargo.lock (+800 -0)"));
+        assert!(!text.contains("Cargo.lock omitted"));
+        assert!(text.contains("+line 1\n"));
+        assert!(text.contains("more lines in src/big.rs omitted"));
+    }
+
+    #[test]
+    fn parses_fenced_reply() {
+        let (summary, body) = parse_message("```\nfeat(git): add staged diff summary\n\nExplains the change.\n```");
+        assert_eq!(summary, "feat(git): add staged diff summary");
+        assert_eq!(body, "Explains the change.");
+    }
+}
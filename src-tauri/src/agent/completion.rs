@@ -0,0 +1,466 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use futures_util::future::{BoxFuture, FutureExt};
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use crate::agent::ollama::{OllamaClient, OllamaGenerateRequest, OllamaOptions};
+use crate::agent::openai::ChatMessage;
+use crate::agent::provider::{stream_completion, Backend, ProviderConfig};
+use crate::agent::rag::RagEngine;
+
+const DEFAULT_DEBOUNCE_MS: u64 = 75;
+const DEFAULT_BUDGET_MS: u64 = 1500;
+const MAX_SUGGESTIONS: usize = 3;
+const MAX_PREFIX_CHARS: usize = 6000;
+const MAX_SUFFIX_CHARS: usize = 2000;
+const MAX_CONTEXT_SYMBOLS: usize = 8;
+const MAX_COMPLETION_TOKENS: u32 = 128;
+
+lazy_static::lazy_static! {
+    /// Whether an Ollama model has a fill-in-the-middle template, by server URL and model.
+    static ref FIM_SUPPORT: Mutex<HashMap<(String, String), bool>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineCompletionRequest {
+    pub file_path: String,
+    /// Zero-based cursor position, echoed back so the editor can drop stale results.
+    pub line: u32,
+    pub character: u32,
+    /// Text before and after the cursor, as much as the editor wants to send.
+    pub prefix: String,
+    #[serde(default)]
+    pub suffix: String,
+    pub model: String,
+    #[serde(default)]
+    pub max_suggestions: Option<usize>,
+    #[serde(default)]
+    pub debounce_ms: Option<u64>,
+    #[serde(default)]
+    pub budget_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineSuggestion {
+    pub text: String,
+    pub score: f32,
+    /// `fim` for native fill-in-the-middle, `chat` for the chat fallback.
+    pub source: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineCompletionResult {
+    pub request_id: u64,
+    pub line: u32,
+    pub character: u32,
+    pub suggestions: Vec<InlineSuggestion>,
+    pub latency_ms: u64,
+    /// A newer request arrived before this one finished.
+    pub cancelled: bool,
+    /// The latency budget ran out; `suggestions` holds whatever finished in time.
+    pub timed_out: bool,
+}
+
+/// Tracks the latest inline completion request so superseded ones can stop early.
+pub struct InlineCompletionTracker {
+    latest: watch::Sender<u64>,
+}
+
+impl Default for InlineCompletionTracker {
+    fn default() -> Self {
+        Self { latest: watch::channel(0).0 }
+    }
+}
+
+impl InlineCompletionTracker {
+    fn begin(&self) -> (u64, watch::Receiver<u64>) {
+        let mut id = 0;
+        self.latest.send_modify(|latest| {
+            *latest += 1;
+            id = *latest;
+        });
+        (id, self.latest.subscribe())
+    }
+}
+
+/// Resolves once a request newer than `id` has started.
+async fn superseded(rx: &mut watch::Receiver<u64>, id: u64) {
+    loop {
+        if *rx.borrow_and_update() != id {
+            return;
+        }
+        if rx.changed().await.is_err() {
+            return std::future::pending().await;
+        }
+    }
+}
+
+fn tail_chars(text: &str, max: usize) -> &str {
+    match text.char_indices().rev().nth(max.saturating_sub(1)) {
+        Some((idx, _)) if max > 0 => &text[idx..],
+        _ if max == 0 => "",
+        _ => text,
+    }
+}
+
+fn head_chars(text: &str, max: usize) -> &str {
+    match text.char_indices().nth(max) {
+        Some((idx, _)) => &text[..idx],
+        None => text,
+    }
+}
+
+fn comment_token(file_path: &str) -> &'static str {
+    let ext = Path::new(file_path).extension().and_then(|e| e.to_str()).unwrap_or("");
+    match ext {
+        "py" | "sh" | "bash" | "zsh" | "rb" | "toml" | "yaml" | "yml" | "r" | "pl" => "#",
+        "sql" | "lua" | "hs" => "--",
+        _ => "//",
+    }
+}
+
+/// Identifiers near the cursor, most recent first.
+fn nearby_identifiers(prefix: &str) -> Vec<String> {
+    let window = tail_chars(prefix, 400);
+    let mut seen = HashSet::new();
+    let mut idents = Vec::new();
+    for word in window.rsplit(|c: char| !(c.is_alphanumeric() || c == '_')) {
+        if word.len() < 3 || word.chars().next().is_none_or(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        if seen.insert(word.to_string()) {
+            idents.push(word.to_string());
+        }
+        if idents.len() >= 6 {
+            break;
+        }
+    }
+    idents
+}
+
+/// Workspace symbols matching identifiers around the cursor, rendered as comment lines.
+fn symbol_context(rag_engine: &RagEngine, request: &InlineCompletionRequest) -> String {
+    let comment = comment_token(&request.file_path);
+    let mut seen = HashSet::new();
+    let mut lines = Vec::new();
+
+    for ident in nearby_identifiers(&request.prefix) {
+        for symbol in rag_engine.search(&ident).into_iter().take(2) {
+            if symbol.file_path == request.file_path || !seen.insert((symbol.name.clone(), symbol.file_path.clone())) {
+                continue;
+            }
+            let detail = symbol.detail.as_deref().map(|d| format!(" {}", d)).unwrap_or_default();
+            lines.push(format!("{} {} {}{} ({}:{})", comment, symbol.kind, symbol.name, detail, symbol.file_path, symbol.start_line));
+        }
+        if lines.len() >= MAX_CONTEXT_SYMBOLS {
+            break;
+        }
+    }
+
+    if lines.is_empty() {
+        return String::new();
+    }
+    lines.truncate(MAX_CONTEXT_SYMBOLS);
+    format!("{} Related symbols:\n{}\n", comment, lines.join("\n"))
+}
+
+/// Cleans a raw model completion: strips code fences and drops any tail that
+/// just repeats the text already after the cursor.
+fn clean_completion(raw: &str, suffix: &str) -> String {
+    let mut text = raw.to_string();
+
+    let trimmed = text.trim_start();
+    if trimmed.starts_with("```") {
+        let body = trimmed.split_once('\n').map(|(_, rest)| rest).unwrap_or("");
+        text = body.rsplit_once("```").map(|(code, _)| code).unwrap_or(body).to_string();
+        if text.ends_with('\n') {
+            text.pop();
+        }
+    }
+
+    let first_line = suffix.trim_start().lines().next().unwrap_or("").trim();
+    if first_line.len() >= 3 {
+        let mut offset = 0;
+        let mut cut = None;
+        for line in text.split_inclusive('\n') {
+            if line.trim() == first_line {
+                cut = Some(offset);
+                break;
+            }
+            offset += line.len();
+        }
+        if let Some(cut) = cut {
+            text.truncate(cut);
+        }
+    }
+
+    text.trim_end().to_string()
+}
+
+/// Scores a suggestion: earlier (lower temperature) samples rank higher, and
+/// unbalanced brackets or very long insertions are penalised.
+fn score_suggestion(text: &str, sample_index: usize) -> f32 {
+    let mut score = 1.0 - sample_index as f32 * 0.1;
+    let depth = text.chars().fold(0i32, |depth, c| match c {
+        '(' | '[' | '{' => depth + 1,
+        ')' | ']' | '}' => depth - 1,
+        _ => depth,
+    });
+    if depth != 0 {
+        score -= 0.2;
+    }
+    if text.lines().count() > 12 {
+        score -= 0.1;
+    }
+    score
+}
+
+/// Drops empty and duplicate suggestions and sorts the rest by score.
+fn rank_suggestions(mut suggestions: Vec<InlineSuggestion>, limit: usize) -> Vec<InlineSuggestion> {
+    let mut seen = HashSet::new();
+    suggestions.retain(|s| !s.text.trim().is_empty() && seen.insert(s.text.trim().to_string()));
+    suggestions.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    suggestions.truncate(limit);
+    suggestions
+}
+
+/// Ollama lists `insert` for models whose template accepts a suffix; older
+/// servers report no capabilities at all, so those get the benefit of the doubt.
+fn capabilities_allow_fim(capabilities: &[String]) -> bool {
+    capabilities.is_empty() || capabilities.iter().any(|c| c == "insert")
+}
+
+fn fim_support_key(config: &ProviderConfig, model: &str) -> (String, String) {
+    (config.ollama_base_url.clone().unwrap_or_default(), model.to_string())
+}
+
+/// Only Ollama serves `suffix` completions; whether a model can is asked once and remembered.
+async fn uses_native_fim(config: &ProviderConfig, model: &str) -> bool {
+    if config.backend(model) != Backend::Ollama {
+        return false;
+    }
+    let key = fim_support_key(config, model);
+    if let Some(supported) = FIM_SUPPORT.lock().unwrap().get(&key) {
+        return *supported;
+    }
+    // Unknown capabilities let the FIM request itself fail and fall back to chat
+    let capabilities = OllamaClient::new(config.ollama_base_url.clone()).capabilities(model).await;
+    let supported = capabilities_allow_fim(&capabilities);
+    FIM_SUPPORT.lock().unwrap().insert(key, supported);
+    supported
+}
+
+async fn fim_sample(
+    config: &ProviderConfig,
+    request: &InlineCompletionRequest,
+    prompt: &str,
+    suffix: &str,
+    temperature: f32,
+) -> Result<String, String> {
+    let client = OllamaClient::new(config.ollama_base_url.clone());
+    client.generate(OllamaGenerateRequest {
+        model: request.model.clone(),
+        prompt: prompt.to_string(),
+        suffix: Some(suffix.to_string()),
+        stream: false,
+        options: Some(OllamaOptions {
+            temperature: Some(temperature),
+            num_predict: Some(MAX_COMPLETION_TOKENS),
+            stop: Some(vec!["\n\n\n".to_string()]),
+        }),
+        keep_alive: config.ollama_keep_alive.clone(),
+    }).await.map_err(|e| e.to_string())
+}
+
+async fn chat_sample(
+    config: &ProviderConfig,
+    request: &InlineCompletionRequest,
+    context: &str,
+    prefix: &str,
+    suffix: &str,
+) -> Result<String, String> {
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: "You are a code completion engine. Reply with only the code to insert at <CURSOR>: no explanations, no code fences, and do not repeat the text before or after the cursor.".to_string(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!("File: {}\n{}\n{}<CURSOR>{}", request.file_path, context, prefix, suffix),
+        },
+    ];
+    stream_completion(config, &request.model, &messages, |_| Ok(())).await
+}
+
+/// Produces ranked inline suggestions for the cursor in `request`.
+///
+/// Waits `debounce_ms` first and gives up as soon as a newer request starts.
+/// Samples are collected until `budget_ms` elapses; whatever finished by then is returned.
+pub async fn complete(
+    config: &ProviderConfig,
+    rag_engine: &RagEngine,
+    tracker: &InlineCompletionTracker,
+    request: InlineCompletionRequest,
+) -> Result<InlineCompletionResult, String> {
+    let started = Instant::now();
+    let (request_id, mut latest) = tracker.begin();
+    let mut result = InlineCompletionResult {
+        request_id,
+        line: request.line,
+        character: request.character,
+        suggestions: Vec::new(),
+        latency_ms: 0,
+        cancelled: false,
+        timed_out: false,
+    };
+
+    let debounce = Duration::from_millis(request.debounce_ms.unwrap_or(DEFAULT_DEBOUNCE_MS));
+    tokio::select! {
+        _ = tokio::time::sleep(debounce) => {}
+        _ = superseded(&mut latest, request_id) => {
+            result.cancelled = true;
+            return Ok(result);
+        }
+    }
+
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(request.budget_ms.unwrap_or(DEFAULT_BUDGET_MS));
+    let limit = request.max_suggestions.unwrap_or(1).clamp(1, MAX_SUGGESTIONS);
+    let prefix = tail_chars(&request.prefix, MAX_PREFIX_CHARS);
+    let suffix = head_chars(&request.suffix, MAX_SUFFIX_CHARS);
+    let context = symbol_context(rag_engine, &request);
+
+    let (context, request) = (&context, &request);
+    let chat = move || async move { (0, "chat", chat_sample(config, request, context, prefix, suffix).await) }.boxed();
+
+    let mut samples: FuturesUnordered<BoxFuture<'_, (usize, &'static str, Result<String, String>)>> = FuturesUnordered::new();
+    let mut chat_started = false;
+    if tokio::time::timeout_at(deadline, uses_native_fim(config, &request.model)).await.unwrap_or(true) {
+        let prompt = format!("{}{}", context, prefix);
+        for index in 0..limit {
+            let prompt = prompt.clone();
+            samples.push(async move {
+                let temperature = index as f32 * 0.4;
+                let text = fim_sample(config, request, &prompt, suffix, temperature).await;
+                (index, "fim", text)
+            }.boxed());
+        }
+    } else {
+        samples.push(chat());
+        chat_started = true;
+    }
+
+    let mut suggestions = Vec::new();
+    let mut last_error = None;
+    loop {
+        tokio::select! {
+            next = samples.next() => match next {
+                Some((index, source, Ok(raw))) => {
+                    let text = clean_completion(&raw, suffix);
+                    let score = score_suggestion(&text, index);
+                    suggestions.push(InlineSuggestion { text, score, source });
+                }
+                Some((_, source, Err(e))) => {
+                    // The model has no FIM template, or the call failed: ask through chat instead
+                    if source == "fim" && !chat_started && suggestions.is_empty() {
+                        if e.contains("does not support insert") {
+                            FIM_SUPPORT.lock().unwrap().insert(fim_support_key(config, &request.model), false);
+                        }
+                        samples.push(chat());
+                        chat_started = true;
+                    }
+                    last_error = Some(e);
+                }
+                None => break,
+            },
+            _ = tokio::time::sleep_until(deadline) => {
+                result.timed_out = true;
+                break;
+            }
+            _ = superseded(&mut latest, request_id) => {
+                result.cancelled = true;
+                break;
+            }
+        }
+    }
+
+    result.suggestions = if result.cancelled { Vec::new() } else { rank_suggestions(suggestions, limit) };
+    result.latency_ms = started.elapsed().as_millis() as u64;
+
+    if result.suggestions.is_empty() && !result.cancelled && !result.timed_out {
+        if let Some(e) = last_error {
+            return Err(e);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_fences_and_suffix_overlap() {
+        let raw = "```rust\nlet total = a + b;\n    total\n}\n```";
+        assert_eq!(clean_completion(raw, "\n    total\n}\n"), "let total = a + b;");
+
+        // Short suffix lines such as a lone `}` are too ambiguous to strip
+        assert_eq!(clean_completion("x + y)\n}", ")\n}"), "x + y)\n}");
+    }
+
+    #[test]
+    fn ranks_and_dedupes() {
+        let suggestions = vec![
+            InlineSuggestion { text: "foo(".into(), score: score_suggestion("foo(", 0), source: "fim" },
+            InlineSuggestion { text: "foo()".into(), score: score_suggestion("foo()", 1), source: "fim" },
+            InlineSuggestion { text: " foo() ".into(), score: 0.5, source: "fim" },
+            InlineSuggestion { text: "  ".into(), score: 2.0, source: "fim" },
+        ];
+        let ranked = rank_suggestions(suggestions, 3);
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].text, "foo()");
+    }
+
+    #[test]
+    fn fim_needs_the_insert_capability() {
+        let caps = |list: &[&str]| list.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        assert!(capabilities_allow_fim(&caps(&["completion", "insert"])));
+        assert!(!capabilities_allow_fim(&caps(&["completion", "tools"])));
+        assert!(capabilities_allow_fim(&[]));
+    }
+
+    #[tokio::test]
+    async fn routes_by_provider() {
+        let profile = serde_json::from_value(serde_json::json!({
+            "id": "local", "name": "vLLM", "baseUrl": "http://127.0.0.1:8000/v1"
+        })).unwrap();
+        let config = ProviderConfig { profile: Some(profile), ..ProviderConfig::default() };
+        // A profile serving a codellama model is OpenAI-compatible, not Ollama
+        assert!(!uses_native_fim(&config, "codellama:7b-code").await);
+        assert!(!uses_native_fim(&ProviderConfig::default(), "gpt-4o").await);
+        assert!(!uses_native_fim(&ProviderConfig::default(), "gemini-2.0-flash").await);
+    }
+
+    #[test]
+    fn char_windows_respect_boundaries() {
+        assert_eq!(tail_chars("héllo", 3), "llo");
+        assert_eq!(tail_chars("hi", 10), "hi");
+        assert_eq!(head_chars("héllo", 2), "hé");
+    }
+
+    #[tokio::test]
+    async fn newer_request_supersedes() {
+        let tracker = InlineCompletionTracker::default();
+        let (first, mut rx) = tracker.begin();
+        let (second, _) = tracker.begin();
+        assert_ne!(first, second);
+        tokio::time::timeout(Duration::from_millis(100), superseded(&mut rx, first))
+            .await
+            .expect("first request should be superseded");
+    }
+}
@@ -0,0 +1,335 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::agent::openai::ChatMessage;
+use crate::agent::provider::{ModelProvider, StreamDelta};
+use crate::agent::runner::AgentEventSink;
+use crate::git::DiffLine;
+
+const CONTEXT_LINES_BEFORE: usize = 80;
+const CONTEXT_LINES_AFTER: usize = 40;
+/// Above this many line pairs the diff falls back to delete-all/add-all.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditSelectionRequest {
+    pub file_path: String,
+    /// Zero-based selection; `character` counts chars within the line.
+    pub start_line: u32,
+    pub start_character: u32,
+    pub end_line: u32,
+    pub end_character: u32,
+    pub instruction: String,
+    pub model: String,
+    /// Unsaved editor buffer; the file on disk is used when absent.
+    #[serde(default)]
+    pub content: Option<String>,
+    /// Echoed in `edit-chunk` events so the editor can match them to the request.
+    #[serde(default)]
+    pub edit_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditSelectionResult {
+    pub edit_id: String,
+    pub original: String,
+    pub replacement: String,
+    /// The whole document with the replacement applied.
+    pub new_content: String,
+    pub diff: Vec<DiffLine>,
+    /// Non-fatal validation notes, e.g. unbalanced brackets.
+    pub warnings: Vec<String>,
+}
+
+/// Converts a line/char position into a byte offset, or `None` if it lies outside the text.
+fn offset_at(text: &str, line: u32, character: u32) -> Option<usize> {
+    let mut line_start = 0;
+    for _ in 0..line {
+        line_start += text[line_start..].find('\n')? + 1;
+    }
+    let line_end = text[line_start..].find('\n').map(|i| line_start + i).unwrap_or(text.len());
+    let line_text = &text[line_start..line_end];
+    let line_text = line_text.strip_suffix('\r').unwrap_or(line_text);
+    if character as usize == line_text.chars().count() {
+        return Some(line_start + line_text.len());
+    }
+    line_text.char_indices().nth(character as usize).map(|(i, _)| line_start + i)
+}
+
+fn last_lines(text: &str, count: usize) -> &str {
+    let mut idx = text.len();
+    for _ in 0..count {
+        match text[..idx].rfind('\n') {
+            Some(i) => idx = i,
+            None => return text,
+        }
+    }
+    &text[idx + 1..]
+}
+
+fn first_lines(text: &str, count: usize) -> &str {
+    let mut idx = 0;
+    for _ in 0..count {
+        match text[idx..].find('\n') {
+            Some(i) => idx += i + 1,
+            None => return text,
+        }
+    }
+    &text[..idx]
+}
+
+/// The code inside the first fenced block, even if the closing fence has not streamed in yet.
+/// Replies without a fence are taken as-is.
+fn extract_code(reply: &str) -> String {
+    let start = match reply.find("```") {
+        Some(start) => start,
+        None => return reply.trim_matches('\n').to_string(),
+    };
+    let after_fence = &reply[start + 3..];
+    let body = match after_fence.find('\n') {
+        Some(i) => &after_fence[i + 1..],
+        None => return String::new(),
+    };
+    match body.find("```") {
+        Some(end) => body[..end].strip_suffix('\n').unwrap_or(&body[..end]).to_string(),
+        // Still streaming: hold back a partial closing fence
+        None => body.trim_end_matches('`').to_string(),
+    }
+}
+
+fn bracket_balance(text: &str) -> [i32; 3] {
+    let mut depth = [0; 3];
+    for c in text.chars() {
+        match c {
+            '(' => depth[0] += 1,
+            ')' => depth[0] -= 1,
+            '[' => depth[1] += 1,
+            ']' => depth[1] -= 1,
+            '{' => depth[2] += 1,
+            '}' => depth[2] -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+/// Adjusts the replacement to the selection and returns validation warnings.
+fn validate(original: &str, mut replacement: String) -> (String, Vec<String>) {
+    let mut warnings = Vec::new();
+    if replacement.trim().is_empty() && !original.trim().is_empty() {
+        warnings.push("The replacement removes the whole selection".to_string());
+    }
+
+    // Keep the selection's trailing newline so the following line does not get joined
+    if original.ends_with('\n') && !replacement.ends_with('\n') && !replacement.is_empty() {
+        replacement.push('\n');
+    } else if !original.ends_with('\n') && replacement.ends_with('\n') {
+        replacement.pop();
+    }
+
+    // The first selected line usually starts mid-line, so models tend to re-add its indentation
+    let original_indent = original.len() - original.trim_start_matches([' ', '\t']).len();
+    if original_indent == 0 && !original.starts_with('\n') {
+        let trimmed = replacement.trim_start_matches([' ', '\t']);
+        if trimmed.len() != replacement.len() && !original.is_empty() {
+            replacement = trimmed.to_string();
+        }
+    }
+
+    if bracket_balance(original) != bracket_balance(&replacement) {
+        warnings.push("Bracket balance differs from the original selection".to_string());
+    }
+    if replacement == original {
+        warnings.push("The model returned the selection unchanged".to_string());
+    }
+    (replacement, warnings)
+}
+
+/// Line diff between the selection and its replacement, numbered within the document.
+fn line_diff(original: &str, replacement: &str, first_line: u32) -> Vec<DiffLine> {
+    let old: Vec<&str> = original.lines().collect();
+    let new: Vec<&str> = replacement.lines().collect();
+    let line = |line_type: &str, content: &str, old_no: Option<usize>, new_no: Option<usize>| DiffLine {
+        line_type: line_type.to_string(),
+        content: format!("{}\n", content),
+        old_line_no: old_no.map(|n| first_line + n as u32 + 1),
+        new_line_no: new_no.map(|n| first_line + n as u32 + 1),
+    };
+
+    if old.len() * new.len() > MAX_DIFF_CELLS {
+        return old.iter().enumerate().map(|(i, l)| line("delete", l, Some(i), None))
+            .chain(new.iter().enumerate().map(|(j, l)| line("add", l, None, Some(j))))
+            .collect();
+    }
+
+    // Longest common subsequence table, filled from the end
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut diff = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push(line("context", old[i], Some(i), Some(j)));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            diff.push(line("add", new[j], None, Some(j)));
+            j += 1;
+        } else {
+            diff.push(line("delete", old[i], Some(i), None));
+            i += 1;
+        }
+    }
+    diff
+}
+
+fn emit(sink: &dyn AgentEventSink, edit_id: &str, event_type: &str, payload: serde_json::Value) -> Result<(), String> {
+    sink.emit_event(serde_json::json!({
+        "type": event_type,
+        "editId": edit_id,
+        "payload": payload
+    }))
+}
+
+/// Rewrites the selected range according to the instruction with a single model turn.
+///
+/// Emits `edit-chunk` events holding the replacement streamed so far; the
+/// returned result is validated and carries a line diff. Nothing is written to disk.
+pub async fn edit_selection(
+    sink: &dyn AgentEventSink,
+    provider: &dyn ModelProvider,
+    request: EditSelectionRequest,
+) -> Result<EditSelectionResult, String> {
+    if request.instruction.trim().is_empty() {
+        return Err("Instruction is empty".to_string());
+    }
+    let text = match request.content {
+        Some(content) => content,
+        None => std::fs::read_to_string(&request.file_path).map_err(|e| format!("Failed to read {}: {}", request.file_path, e))?,
+    };
+
+    let start = offset_at(&text, request.start_line, request.start_character).ok_or("Selection start is outside the document")?;
+    let end = offset_at(&text, request.end_line, request.end_character).ok_or("Selection end is outside the document")?;
+    if start > end {
+        return Err("Selection start is after its end".to_string());
+    }
+
+    let original = &text[start..end];
+    let before = last_lines(&text[..start], CONTEXT_LINES_BEFORE);
+    let after = first_lines(&text[end..], CONTEXT_LINES_AFTER);
+    let language = Path::new(&request.file_path).extension().and_then(|e| e.to_str()).unwrap_or("");
+
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: "You edit code. Rewrite the text between <selection> and </selection> according to the instruction. \
+                      Reply with only the replacement for the selection in a single fenced code block: no explanation, \
+                      and do not repeat the code before or after the selection. Keep the surrounding indentation style.".to_string(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!(
+                "File: {}\n```{}\n{}<selection>{}</selection>{}\n```\n\nInstruction: {}",
+                request.file_path, language, before, original, after, request.instruction.trim()
+            ),
+        },
+    ];
+
+    let edit_id = request.edit_id.clone().unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let mut streamed = String::new();
+    let reply = provider.stream_turn(&request.model, &messages, &mut |delta| {
+        let StreamDelta::Content(chunk) = delta else { return Ok(()) };
+        streamed.push_str(chunk);
+        emit(sink, &edit_id, "edit-chunk", serde_json::json!({ "text": extract_code(&streamed) }))
+    }).await?;
+
+    let (replacement, warnings) = validate(original, extract_code(&reply));
+    let diff = line_diff(original, &replacement, request.start_line);
+    let new_content = format!("{}{}{}", &text[..start], replacement, &text[end..]);
+
+    Ok(EditSelectionResult {
+        edit_id,
+        original: original.to_string(),
+        replacement,
+        new_content,
+        diff,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::agent::replay::{RecordedTurn, Recording, ReplayProvider};
+
+    struct Events(Mutex<Vec<serde_json::Value>>);
+
+    impl AgentEventSink for Events {
+        fn emit_event(&self, event: serde_json::Value) -> Result<(), String> {
+            self.0.lock().unwrap().push(event);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn offsets_follow_lines_and_chars() {
+        let text = "fn a() {\n    é = 1;\n}\n";
+        assert_eq!(offset_at(text, 0, 0), Some(0));
+        assert_eq!(offset_at(text, 1, 4), Some(13));
+        assert_eq!(offset_at(text, 1, 5), Some(15));
+        assert_eq!(offset_at(text, 1, 10), Some(text.find(";\n").unwrap() + 1));
+        assert_eq!(offset_at(text, 5, 0), None);
+    }
+
+    #[test]
+    fn extracts_partial_fences() {
+        assert_eq!(extract_code("```rust\nlet x = 1;\n```"), "let x = 1;");
+        assert_eq!(extract_code("```rust\nlet x = 1;\n`"), "let x = 1;\n");
+        assert_eq!(extract_code("```rust"), "");
+    }
+
+    #[test]
+    fn diff_marks_changed_lines() {
+        let diff = line_diff("a\nb\nc\n", "a\nB\nc\n", 10);
+        let types: Vec<&str> = diff.iter().map(|l| l.line_type.as_str()).collect();
+        assert_eq!(types, ["context", "add", "delete", "context"]);
+        assert_eq!(diff[1].new_line_no, Some(12));
+    }
+
+    #[tokio::test]
+    async fn streams_and_validates_replacement() {
+        let provider = Arc::new(ReplayProvider::new(Recording {
+            model: None,
+            turns: vec![RecordedTurn {
+                chunks: vec!["```rust\nlet total = ".into(), "a + b;\n```".into()],
+                prompt: None,
+                reasoning: Vec::new(),
+            }],
+        }));
+        let sink = Events(Mutex::new(Vec::new()));
+        let request = EditSelectionRequest {
+            file_path: "src/lib.rs".into(),
+            start_line: 1,
+            start_character: 4,
+            end_line: 1,
+            end_character: 20,
+            instruction: "rename sum to total".into(),
+            model: "replay".into(),
+            content: Some("fn f() {\n    let sum = a + b;\n}\n".into()),
+            edit_id: Some("e1".into()),
+        };
+
+        let result = edit_selection(&sink, provider.as_ref(), request).await.unwrap();
+        assert_eq!(result.original, "let sum = a + b;");
+        assert_eq!(result.new_content, "fn f() {\n    let total = a + b;\n}\n");
+        assert!(result.warnings.is_empty());
+        assert_eq!(sink.0.lock().unwrap().len(), 2);
+    }
+}
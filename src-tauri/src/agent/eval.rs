@@ -0,0 +1,201 @@
+//! Replay-based evaluation of the agent loop.
+//!
+//! Each case lives in `tests/fixtures/agent/<case>/`:
+//! - `workspace/`: files copied into a scratch directory the run operates on
+//! - `replay.json`: the recorded model turns (see `replay::Recording`)
+//! - `expect.json`: assertions on tool calls, completion and resulting files
+//!
+//! New cases can be captured from a real model with `cognitive agent --record`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use serde::Deserialize;
+use crate::agent::openai::ChatMessage;
+use crate::agent::rag::RagEngine;
+use crate::agent::replay::{Recording, ReplayProvider};
+use crate::agent::runner::{AgentEventSink, AgentRun, ApprovalPolicy, DEFAULT_MAX_ITERATIONS};
+use crate::mcp::McpManager;
+use crate::settings::WebFetchSettings;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Expectation {
+    prompt: String,
+    #[serde(default = "default_true")]
+    completed: bool,
+    #[serde(default)]
+    iterations: Option<usize>,
+    /// Expected tool calls in order; parameters are matched as a subset.
+    #[serde(default)]
+    tool_calls: Option<Vec<ExpectedCall>>,
+    /// Workspace-relative path -> exact content after the run.
+    #[serde(default)]
+    files: HashMap<String, String>,
+    #[serde(default)]
+    final_contains: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExpectedCall {
+    name: String,
+    #[serde(default)]
+    parameters: serde_json::Map<String, serde_json::Value>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Default)]
+struct CollectingSink {
+    events: Mutex<Vec<serde_json::Value>>,
+}
+
+impl AgentEventSink for CollectingSink {
+    fn emit_event(&self, event: serde_json::Value) -> Result<(), String> {
+        self.events.lock().unwrap().push(event);
+        Ok(())
+    }
+}
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("agent")
+}
+
+fn copy_workspace(from: &Path, to: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(to).map_err(|e| e.to_string())?;
+    if !from.exists() {
+        return Ok(());
+    }
+    for entry in walkdir::WalkDir::new(from).into_iter().filter_map(|e| e.ok()) {
+        let relative = entry.path().strip_prefix(from).map_err(|e| e.to_string())?;
+        let target = to.join(relative);
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target).map_err(|e| e.to_string())?;
+        } else {
+            std::fs::copy(entry.path(), &target).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let data = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    serde_json::from_str(&data).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Runs one fixture case and returns every failed assertion.
+async fn run_case(case_dir: &Path) -> Result<Vec<String>, String> {
+    let expect: Expectation = read_json(&case_dir.join("expect.json"))?;
+    let recording = Recording::load(&case_dir.join("replay.json"))?;
+    let provider = Arc::new(ReplayProvider::new(recording));
+
+    let workspace = std::env::temp_dir().join(format!("cognitive-agent-eval-{}", uuid::Uuid::new_v4()));
+    copy_workspace(&case_dir.join("workspace"), &workspace)?;
+
+    let sink = CollectingSink::default();
+    let run = AgentRun {
+        sink: &sink,
+        provider: provider.clone(),
+        model: "replay".to_string(),
+        workspace_path: Some(workspace.clone()),
+        rag_engine: Arc::new(RagEngine::new()),
+        mcp: Arc::new(McpManager::new()),
+        approval: ApprovalPolicy::RejectAll,
+        max_iterations: DEFAULT_MAX_ITERATIONS,
+        web_fetch: WebFetchSettings { offline: true, ..WebFetchSettings::default() },
+        trace_dir: None,
+        git_writes: false,
+    };
+    let outcome = run.run(vec![ChatMessage {
+        role: "user".to_string(),
+        content: expect.prompt.clone(),
+    }]).await;
+
+    let mut failures = Vec::new();
+    match &outcome {
+        Ok(outcome) => {
+            if outcome.completed != expect.completed {
+                failures.push(format!("completed: expected {}, got {}", expect.completed, outcome.completed));
+            }
+            if let Some(iterations) = expect.iterations {
+                if outcome.iterations != iterations {
+                    failures.push(format!("iterations: expected {}, got {}", iterations, outcome.iterations));
+                }
+            }
+            if let Some(needle) = &expect.final_contains {
+                let last = outcome.final_response.as_deref().unwrap_or("");
+                if !last.contains(needle.as_str()) {
+                    failures.push(format!("final response does not contain {:?}", needle));
+                }
+            }
+        }
+        Err(e) => failures.push(format!("run failed: {}", e)),
+    }
+
+    if let Some(expected_calls) = &expect.tool_calls {
+        let events = sink.events.lock().unwrap();
+        let actual: Vec<&serde_json::Value> = events.iter()
+            .filter(|e| e["type"] == "agent-tool-start" && e.get("runId").is_none())
+            .map(|e| &e["payload"])
+            .collect();
+
+        if actual.len() != expected_calls.len() {
+            let names: Vec<&str> = actual.iter().filter_map(|p| p["name"].as_str()).collect();
+            failures.push(format!("expected {} tool calls, got {}: {:?}", expected_calls.len(), actual.len(), names));
+        }
+        for (i, (expected, payload)) in expected_calls.iter().zip(actual.iter()).enumerate() {
+            if payload["name"] != expected.name.as_str() {
+                failures.push(format!("tool call {}: expected {}, got {}", i, expected.name, payload["name"]));
+                continue;
+            }
+            for (key, value) in &expected.parameters {
+                if payload["parameters"].get(key) != Some(value) {
+                    failures.push(format!("tool call {} ({}): parameter {} expected {}, got {}", i, expected.name, key, value, payload["parameters"][key]));
+                }
+            }
+        }
+    }
+
+    for (path, expected) in &expect.files {
+        match std::fs::read_to_string(workspace.join(path)) {
+            Ok(actual) if &actual == expected => {}
+            Ok(actual) => failures.push(format!("file {}: expected {:?}, got {:?}", path, expected, actual)),
+            Err(e) => failures.push(format!("file {}: {}", path, e)),
+        }
+    }
+
+    let _ = std::fs::remove_dir_all(&workspace);
+    Ok(failures)
+}
+
+#[tokio::test]
+async fn agent_fixtures() {
+    let mut cases: Vec<PathBuf> = std::fs::read_dir(fixtures_dir())
+        .expect("agent fixtures directory")
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.join("expect.json").exists())
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "no agent fixtures found");
+
+    let mut report = Vec::new();
+    for case in &cases {
+        let name = case.file_name().unwrap().to_string_lossy().to_string();
+        match run_case(case).await {
+            Ok(failures) if failures.is_empty() => {}
+            Ok(failures) => report.push(format!("{}:\n  {}", name, failures.join("\n  "))),
+            Err(e) => report.push(format!("{}: {}", name, e)),
+        }
+    }
+    assert!(report.is_empty(), "agent fixture failures:\n{}", report.join("\n"));
+}
+
+#[tokio::test]
+async fn replay_exhaustion_is_an_error() {
+    let provider = ReplayProvider::new(Recording::default());
+    let result = crate::agent::provider::ModelProvider::stream_turn(&provider, "replay", &[], &mut |_| Ok(())).await;
+    assert!(result.is_err());
+}
@@ -0,0 +1,768 @@
+//! The `fetch_url` tool: downloads a page, reduces it to compact markdown and
+//! caches the result under `.cognitive/web-cache/` so repeated lookups and
+//! offline runs do not need the network.
+
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::settings::WebFetchSettings;
+
+const MAX_BODY_BYTES: usize = 5 * 1024 * 1024;
+const MAX_MARKDOWN_CHARS: usize = 60000;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+const MAX_REDIRECTS: usize = 5;
+
+/// Elements whose content is never useful to the model.
+const SKIPPED_TAGS: &[&str] = &["script", "style", "noscript", "svg", "nav", "footer", "aside", "form", "button", "iframe", "template", "select"];
+const BLOCK_TAGS: &[&str] = &["p", "div", "section", "article", "main", "header", "table", "blockquote", "figure", "details", "summary", "dl"];
+const LINE_TAGS: &[&str] = &["tr", "dt", "dd", "caption"];
+
+lazy_static::lazy_static! {
+    static ref HREF_RE: regex::Regex = regex::Regex::new(r#"(?i)\bhref\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#).unwrap();
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchedPage {
+    pub url: String,
+    pub title: Option<String>,
+    pub markdown: String,
+    pub fetched_at: chrono::DateTime<chrono::Utc>,
+    /// Served from the cache rather than the network.
+    #[serde(skip)]
+    pub from_cache: bool,
+}
+
+impl FetchedPage {
+    /// Renders the page as a tool result.
+    pub fn to_tool_output(&self) -> String {
+        let mut out = String::new();
+        if let Some(title) = &self.title {
+            out.push_str(&format!("# {}\n", title));
+        }
+        let source = if self.from_cache { "cached" } else { "fetched" };
+        out.push_str(&format!("Source: {} ({} {})\n\n", self.url, source, self.fetched_at.to_rfc3339()));
+        out.push_str(&self.markdown);
+        out
+    }
+}
+
+/// Whether `host` is one of `allowed` or a subdomain of one. An empty list allows everything.
+pub fn domain_allowed(host: &str, allowed: &[String]) -> bool {
+    allowed.is_empty() || allowed.iter().any(|domain| domain.trim() == "*") || explicitly_listed(host, allowed)
+}
+
+/// Whether `host` matches an entry of `allowed` by name, ignoring the `*` wildcard.
+fn explicitly_listed(host: &str, allowed: &[String]) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    allowed.iter().any(|domain| {
+        let domain = domain.trim().trim_start_matches("*.").trim_end_matches('.').to_ascii_lowercase();
+        domain != "*" && (host == domain || host.ends_with(&format!(".{}", domain)))
+    })
+}
+
+/// Loopback, private, link-local and other addresses that only exist inside
+/// the user's network (or the cloud metadata service at 169.254.169.254).
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast()
+                || (a == 100 && (b & 0xc0) == 64)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_internal(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                ip.is_loopback() || ip.is_unspecified() || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80
+            }
+        },
+    }
+}
+
+/// Whether `url` names an internal address directly (an IP literal or `localhost`)
+/// that the allow-list does not list by name.
+fn internal_host(url: &Url, allowed: &[String]) -> bool {
+    let Some(host) = url.host_str() else { return false };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let internal = match host.parse::<IpAddr>() {
+        Ok(ip) => is_internal(ip),
+        Err(_) => {
+            let host = host.trim_end_matches('.').to_ascii_lowercase();
+            host == "localhost" || host.ends_with(".localhost")
+        }
+    };
+    internal && !explicitly_listed(host, allowed)
+}
+
+/// Resolves names for the fetch client and refuses any that land on an
+/// internal address, so neither a DNS answer nor a redirect can point a fetch
+/// back into the local network unless the allow-list names the host.
+struct PublicResolver {
+    allowed: Vec<String>,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let listed = explicitly_listed(&host, &self.allowed);
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if !listed && addrs.iter().any(|addr| is_internal(addr.ip())) {
+                return Err(format!("{} resolves to a private address; add it to the allowed domains to fetch it", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Formats `err` with its sources, which carry the actual reason behind reqwest's summary.
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(&format!(": {}", err));
+        source = err.source();
+    }
+    message
+}
+
+pub struct WebFetcher {
+    cache_dir: PathBuf,
+    settings: WebFetchSettings,
+}
+
+impl WebFetcher {
+    pub fn new(cache_dir: PathBuf, settings: WebFetchSettings) -> Self {
+        Self { cache_dir, settings }
+    }
+
+    /// Caches under the workspace, or the user cache directory without one.
+    pub fn for_workspace(workspace: Option<&Path>, settings: WebFetchSettings) -> Self {
+        let cache_dir = match workspace {
+            Some(workspace) => workspace.join(".cognitive").join("web-cache"),
+            None => dirs::cache_dir().unwrap_or_else(std::env::temp_dir).join("cognitive").join("web-cache"),
+        };
+        Self::new(cache_dir, settings)
+    }
+
+    fn cache_path(&self, url: &Url) -> PathBuf {
+        let key = Sha256::digest(url.as_str().as_bytes());
+        self.cache_dir.join(format!("{:x}.json", key))
+    }
+
+    fn read_cache(&self, url: &Url) -> Option<FetchedPage> {
+        let data = std::fs::read_to_string(self.cache_path(url)).ok()?;
+        let mut page: FetchedPage = serde_json::from_str(&data).ok()?;
+        page.from_cache = true;
+        Some(page)
+    }
+
+    fn write_cache(&self, url: &Url, page: &FetchedPage) -> Result<(), String> {
+        std::fs::create_dir_all(&self.cache_dir).map_err(|e| format!("Failed to create {}: {}", self.cache_dir.display(), e))?;
+        let data = serde_json::to_string(page).map_err(|e| e.to_string())?;
+        std::fs::write(self.cache_path(url), data).map_err(|e| e.to_string())
+    }
+
+    fn check_url(&self, url: &Url) -> Result<(), String> {
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(format!("Only http and https URLs can be fetched, got {}", url));
+        }
+        let host = url.host_str().ok_or_else(|| format!("URL has no host: {}", url))?;
+        if !domain_allowed(host, &self.settings.allowed_domains) {
+            return Err(format!("{} is not in the allowed domains ({})", host, self.settings.allowed_domains.join(", ")));
+        }
+        if internal_host(url, &self.settings.allowed_domains) {
+            return Err(format!("{} is a private address; add it to the allowed domains to fetch it", host));
+        }
+        Ok(())
+    }
+
+    /// Returns the page at `url` as markdown, from the cache when it is fresh
+    /// (or always in offline mode) unless `refresh` is set.
+    pub async fn fetch(&self, url: &str, refresh: bool) -> Result<FetchedPage, String> {
+        let mut url = Url::parse(url.trim()).map_err(|e| format!("Invalid URL {}: {}", url, e))?;
+        url.set_fragment(None);
+        self.check_url(&url)?;
+
+        let cached = self.read_cache(&url);
+        if self.settings.offline {
+            return cached.ok_or_else(|| format!("Offline mode: {} is not in the fetch cache", url));
+        }
+        if let Some(page) = cached {
+            let age = chrono::Utc::now().signed_duration_since(page.fetched_at);
+            if !refresh && age.num_seconds() >= 0 && (age.num_seconds() as u64) < self.settings.cache_ttl_hours.saturating_mul(3600) {
+                return Ok(page);
+            }
+        }
+
+        let page = self.download(&url).await?;
+        // The page is already in hand; a cache that cannot be written only costs a refetch.
+        let _ = self.write_cache(&url, &page);
+        Ok(page)
+    }
+
+    async fn download(&self, url: &Url) -> Result<FetchedPage, String> {
+        let allowed = self.settings.allowed_domains.clone();
+        let redirect = reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if !attempt.url().host_str().is_some_and(|host| domain_allowed(host, &allowed)) {
+                attempt.error("redirect to a domain outside the allow-list")
+            } else if internal_host(attempt.url(), &allowed) {
+                attempt.error("redirect to a private address")
+            } else {
+                attempt.follow()
+            }
+        });
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .redirect(redirect)
+            .dns_resolver(Arc::new(PublicResolver { allowed: self.settings.allowed_domains.clone() }))
+            .user_agent(concat!("Cognitive/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let mut response = client.get(url.clone())
+            .header("Accept", "text/html, text/markdown;q=0.9, text/plain;q=0.8, */*;q=0.5")
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch {}: {}", url, error_chain(&e)))?;
+        if !response.status().is_success() {
+            return Err(format!("Failed to fetch {}: HTTP {}", url, response.status()));
+        }
+
+        let final_url = response.url().clone();
+        let content_type = response.headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        if !content_type.is_empty() && !content_type.starts_with("text/") && !content_type.contains("json") && !content_type.contains("xml") {
+            return Err(format!("Unsupported content type for {}: {}", url, content_type));
+        }
+
+        let mut body = Vec::new();
+        while let Some(bytes) = response.chunk().await.map_err(|e| e.to_string())? {
+            body.extend_from_slice(&bytes);
+            if body.len() > MAX_BODY_BYTES {
+                return Err(format!("{} is larger than {} MB", url, MAX_BODY_BYTES / (1024 * 1024)));
+            }
+        }
+        let body = String::from_utf8_lossy(&body);
+
+        let (title, markdown) = if content_type.contains("html") || (content_type.is_empty() && body.trim_start().starts_with('<')) {
+            html_to_markdown(&body, Some(&final_url))
+        } else {
+            (None, body.trim().to_string())
+        };
+
+        Ok(FetchedPage {
+            url: final_url.to_string(),
+            title,
+            markdown: truncate_chars(markdown, MAX_MARKDOWN_CHARS),
+            fetched_at: chrono::Utc::now(),
+            from_cache: false,
+        })
+    }
+}
+
+fn truncate_chars(text: String, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((idx, _)) => format!("{}\n\n... (truncated)", &text[..idx]),
+        None => text,
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('&') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" | "#39" => Some('\''),
+                "nbsp" => Some(' '),
+                "mdash" => Some('—'),
+                "ndash" => Some('–'),
+                "hellip" => Some('…'),
+                "copy" => Some('©'),
+                _ => entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Incremental HTML to markdown writer driven by `html_to_markdown`.
+struct MarkdownWriter<'a> {
+    out: String,
+    base: Option<&'a Url>,
+    title: Option<String>,
+    in_title: bool,
+    /// Tag being skipped and how deeply it is nested.
+    skipping: Option<(String, usize)>,
+    pre_depth: usize,
+    /// Open links: output position of the link text and the resolved target.
+    links: Vec<(usize, Option<String>)>,
+    /// Open lists: `Some(next number)` for ordered lists.
+    lists: Vec<Option<usize>>,
+}
+
+impl<'a> MarkdownWriter<'a> {
+    fn trim_trailing_spaces(&mut self) {
+        let len = self.out.trim_end_matches([' ', '\t']).len();
+        self.out.truncate(len);
+    }
+
+    fn block(&mut self) {
+        self.trim_trailing_spaces();
+        if self.out.is_empty() {
+            return;
+        }
+        while !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn line_break(&mut self) {
+        self.trim_trailing_spaces();
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+    }
+
+    fn text(&mut self, raw: &str) {
+        if self.skipping.is_some() {
+            return;
+        }
+        let text = decode_entities(raw);
+        if self.in_title {
+            let title = self.title.get_or_insert_with(String::new);
+            title.push_str(text.split_whitespace().collect::<Vec<_>>().join(" ").as_str());
+            return;
+        }
+        if self.pre_depth > 0 {
+            self.out.push_str(&text);
+            return;
+        }
+
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if words.is_empty() {
+            if !text.is_empty() && !self.out.ends_with([' ', '\n']) && !self.out.is_empty() {
+                self.out.push(' ');
+            }
+            return;
+        }
+        if text.starts_with(char::is_whitespace) && !self.out.ends_with([' ', '\n']) && !self.out.is_empty() {
+            self.out.push(' ');
+        }
+        self.out.push_str(&words.join(" "));
+        if text.ends_with(char::is_whitespace) {
+            self.out.push(' ');
+        }
+    }
+
+    fn resolve(&self, href: &str) -> Option<String> {
+        let href = decode_entities(href.trim());
+        if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") || href.starts_with("mailto:") {
+            return None;
+        }
+        match self.base {
+            Some(base) => base.join(&href).ok().map(|u| u.to_string()),
+            None => Some(href),
+        }
+    }
+
+    fn open(&mut self, name: &str, attrs: &str) {
+        if let Some((tag, depth)) = &mut self.skipping {
+            if tag == name {
+                *depth += 1;
+            }
+            return;
+        }
+        if SKIPPED_TAGS.contains(&name) {
+            self.skipping = Some((name.to_string(), 1));
+            return;
+        }
+
+        match name {
+            "title" => self.in_title = self.title.is_none(),
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.block();
+                let level = name[1..].parse::<usize>().unwrap_or(1);
+                self.out.push_str(&"#".repeat(level));
+                self.out.push(' ');
+            }
+            "br" => self.line_break(),
+            "hr" => {
+                self.block();
+                self.out.push_str("---");
+                self.block();
+            }
+            "ul" | "ol" => {
+                self.line_break();
+                self.lists.push(if name == "ol" { Some(1) } else { None });
+            }
+            "li" => {
+                self.line_break();
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.out.push_str(&indent);
+                self.out.push_str(&marker);
+            }
+            "pre" => {
+                self.block();
+                self.out.push_str("```\n");
+                self.pre_depth += 1;
+            }
+            "code" if self.pre_depth == 0 => self.out.push('`'),
+            "strong" | "b" => self.out.push_str("**"),
+            "em" | "i" => self.out.push('_'),
+            "td" | "th" if !self.out.ends_with('\n') && !self.out.is_empty() => self.out.push_str(" | "),
+            "a" => {
+                let href = HREF_RE.captures(attrs)
+                    .and_then(|c| c.get(1).or_else(|| c.get(2)).or_else(|| c.get(3)))
+                    .and_then(|m| self.resolve(m.as_str()));
+                self.links.push((self.out.len(), href));
+            }
+            _ if BLOCK_TAGS.contains(&name) => self.block(),
+            _ if LINE_TAGS.contains(&name) => self.line_break(),
+            _ => {}
+        }
+    }
+
+    fn close(&mut self, name: &str) {
+        if let Some((tag, depth)) = &mut self.skipping {
+            if tag == name {
+                *depth -= 1;
+                if *depth == 0 {
+                    self.skipping = None;
+                }
+            }
+            return;
+        }
+
+        match name {
+            "title" => self.in_title = false,
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => self.block(),
+            "ul" | "ol" => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.block();
+                } else {
+                    self.line_break();
+                }
+            }
+            "pre" if self.pre_depth > 0 => {
+                self.pre_depth -= 1;
+                if !self.out.ends_with('\n') {
+                    self.out.push('\n');
+                }
+                self.out.push_str("```");
+                self.block();
+            }
+            "code" if self.pre_depth == 0 => self.out.push('`'),
+            "strong" | "b" => self.out.push_str("**"),
+            "em" | "i" => self.out.push('_'),
+            "a" => {
+                if let Some((start, Some(href))) = self.links.pop() {
+                    let text = self.out[start..].trim().to_string();
+                    if !text.is_empty() {
+                        self.out.truncate(start);
+                        if text.starts_with(char::is_whitespace) || start == 0 || self.out.ends_with([' ', '\n']) {
+                            self.out.push_str(&format!("[{}]({})", text, href));
+                        } else {
+                            self.out.push_str(&format!(" [{}]({})", text, href));
+                        }
+                    }
+                }
+            }
+            _ if BLOCK_TAGS.contains(&name) => self.block(),
+            _ if LINE_TAGS.contains(&name) => self.line_break(),
+            _ => {}
+        }
+    }
+
+    fn finish(self) -> (Option<String>, String) {
+        let mut out = String::with_capacity(self.out.len());
+        let mut blank_lines = 0;
+        let mut in_fence = false;
+        for line in self.out.lines() {
+            let line = if in_fence { line } else { line.trim_end() };
+            if line.starts_with("```") {
+                in_fence = !in_fence;
+            }
+            if line.trim().is_empty() && !in_fence {
+                blank_lines += 1;
+                if blank_lines > 1 {
+                    continue;
+                }
+            } else {
+                blank_lines = 0;
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+        let title = self.title.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+        (title, out.trim().to_string())
+    }
+}
+
+/// Converts an HTML document to compact markdown, returning the `<title>` separately.
+///
+/// This is a forgiving tag scanner rather than a full HTML parser: navigation,
+/// scripts and other chrome are dropped, headings, lists, links, emphasis and
+/// code blocks are kept. Relative links are resolved against `base`.
+pub fn html_to_markdown(html: &str, base: Option<&Url>) -> (Option<String>, String) {
+    let mut writer = MarkdownWriter {
+        out: String::new(),
+        base,
+        title: None,
+        in_title: false,
+        skipping: None,
+        pre_depth: 0,
+        links: Vec::new(),
+        lists: Vec::new(),
+    };
+
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            writer.text(rest);
+            break;
+        };
+        writer.text(&rest[..lt]);
+        rest = &rest[lt..];
+
+        if rest.starts_with("<!--") {
+            rest = rest.find("-->").map(|end| &rest[end + 3..]).unwrap_or("");
+            continue;
+        }
+
+        // Find the end of the tag, ignoring '>' inside quoted attribute values.
+        let mut quote = None;
+        let mut end = None;
+        for (i, c) in rest.char_indices().skip(1) {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '"') | (None, '\'') => quote = Some(c),
+                (None, '>') => {
+                    end = Some(i);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let Some(end) = end else {
+            writer.text(rest);
+            break;
+        };
+
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('!') || tag.starts_with('?') {
+            continue;
+        }
+
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let name_end = tag.find(|c: char| c.is_whitespace() || c == '/').unwrap_or(tag.len());
+        let name = tag[..name_end].to_ascii_lowercase();
+        if name.is_empty() {
+            continue;
+        }
+
+        if closing {
+            writer.close(&name);
+        } else {
+            writer.open(&name, &tag[name_end..]);
+            // Raw text elements: their content is not markup.
+            if name == "script" || name == "style" {
+                let close = format!("</{}", name);
+                let lower = rest.to_ascii_lowercase();
+                let skip_to = lower.find(&close).unwrap_or(rest.len());
+                if writer.skipping.is_none() {
+                    writer.text(&rest[..skip_to]);
+                }
+                rest = &rest[skip_to..];
+            }
+        }
+    }
+
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const PAGE: &str = r#"<!DOCTYPE html>
+<html><head><title>Guide &amp; Reference</title><style>body { color: red }</style></head>
+<body>
+<nav><a href="/">Home</a></nav>
+<main>
+<h1>Getting started</h1>
+<p>Install the <code>cli</code> and read <a href="/docs/config.html">the config page</a>.</p>
+<ul><li>fast</li><li><strong>small</strong></li></ul>
+<pre><code>cargo add demo
+demo --help</code></pre>
+<script>console.log("<p>hidden</p>")</script>
+</main>
+<footer>Copyright</footer>
+</body></html>"#;
+
+    /// Serves `PAGE` for every request and counts them.
+    async fn serve() -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut buf = [0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    PAGE.len(),
+                    PAGE
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (format!("http://{}", addr), hits)
+    }
+
+    /// Settings that let the tests reach the local server.
+    fn local_settings() -> WebFetchSettings {
+        WebFetchSettings { allowed_domains: vec!["127.0.0.1".to_string()], ..WebFetchSettings::default() }
+    }
+
+    fn scratch_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("cognitive-fetch-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn converts_html_to_markdown() {
+        let base = Url::parse("https://example.com/guide/").unwrap();
+        let (title, markdown) = html_to_markdown(PAGE, Some(&base));
+        assert_eq!(title.as_deref(), Some("Guide & Reference"));
+        assert_eq!(markdown, "# Getting started\n\nInstall the `cli` and read [the config page](https://example.com/docs/config.html).\n\n- fast\n- **small**\n\n```\ncargo add demo\ndemo --help\n```");
+    }
+
+    #[test]
+    fn allow_list_matches_subdomains_only() {
+        let allowed = vec!["docs.rs".to_string()];
+        assert!(domain_allowed("docs.rs", &allowed));
+        assert!(domain_allowed("api.docs.rs", &allowed));
+        assert!(!domain_allowed("evildocs.rs", &allowed));
+        assert!(domain_allowed("anything.example", &[]));
+        assert!(domain_allowed("anything.example", &["*".to_string()]));
+        assert!(!explicitly_listed("anything.example", &["*".to_string()]));
+    }
+
+    #[test]
+    fn recognizes_internal_addresses() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1"] {
+            assert!(is_internal(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["8.8.8.8", "172.32.0.1", "2606:4700::1111"] {
+            assert!(!is_internal(ip.parse().unwrap()), "{}", ip);
+        }
+        let url = |s: &str| Url::parse(s).unwrap();
+        assert!(internal_host(&url("http://localhost:8080/"), &[]));
+        assert!(internal_host(&url("http://[::1]/"), &[]));
+        assert!(!internal_host(&url("http://127.0.0.1/"), &["127.0.0.1".to_string()]));
+        assert!(!internal_host(&url("https://docs.rs/"), &[]));
+    }
+
+    #[tokio::test]
+    async fn rejects_private_addresses_unless_listed() {
+        let (base, hits) = serve().await;
+        let fetcher = WebFetcher::new(scratch_dir(), WebFetchSettings::default());
+        let err = fetcher.fetch(&base, false).await.unwrap_err();
+        assert!(err.contains("private address"));
+
+        let port = base.rsplit(':').next().unwrap();
+        let fetcher = WebFetcher::new(scratch_dir(), WebFetchSettings { allowed_domains: vec!["*".to_string()], ..WebFetchSettings::default() });
+        assert!(fetcher.fetch(&format!("http://localhost:{}", port), false).await.unwrap_err().contains("private address"));
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn caches_pages_and_serves_them_offline() {
+        let (base, hits) = serve().await;
+        let cache = scratch_dir();
+        let url = format!("{}/guide", base);
+
+        let fetcher = WebFetcher::new(cache.clone(), local_settings());
+        let page = fetcher.fetch(&url, false).await.unwrap();
+        assert!(!page.from_cache);
+        assert!(page.markdown.starts_with("# Getting started"));
+        assert!(page.markdown.contains(&format!("({}/docs/config.html)", base)));
+
+        let again = fetcher.fetch(&url, false).await.unwrap();
+        assert!(again.from_cache);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let offline = WebFetcher::new(cache.clone(), WebFetchSettings { offline: true, ..local_settings() });
+        assert!(offline.fetch(&url, false).await.unwrap().from_cache);
+        assert!(offline.fetch(&format!("{}/other", base), false).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let _ = std::fs::remove_dir_all(&cache);
+    }
+
+    #[tokio::test]
+    async fn rejects_domains_outside_the_allow_list() {
+        let (base, hits) = serve().await;
+        let fetcher = WebFetcher::new(scratch_dir(), WebFetchSettings {
+            allowed_domains: vec!["docs.rs".to_string()],
+            ..WebFetchSettings::default()
+        });
+        let err = fetcher.fetch(&base, false).await.unwrap_err();
+        assert!(err.contains("not in the allowed domains"));
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+    }
+}
@@ -2,10 +2,21 @@ use serde::{Deserialize, Serialize};
 use reqwest::Client;
 use futures_util::StreamExt;
 use std::error::Error;
+use crate::agent::openai::StreamChunk;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GeminiPart {
+    #[serde(default)]
     pub text: String,
+    /// Set on response parts that carry the model's thought summary.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thought: Option<bool>,
+}
+
+impl GeminiPart {
+    pub fn text(text: String) -> Self {
+        Self { text, thought: None }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -31,6 +42,29 @@ pub struct GeminiConfig {
     pub temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking_config: Option<GeminiThinkingConfig>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiThinkingConfig {
+    pub include_thoughts: bool,
+}
+
+/// Whether the model accepts `thinkingConfig`; older models reject the field.
+pub fn supports_thinking(model: &str) -> bool {
+    model.contains("2.5") || model.contains("thinking") || model.contains("gemini-3")
+}
+
+fn collect_parts(parts: Vec<GeminiPart>, out: &mut StreamChunk) {
+    for part in parts {
+        if part.thought.unwrap_or(false) {
+            out.reasoning.push_str(&part.text);
+        } else {
+            out.content.push_str(&part.text);
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -62,7 +96,7 @@ impl GeminiClient {
         &self,
         model: &str,
         request: GeminiRequest,
-    ) -> Result<impl futures_util::Stream<Item = Result<String, Box<dyn Error + Send + Sync>>>, Box<dyn Error + Send + Sync>> {
+    ) -> Result<impl futures_util::Stream<Item = Result<StreamChunk, Box<dyn Error + Send + Sync>>>, Box<dyn Error + Send + Sync>> {
         let url = format!(
             "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?key={}",
             model, self.api_key
@@ -84,7 +118,7 @@ impl GeminiClient {
             match item {
                 Ok(bytes) => {
                     buffer.extend_from_slice(&bytes);
-                    let mut content = String::new();
+                    let mut content = StreamChunk::default();
                     
                     let text = String::from_utf8_lossy(&buffer).to_string();
                     
@@ -124,18 +158,14 @@ impl GeminiClient {
                                         // Try to parse as single chunk
                                         if let Ok(chunk) = serde_json::from_str::<GeminiResponseChunk>(json_str) {
                                             for candidate in chunk.candidates {
-                                                for part in candidate.content.parts {
-                                                    content.push_str(&part.text);
-                                                }
+                                                collect_parts(candidate.content.parts, &mut content);
                                             }
                                         } 
                                         // Try to parse as array of chunks
                                         else if let Ok(chunks) = serde_json::from_str::<Vec<GeminiResponseChunk>>(json_str) {
                                             for chunk in chunks {
                                                 for candidate in chunk.candidates {
-                                                    for part in candidate.content.parts {
-                                                        content.push_str(&part.text);
-                                                    }
+                                                    collect_parts(candidate.content.parts, &mut content);
                                                 }
                                             }
                                         }
@@ -163,3 +193,17 @@ impl GeminiClient {
         Ok(stream)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thought_parts_are_routed_to_reasoning() {
+        let parts: Vec<GeminiPart> = serde_json::from_str(r#"[{"text":"Check the caller first.","thought":true},{"text":"It returns None."}]"#).unwrap();
+        let mut chunk = StreamChunk::default();
+        collect_parts(parts, &mut chunk);
+        assert_eq!(chunk.reasoning, "Check the caller first.");
+        assert_eq!(chunk.content, "It returns None.");
+    }
+}
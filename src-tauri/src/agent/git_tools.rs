@@ -0,0 +1,375 @@
+//! Git tools for the agent: read-only inspection of status, diffs, history and
+//! blame, plus staging and committing when the user has enabled them.
+//!
+//! Paths are taken relative to the workspace, which may be a subdirectory of
+//! the repository. Output is plain text sized for the model's context.
+
+use std::path::{Component, Path, PathBuf};
+use git2::{BlameOptions, DiffFormat, DiffOptions, Repository};
+use crate::git;
+
+/// Tools that only read from the repository; also available to sub-agents.
+pub const GIT_READ_TOOLS: &[&str] = &["git_status", "git_diff", "git_log", "git_blame", "git_show"];
+/// Tools that change the index or history; opt-in and always behind approval.
+pub const GIT_WRITE_TOOLS: &[&str] = &["git_stage", "git_commit"];
+
+const MAX_OUTPUT_CHARS: usize = 30000;
+const DEFAULT_LOG_COUNT: usize = 20;
+const MAX_LOG_COUNT: usize = 200;
+/// Commits inspected when filtering the log by path.
+const MAX_LOG_SCAN: usize = 5000;
+const DEFAULT_BLAME_LINES: usize = 100;
+const MAX_BLAME_LINES: usize = 400;
+
+struct Repo {
+    repo: Repository,
+    root: PathBuf,
+    workspace: PathBuf,
+}
+
+impl Repo {
+    fn open(workspace: &Path) -> Result<Self, String> {
+        let repo = Repository::discover(workspace).map_err(|e| format!("Not a git repository: {}", e.message()))?;
+        let root = repo.workdir().ok_or("Bare repositories are not supported")?;
+        let root = root.canonicalize().map_err(|e| e.to_string())?;
+        let workspace = workspace.canonicalize().map_err(|e| e.to_string())?;
+        Ok(Self { repo, root, workspace })
+    }
+
+    /// Repository-relative form of a workspace-relative or absolute path.
+    fn relative(&self, path: &str) -> Result<String, String> {
+        let joined = self.workspace.join(path);
+        let mut normalized = PathBuf::new();
+        for component in joined.components() {
+            match component {
+                Component::ParentDir => {
+                    normalized.pop();
+                }
+                Component::CurDir => {}
+                other => normalized.push(other),
+            }
+        }
+        let relative = normalized.strip_prefix(&self.root)
+            .map_err(|_| format!("{} is outside the repository", path))?;
+        Ok(relative.to_string_lossy().replace('\\', "/"))
+    }
+
+    fn root_str(&self) -> String {
+        self.root.to_string_lossy().to_string()
+    }
+
+    fn tree(&self, rev: &str) -> Result<git2::Tree<'_>, String> {
+        self.repo.revparse_single(rev)
+            .and_then(|o| o.peel_to_tree())
+            .map_err(|e| format!("Unknown revision {}: {}", rev, e.message()))
+    }
+
+    fn head_tree(&self) -> Option<git2::Tree<'_>> {
+        self.repo.head().ok().and_then(|h| h.peel_to_tree().ok())
+    }
+}
+
+fn truncate(mut text: String) -> String {
+    if let Some((idx, _)) = text.char_indices().nth(MAX_OUTPUT_CHARS) {
+        let omitted = text[idx..].lines().count();
+        text.truncate(idx);
+        text.push_str(&format!("\n... (output truncated, {} more lines)", omitted));
+    }
+    text
+}
+
+fn format_time(time: git2::Time) -> String {
+    chrono::DateTime::from_timestamp(time.seconds(), 0)
+        .map(|t| t.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+fn short(oid: git2::Oid) -> String {
+    oid.to_string()[..7].to_string()
+}
+
+fn render_patch(diff: &git2::Diff) -> Result<String, String> {
+    let stats = diff.stats().map_err(|e| e.to_string())?;
+    let mut out = format!("{} file(s) changed, +{} -{}\n\n", stats.files_changed(), stats.insertions(), stats.deletions());
+    diff.print(DiffFormat::Patch, |_delta, _hunk, line| {
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            out.push(line.origin());
+        }
+        out.push_str(&String::from_utf8_lossy(line.content()));
+        true
+    }).map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+fn diff_options(path: Option<&str>) -> DiffOptions {
+    let mut opts = DiffOptions::new();
+    if let Some(path) = path {
+        opts.pathspec(path);
+    }
+    opts
+}
+
+/// Changed, staged and untracked files.
+pub fn status(workspace: &Path) -> Result<String, String> {
+    let repo = Repo::open(workspace)?;
+    let branch = repo.repo.head().ok()
+        .and_then(|h| h.shorthand().map(|s| s.to_string()))
+        .unwrap_or_else(|| "(no branch)".to_string());
+
+    let entries: Vec<_> = git::git_status(repo.root_str())?
+        .into_iter()
+        .filter(|e| !e.is_ignored && e.status != "ignored")
+        .collect();
+    let mut out = format!("On branch {}\n", branch);
+    if entries.is_empty() {
+        out.push_str("Working tree clean");
+    }
+    for entry in entries {
+        out.push_str(&format!("{:<16} {}\n", entry.status, entry.path));
+    }
+    Ok(out)
+}
+
+/// Unified diff of the working tree, the index, or between two revisions.
+///
+/// With `from` and `to` the revisions are compared; with only `from` the
+/// working tree is compared against it. Otherwise `staged` selects the index
+/// against HEAD, or the working tree against the index.
+pub fn diff(workspace: &Path, path: Option<&str>, staged: bool, from: Option<&str>, to: Option<&str>) -> Result<String, String> {
+    let repo = Repo::open(workspace)?;
+    let path = path.map(|p| repo.relative(p)).transpose()?;
+    let mut opts = diff_options(path.as_deref());
+
+    let diff = match (from, to) {
+        (Some(from), Some(to)) => repo.repo.diff_tree_to_tree(Some(&repo.tree(from)?), Some(&repo.tree(to)?), Some(&mut opts)),
+        (Some(from), None) => repo.repo.diff_tree_to_workdir_with_index(Some(&repo.tree(from)?), Some(&mut opts)),
+        (None, Some(_)) => return Err("`to` requires `from`".to_string()),
+        (None, None) if staged => repo.repo.diff_tree_to_index(repo.head_tree().as_ref(), None, Some(&mut opts)),
+        (None, None) => {
+            opts.include_untracked(true).recurse_untracked_dirs(true).show_untracked_content(true);
+            repo.repo.diff_index_to_workdir(None, Some(&mut opts))
+        }
+    }.map_err(|e| e.to_string())?;
+
+    if diff.deltas().len() == 0 {
+        return Ok("No changes".to_string());
+    }
+    render_patch(&diff).map(truncate)
+}
+
+/// Whether `commit` changed `path` relative to its first parent.
+fn touches(repo: &Repository, commit: &git2::Commit, path: &str) -> bool {
+    let tree = match commit.tree() {
+        Ok(tree) => tree,
+        Err(_) => return false,
+    };
+    let parent = commit.parent(0).ok().and_then(|p| p.tree().ok());
+    let mut opts = diff_options(Some(path));
+    repo.diff_tree_to_tree(parent.as_ref(), Some(&tree), Some(&mut opts))
+        .map(|d| d.deltas().len() > 0)
+        .unwrap_or(false)
+}
+
+/// Commit history, newest first, optionally limited to commits touching `path`.
+pub fn log(workspace: &Path, path: Option<&str>, max_count: Option<usize>, rev: Option<&str>) -> Result<String, String> {
+    let repo = Repo::open(workspace)?;
+    let path = path.map(|p| repo.relative(p)).transpose()?;
+    let max = max_count.unwrap_or(DEFAULT_LOG_COUNT).clamp(1, MAX_LOG_COUNT);
+
+    let mut revwalk = repo.repo.revwalk().map_err(|e| e.to_string())?;
+    revwalk.set_sorting(git2::Sort::TIME).map_err(|e| e.to_string())?;
+    match rev {
+        Some(rev) => {
+            let oid = repo.repo.revparse_single(rev).map_err(|e| format!("Unknown revision {}: {}", rev, e.message()))?.id();
+            revwalk.push(oid)
+        }
+        None => revwalk.push_head(),
+    }.map_err(|e| e.to_string())?;
+
+    let mut lines = Vec::new();
+    for oid in revwalk.take(MAX_LOG_SCAN).filter_map(|o| o.ok()) {
+        let commit = match repo.repo.find_commit(oid) {
+            Ok(commit) => commit,
+            Err(_) => continue,
+        };
+        if let Some(path) = &path {
+            if !touches(&repo.repo, &commit, path) {
+                continue;
+            }
+        }
+        let author = commit.author();
+        lines.push(format!(
+            "{} {} {}: {}",
+            short(oid),
+            format_time(commit.time()),
+            author.name().unwrap_or("Unknown"),
+            commit.summary().unwrap_or("")
+        ));
+        if lines.len() >= max {
+            break;
+        }
+    }
+
+    if lines.is_empty() {
+        return Ok("No commits found".to_string());
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Last commit to change each line of `path` in `start..=end` (1-based), working tree included.
+pub fn blame(workspace: &Path, path: &str, start: Option<usize>, end: Option<usize>) -> Result<String, String> {
+    let repo = Repo::open(workspace)?;
+    let relative = repo.relative(path)?;
+    let content = std::fs::read_to_string(repo.root.join(&relative)).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.is_empty() {
+        return Ok("(empty file)".to_string());
+    }
+
+    let start = start.unwrap_or(1).max(1);
+    if start > lines.len() {
+        return Err(format!("{} has only {} lines", path, lines.len()));
+    }
+    let end = end.unwrap_or(start + DEFAULT_BLAME_LINES - 1)
+        .min(start + MAX_BLAME_LINES - 1)
+        .min(lines.len());
+
+    let mut opts = BlameOptions::new();
+    let committed = repo.repo.blame_file(Path::new(&relative), Some(&mut opts))
+        .map_err(|e| format!("Failed to blame {}: {}", path, e.message()))?;
+    let blame = committed.blame_buffer(content.as_bytes()).map_err(|e| e.to_string())?;
+
+    let mut out = String::new();
+    for line_no in start..=end {
+        let origin = match blame.get_line(line_no) {
+            Some(hunk) if !hunk.final_commit_id().is_zero() => {
+                let signature = hunk.final_signature();
+                format!("{} {} {:<16}", short(hunk.final_commit_id()), format_time(signature.when()), signature.name().unwrap_or("Unknown"))
+            }
+            _ => format!("{:<7} {:<10} {:<16}", "0000000", "", "Not committed"),
+        };
+        out.push_str(&format!("{} {:>5}: {}\n", origin, line_no, lines[line_no - 1]));
+    }
+    Ok(truncate(out))
+}
+
+/// A commit's metadata, message and patch, optionally limited to `path`.
+pub fn show(workspace: &Path, rev: Option<&str>, path: Option<&str>) -> Result<String, String> {
+    let repo = Repo::open(workspace)?;
+    let rev = rev.unwrap_or("HEAD");
+    let path = path.map(|p| repo.relative(p)).transpose()?;
+    let commit = repo.repo.revparse_single(rev)
+        .and_then(|o| o.peel_to_commit())
+        .map_err(|e| format!("Unknown revision {}: {}", rev, e.message()))?;
+
+    let author = commit.author();
+    let mut out = format!(
+        "commit {}\nAuthor: {} <{}>\nDate:   {}\n\n",
+        commit.id(),
+        author.name().unwrap_or("Unknown"),
+        author.email().unwrap_or(""),
+        format_time(commit.time())
+    );
+    for line in commit.message().unwrap_or("").trim_end().lines() {
+        out.push_str(&format!("    {}\n", line));
+    }
+    out.push('\n');
+
+    let tree = commit.tree().map_err(|e| e.to_string())?;
+    let parent = commit.parent(0).ok().and_then(|p| p.tree().ok());
+    let mut opts = diff_options(path.as_deref());
+    let diff = repo.repo.diff_tree_to_tree(parent.as_ref(), Some(&tree), Some(&mut opts)).map_err(|e| e.to_string())?;
+    out.push_str(&render_patch(&diff)?);
+    Ok(truncate(out))
+}
+
+/// Adds `paths` to the index; deleted files are removed from it.
+pub fn stage(workspace: &Path, paths: &[String]) -> Result<String, String> {
+    if paths.is_empty() {
+        return Err("No paths to stage".to_string());
+    }
+    let repo = Repo::open(workspace)?;
+    let mut index = repo.repo.index().map_err(|e| e.to_string())?;
+    let mut staged = Vec::new();
+    for path in paths {
+        let relative = repo.relative(path)?;
+        if repo.root.join(&relative).exists() {
+            index.add_path(Path::new(&relative)).map_err(|e| format!("Failed to stage {}: {}", path, e.message()))?;
+        } else {
+            index.remove_path(Path::new(&relative)).map_err(|e| format!("Failed to stage {}: {}", path, e.message()))?;
+        }
+        staged.push(relative);
+    }
+    index.write().map_err(|e| e.to_string())?;
+    Ok(format!("Staged: {}", staged.join(", ")))
+}
+
+/// Commits the staged changes with `message`.
+pub fn commit(workspace: &Path, message: &str) -> Result<String, String> {
+    let message = message.trim();
+    if message.is_empty() {
+        return Err("Commit message must not be empty".to_string());
+    }
+    let repo = Repo::open(workspace)?;
+    let staged = repo.repo.diff_tree_to_index(repo.head_tree().as_ref(), None, None).map_err(|e| e.to_string())?;
+    if staged.deltas().len() == 0 {
+        return Err("Nothing staged to commit".to_string());
+    }
+    let hash = git::git_commit(repo.root_str(), message.to_string())?;
+    Ok(format!("Committed {}: {}", &hash[..7], message.lines().next().unwrap_or("")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit_all(repo: &Repository, message: &str) {
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = git2::Signature::now("Ada", "ada@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents).unwrap();
+    }
+
+    #[test]
+    fn inspects_history_and_changes() {
+        let dir = std::env::temp_dir().join(format!("cognitive-git-tools-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "fn a() {}\n").unwrap();
+        std::fs::write(dir.join("README.md"), "demo\n").unwrap();
+        commit_all(&repo, "Initial commit");
+        std::fs::write(dir.join("src/lib.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+        commit_all(&repo, "Add b");
+        std::fs::write(dir.join("src/lib.rs"), "fn a() {}\nfn b() {}\nfn c() {}\n").unwrap();
+
+        let workspace = dir.join("src");
+        let log = log(&workspace, Some("lib.rs"), None, None).unwrap();
+        assert_eq!(log.lines().count(), 2);
+        assert!(log.lines().next().unwrap().ends_with("Ada: Add b"));
+        assert_eq!(super::log(&dir, Some("README.md"), None, None).unwrap().lines().count(), 1);
+
+        let working = diff(&workspace, Some("lib.rs"), false, None, None).unwrap();
+        assert!(working.contains("+fn c() {}"));
+        assert_eq!(diff(&workspace, None, true, None, None).unwrap(), "No changes");
+        assert!(diff(&dir, None, false, Some("HEAD~1"), Some("HEAD")).unwrap().contains("+fn b() {}"));
+
+        let blame = blame(&workspace, "lib.rs", Some(2), Some(3)).unwrap();
+        let blame: Vec<&str> = blame.lines().collect();
+        assert!(blame[0].contains("Ada") && blame[0].ends_with("2: fn b() {}"));
+        assert!(blame[1].contains("Not committed"));
+
+        assert!(show(&dir, None, None).unwrap().contains("    Add b"));
+        assert!(diff(&dir, Some("../outside"), false, None, None).is_err());
+
+        stage(&workspace, &["lib.rs".to_string()]).unwrap();
+        assert!(status(&dir).unwrap().contains("staged_modified  src/lib.rs"));
+        assert!(commit(&dir, "Add c").unwrap().ends_with("Add c"));
+        assert!(commit(&dir, "Again").is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
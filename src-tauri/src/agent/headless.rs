@@ -0,0 +1,385 @@
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use crate::agent::openai::ChatMessage;
+use crate::agent::provider::{ModelProvider, ProviderConfig};
+use crate::agent::rag::RagEngine;
+use crate::agent::replay::{Recording, RecordingProvider, ReplayProvider};
+use crate::agent::trace::traces_dir;
+use crate::agent::runner::{AgentEventSink, AgentRun, ApprovalPolicy, DEFAULT_MAX_ITERATIONS};
+use crate::mcp::McpManager;
+use crate::settings::{SettingsStore, WebFetchSettings};
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_ERROR: i32 = 1;
+/// The iteration cap was hit before the model gave a final answer.
+pub const EXIT_INCOMPLETE: i32 = 2;
+pub const EXIT_USAGE: i32 = 64;
+
+const USAGE: &str = "Usage: cognitive agent --model <model> --prompt <text|-> [options]
+
+Options:
+  --workspace <path>      Workspace root (default: current directory)
+  --format <text|json>    Output streamed text, or one JSON event per line (default: text)
+  --max-iterations <n>    Maximum model turns (default: 10)
+  --approve-all           Approve MCP tool calls that would normally ask
+  --offline               Answer fetch_url only from the workspace cache
+  --allow-git-writes      Offer git_stage and git_commit (approved only with --approve-all)
+  --openai-key <key>      Defaults to $OPENAI_API_KEY
+  --gemini-key <key>      Defaults to $GEMINI_API_KEY
+  --base-url <url>        OpenAI-compatible base URL, defaults to $OPENAI_BASE_URL
+  --ollama-url <url>      Defaults to $OLLAMA_HOST
+  --keep-alive <dur>      How long Ollama keeps the model loaded, e.g. 10m or 0
+  --record <file>         Save every model turn to a replay file
+  --replay <file>         Answer from a replay file instead of calling a model
+
+Exit status: 0 on a final answer, 2 if the iteration cap was reached, 1 on error.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+struct HeadlessArgs {
+    workspace: PathBuf,
+    model: String,
+    prompt: String,
+    format: OutputFormat,
+    max_iterations: usize,
+    approve_all: bool,
+    offline: bool,
+    git_writes: bool,
+    provider: ProviderConfig,
+    record: Option<PathBuf>,
+    replay: Option<PathBuf>,
+}
+
+/// `None` when help was asked for.
+fn parse_args(args: &[String]) -> Result<Option<HeadlessArgs>, String> {
+    let mut workspace = None;
+    let mut model = None;
+    let mut prompt = None;
+    let mut format = OutputFormat::Text;
+    let mut max_iterations = DEFAULT_MAX_ITERATIONS;
+    let mut approve_all = false;
+    let mut offline = false;
+    let mut git_writes = false;
+    let mut record = None;
+    let mut replay = None;
+    let mut provider = ProviderConfig {
+        openai_api_key: std::env::var("OPENAI_API_KEY").ok(),
+        gemini_api_key: std::env::var("GEMINI_API_KEY").ok(),
+        base_url: std::env::var("OPENAI_BASE_URL").ok(),
+        ollama_base_url: std::env::var("OLLAMA_HOST").ok(),
+        ollama_keep_alive: None,
+        profile: None,
+    };
+
+    let mut i = 0;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        if flag == "--approve-all" {
+            approve_all = true;
+            i += 1;
+            continue;
+        }
+        if flag == "--offline" {
+            offline = true;
+            i += 1;
+            continue;
+        }
+        if flag == "--allow-git-writes" {
+            git_writes = true;
+            i += 1;
+            continue;
+        }
+        if flag == "--help" || flag == "-h" {
+            return Ok(None);
+        }
+
+        let value = args.get(i + 1).cloned().ok_or_else(|| format!("Missing value for {}", flag))?;
+        match flag {
+            "--workspace" => workspace = Some(PathBuf::from(value)),
+            "--model" => model = Some(value),
+            "--prompt" => prompt = Some(value),
+            "--format" => {
+                format = match value.as_str() {
+                    "text" => OutputFormat::Text,
+                    "json" => OutputFormat::Json,
+                    other => return Err(format!("Unknown format: {}", other)),
+                }
+            }
+            "--max-iterations" => {
+                max_iterations = value.parse::<usize>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| format!("Invalid --max-iterations: {}", value))?;
+            }
+            "--openai-key" => provider.openai_api_key = Some(value),
+            "--gemini-key" => provider.gemini_api_key = Some(value),
+            "--base-url" => provider.base_url = Some(value),
+            "--ollama-url" => provider.ollama_base_url = Some(value),
+            "--keep-alive" => provider.ollama_keep_alive = Some(serde_json::Value::String(value)),
+            "--record" => record = Some(PathBuf::from(value)),
+            "--replay" => replay = Some(PathBuf::from(value)),
+            other => return Err(format!("Unknown option: {}", other)),
+        }
+        i += 2;
+    }
+
+    if record.is_some() && replay.is_some() {
+        return Err("--record and --replay cannot be combined".to_string());
+    }
+
+    let mut prompt = prompt.ok_or("Missing --prompt")?;
+    if prompt == "-" {
+        prompt.clear();
+        std::io::stdin().read_to_string(&mut prompt).map_err(|e| format!("Failed to read prompt from stdin: {}", e))?;
+    }
+
+    let workspace = match workspace {
+        Some(path) => path,
+        None => std::env::current_dir().map_err(|e| e.to_string())?,
+    };
+    let workspace = workspace.canonicalize().map_err(|e| format!("Invalid workspace {}: {}", workspace.display(), e))?;
+
+    Ok(Some(HeadlessArgs {
+        workspace,
+        model: model.ok_or("Missing --model")?,
+        prompt,
+        format,
+        max_iterations,
+        approve_all,
+        offline,
+        git_writes,
+        provider,
+        record,
+        replay,
+    }))
+}
+
+/// `ai.webFetch` from the user and workspace settings, as the app would use them.
+fn web_fetch_settings(workspace: &std::path::Path, offline: bool) -> Result<WebFetchSettings, String> {
+    let store = SettingsStore::new();
+    // Loading writes defaults when the file is missing; CI machines keep theirs untouched
+    if store.get_user_config_path().exists() {
+        store.load_user_settings()?;
+    }
+    store.set_workspace(&workspace.to_string_lossy())?;
+    let settings = store.get_settings().ai.web_fetch;
+    Ok(WebFetchSettings { offline: offline || settings.offline, ..settings })
+}
+
+/// Prints agent events: streamed text on stdout with tool activity on stderr,
+/// or every event as a JSON line on stdout.
+struct ConsoleSink {
+    format: OutputFormat,
+}
+
+impl AgentEventSink for ConsoleSink {
+    fn emit_event(&self, event: serde_json::Value) -> Result<(), String> {
+        if self.format == OutputFormat::Json {
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "{}", event).map_err(|e| e.to_string())?;
+            return stdout.flush().map_err(|e| e.to_string());
+        }
+
+        let event_type = event.get("type").and_then(|t| t.as_str()).unwrap_or("");
+        let payload = event.get("payload").cloned().unwrap_or_default();
+        let name = payload.get("name").and_then(|n| n.as_str()).unwrap_or("?");
+        match event_type {
+            "chunk" => {
+                let mut stdout = std::io::stdout().lock();
+                write!(stdout, "{}", payload.as_str().unwrap_or("")).map_err(|e| e.to_string())?;
+                stdout.flush().map_err(|e| e.to_string())?;
+            }
+            "reasoning" => eprint!("{}", payload.as_str().unwrap_or("")),
+            "agent-tool-start" => eprintln!("\n> {} {}", name, payload.get("parameters").cloned().unwrap_or_default()),
+            "agent-tool-res" => eprintln!("< {} done", name),
+            "agent-tool-error" => eprintln!("< {} failed: {}", name, payload.get("error").and_then(|e| e.as_str()).unwrap_or("")),
+            "subagent-start" => eprintln!("> sub-agent: {}", payload.get("task").and_then(|t| t.as_str()).unwrap_or("")),
+            "agent-trace-error" => eprintln!("Agent run is not traced: {}", payload.get("error").and_then(|e| e.as_str()).unwrap_or("")),
+            "subagent-end" => eprintln!("< sub-agent {}", payload.get("status").and_then(|s| s.as_str()).unwrap_or("")),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Entry point for `cognitive agent ...`; returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let args = match parse_args(args) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return EXIT_OK;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Failed to start runtime: {}", e);
+            return EXIT_ERROR;
+        }
+    };
+
+    let provider: Arc<dyn ModelProvider> = match (&args.replay, &args.record) {
+        (Some(path), _) => match Recording::load(path) {
+            Ok(recording) => Arc::new(ReplayProvider::new(recording)),
+            Err(e) => {
+                eprintln!("{}", e);
+                return EXIT_USAGE;
+            }
+        },
+        (None, Some(path)) => Arc::new(RecordingProvider::new(Arc::new(args.provider.clone()), path.clone(), &args.model)),
+        (None, None) => Arc::new(args.provider.clone()),
+    };
+
+    let web_fetch = match web_fetch_settings(&args.workspace, args.offline) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_ERROR;
+        }
+    };
+
+    runtime.block_on(async move {
+        let sink = ConsoleSink { format: args.format };
+        let rag_engine = Arc::new(RagEngine::new());
+        let _ = rag_engine.load_or_index(&args.workspace).await;
+        let mcp = Arc::new(McpManager::new());
+        let _ = mcp.load_workspace(&args.workspace).await;
+
+        let trace_dir = Some(traces_dir(&args.workspace));
+        let run = AgentRun {
+            sink: &sink,
+            provider,
+            model: args.model,
+            workspace_path: Some(args.workspace),
+            rag_engine,
+            mcp,
+            approval: if args.approve_all { ApprovalPolicy::ApproveAll } else { ApprovalPolicy::RejectAll },
+            max_iterations: args.max_iterations,
+            web_fetch,
+            trace_dir,
+            git_writes: args.git_writes,
+        };
+
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: args.prompt,
+        }];
+
+        let result = run.run(messages).await;
+        let code = match &result {
+            Ok(outcome) if outcome.completed => EXIT_OK,
+            Ok(_) => EXIT_INCOMPLETE,
+            Err(_) => EXIT_ERROR,
+        };
+
+        match (&result, args.format) {
+            (Ok(outcome), OutputFormat::Json) => {
+                let _ = sink.emit_event(serde_json::json!({ "type": "done", "payload": outcome, "exitCode": code }));
+            }
+            (Err(e), OutputFormat::Json) => {
+                let _ = sink.emit_event(serde_json::json!({ "type": "error", "payload": e, "exitCode": code }));
+            }
+            (Ok(outcome), OutputFormat::Text) => {
+                println!();
+                if let Some(trace_id) = &outcome.trace_id {
+                    eprintln!("Trace: {}", trace_id);
+                }
+            }
+            (Err(e), OutputFormat::Text) => eprintln!("\nError: {}", e),
+        }
+        if code == EXIT_INCOMPLETE && args.format == OutputFormat::Text {
+            eprintln!("Stopped after {} iterations without a final answer", args.max_iterations);
+        }
+        code
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn parse(list: &[&str]) -> Result<HeadlessArgs, String> {
+        parse_args(&args(list)).map(|parsed| parsed.expect("not a help request"))
+    }
+
+    #[test]
+    fn parses_every_flag() {
+        let workspace = std::env::temp_dir();
+        let parsed = parse(&[
+            "--model", "gpt-4o", "--prompt", "fix it", "--workspace", &workspace.to_string_lossy(),
+            "--format", "json", "--max-iterations", "3", "--approve-all", "--offline", "--allow-git-writes",
+            "--openai-key", "sk-test", "--gemini-key", "g-test", "--base-url", "http://localhost:8000/v1",
+            "--ollama-url", "http://localhost:11434", "--keep-alive", "0", "--record", "run.json",
+        ]).unwrap();
+        assert_eq!(parsed.workspace, workspace.canonicalize().unwrap());
+        assert_eq!(parsed.model, "gpt-4o");
+        assert_eq!(parsed.prompt, "fix it");
+        assert_eq!(parsed.format, OutputFormat::Json);
+        assert_eq!(parsed.max_iterations, 3);
+        assert!(parsed.approve_all && parsed.offline && parsed.git_writes);
+        assert_eq!(parsed.provider.openai_api_key.as_deref(), Some("sk-test"));
+        assert_eq!(parsed.provider.gemini_api_key.as_deref(), Some("g-test"));
+        assert_eq!(parsed.provider.base_url.as_deref(), Some("http://localhost:8000/v1"));
+        assert_eq!(parsed.provider.ollama_base_url.as_deref(), Some("http://localhost:11434"));
+        assert_eq!(parsed.provider.ollama_keep_alive, Some(serde_json::json!("0")));
+        assert_eq!(parsed.record, Some(PathBuf::from("run.json")));
+        assert_eq!(parsed.replay, None);
+
+        let defaults = parse(&["--model", "llama3", "--prompt", "hi"]).unwrap();
+        assert_eq!(defaults.workspace, std::env::current_dir().unwrap().canonicalize().unwrap());
+        assert_eq!(defaults.format, OutputFormat::Text);
+        assert_eq!(defaults.max_iterations, DEFAULT_MAX_ITERATIONS);
+        assert!(!defaults.approve_all && !defaults.offline && !defaults.git_writes);
+    }
+
+    #[test]
+    fn rejects_invalid_usage() {
+        let cases: &[(&[&str], &str)] = &[
+            (&["--prompt", "hi"], "Missing --model"),
+            (&["--model", "m"], "Missing --prompt"),
+            (&["--model", "m", "--prompt"], "Missing value for --prompt"),
+            (&["--model", "m", "--prompt", "hi", "--verbose", "1"], "Unknown option: --verbose"),
+            (&["--model", "m", "--prompt", "hi", "--format", "xml"], "Unknown format: xml"),
+            (&["--model", "m", "--prompt", "hi", "--max-iterations", "0"], "Invalid --max-iterations: 0"),
+            (&["--model", "m", "--prompt", "hi", "--max-iterations", "many"], "Invalid --max-iterations: many"),
+            (&["--model", "m", "--prompt", "hi", "--record", "a", "--replay", "b"], "--record and --replay cannot be combined"),
+        ];
+        for (list, expected) in cases {
+            assert_eq!(parse(list).unwrap_err(), *expected, "{:?}", list);
+        }
+        let missing = std::env::temp_dir().join(format!("cognitive-missing-{}", uuid::Uuid::new_v4()));
+        let err = parse(&["--model", "m", "--prompt", "hi", "--workspace", &missing.to_string_lossy()]).unwrap_err();
+        assert!(err.starts_with("Invalid workspace"));
+
+        assert!(parse_args(&args(&["--help"])).unwrap().is_none());
+        assert!(parse_args(&args(&["--model", "m", "-h"])).unwrap().is_none());
+    }
+
+    #[test]
+    fn exits_with_usage_status_unless_help_is_asked_for() {
+        assert_eq!(run(&args(&["--model", "m"])), EXIT_USAGE);
+        assert_eq!(run(&args(&["--help"])), EXIT_OK);
+
+        let missing = std::env::temp_dir().join(format!("cognitive-missing-{}.json", uuid::Uuid::new_v4()));
+        let workspace = std::env::temp_dir();
+        assert_eq!(run(&args(&[
+            "--model", "m", "--prompt", "hi", "--workspace", &workspace.to_string_lossy(), "--replay", &missing.to_string_lossy(),
+        ])), EXIT_USAGE);
+    }
+}
@@ -5,5 +5,20 @@ pub mod tools;
 pub mod commands;
 pub mod rag;
 pub mod system_prompt;
+pub mod provider;
+pub mod subagent;
+pub mod approval;
+pub mod runner;
+pub mod headless;
+pub mod replay;
+pub mod completion;
+pub mod commit_message;
+pub mod review;
+pub mod edit;
+pub mod fetch;
+pub mod git_tools;
+pub mod trace;
+#[cfg(test)]
+mod eval;
 
 pub use commands::*;
@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
 use futures_util::StreamExt;
+use std::collections::HashMap;
 use std::error::Error;
-use crate::agent::openai::ChatMessage;
+use std::sync::Mutex;
+use crate::agent::openai::{ChatMessage, StreamChunk};
 
 #[derive(Debug, Serialize)]
 pub struct OllamaChatRequest {
@@ -11,6 +13,13 @@ pub struct OllamaChatRequest {
     pub stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub options: Option<OllamaOptions>,
+    /// How long the model stays loaded after this request: a duration such as
+    /// `"10m"`, seconds as a number, `0` to unload right away or `-1` to keep it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<serde_json::Value>,
+    /// Ask thinking models to return their reasoning in `message.thinking`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub think: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
@@ -19,6 +28,28 @@ pub struct OllamaOptions {
     pub temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub num_predict: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+}
+
+/// `/api/generate` request. With `suffix` set, Ollama applies the model's
+/// fill-in-the-middle template.
+#[derive(Debug, Serialize)]
+pub struct OllamaGenerateRequest {
+    pub model: String,
+    pub prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<OllamaOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OllamaGenerateResponse {
+    pub response: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -30,7 +61,10 @@ pub struct OllamaChatResponseChunk {
 
 #[derive(Debug, Deserialize)]
 pub struct OllamaMessage {
+    #[serde(default)]
     pub content: String,
+    #[serde(default)]
+    pub thinking: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -46,6 +80,97 @@ pub struct OllamaModel {
     pub digest: String,
 }
 
+/// One line of `/api/pull` progress.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OllamaPullProgress {
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaPullLine {
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(flatten)]
+    progress: Option<OllamaPullProgress>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct OllamaModelDetails {
+    #[serde(default)]
+    pub parameters: String,
+    #[serde(default)]
+    pub template: String,
+    #[serde(default)]
+    pub system: String,
+    #[serde(default)]
+    pub license: String,
+    #[serde(default)]
+    pub details: serde_json::Value,
+    #[serde(default)]
+    pub model_info: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Filled in from `model_info` (`<arch>.context_length`) or a `num_ctx` parameter.
+    #[serde(default)]
+    pub context_length: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaRunningList {
+    models: Vec<OllamaRunningModel>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OllamaRunningModel {
+    pub name: String,
+    pub model: String,
+    pub size: u64,
+    #[serde(default)]
+    pub size_vram: u64,
+    pub digest: String,
+    pub expires_at: String,
+}
+
+/// Context length from `show` output: the architecture's trained length, overridden by `num_ctx`.
+fn context_length(parameters: &str, model_info: &serde_json::Map<String, serde_json::Value>) -> Option<u64> {
+    let configured = parameters.lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some("num_ctx"), Some(value)) => value.parse::<u64>().ok(),
+                _ => None,
+            }
+        })
+        .next_back();
+    configured.or_else(|| model_info.iter()
+        .find(|(key, _)| key.ends_with(".context_length"))
+        .and_then(|(_, value)| value.as_u64()))
+}
+
+/// Decodes one NDJSON line of an `/api/chat` stream into `out`.
+fn parse_chat_line(line: &str, out: &mut StreamChunk) {
+    if let Ok(chunk) = serde_json::from_str::<OllamaChatResponseChunk>(line) {
+        if let Some(message) = chunk.message {
+            out.content.push_str(&message.content);
+            if let Some(thinking) = message.thinking {
+                out.reasoning.push_str(&thinking);
+            }
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    /// `show` capabilities by server URL and model; dropped when the model is pulled or deleted.
+    static ref CAPABILITIES: Mutex<HashMap<(String, String), Vec<String>>> = Mutex::new(HashMap::new());
+}
+
 pub struct OllamaClient {
     base_url: String,
     client: Client,
@@ -103,10 +228,157 @@ impl OllamaClient {
         Ok(model_list.models)
     }
 
+    async fn check_status(response: reqwest::Response, url: &str) -> Result<reqwest::Response, Box<dyn Error + Send + Sync>> {
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        Err(format!("Ollama API error: {} (Status: {}, URL: {})", error_text, status, url).into())
+    }
+
+    fn forget_capabilities(&self, name: &str) {
+        CAPABILITIES.lock().unwrap().remove(&(self.base_url.clone(), name.to_string()));
+    }
+
+    /// What the model supports (`completion`, `insert`, `thinking`, `tools`, ...),
+    /// asked once per server and model. Empty when unknown: older servers do not
+    /// report capabilities, and a failed `show` is remembered the same way so it
+    /// is not retried on every request.
+    pub async fn capabilities(&self, name: &str) -> Vec<String> {
+        let key = (self.base_url.clone(), name.to_string());
+        if let Some(capabilities) = CAPABILITIES.lock().unwrap().get(&key) {
+            return capabilities.clone();
+        }
+        let capabilities = self.show_model(name).await.map(|info| info.capabilities).unwrap_or_default();
+        CAPABILITIES.lock().unwrap().insert(key, capabilities.clone());
+        capabilities
+    }
+
+    /// `think` for a chat request: asked of models that can think, left out otherwise
+    /// since other models reject it.
+    pub async fn think_for(&self, name: &str) -> Option<bool> {
+        self.capabilities(name).await.iter().any(|c| c == "thinking").then_some(true)
+    }
+
+    /// Streams `/api/pull` progress until the model is downloaded.
+    pub async fn pull_model(
+        &self,
+        name: &str,
+    ) -> Result<impl futures_util::Stream<Item = Result<Vec<OllamaPullProgress>, Box<dyn Error + Send + Sync>>>, Box<dyn Error + Send + Sync>> {
+        self.forget_capabilities(name);
+        let url = format!("{}/api/pull", self.base_url);
+        let response = self.client
+            .post(&url)
+            .json(&serde_json::json!({ "model": name, "stream": true }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to connect to Ollama at {}: {}", url, e))?;
+        let response = Self::check_status(response, &url).await?;
+
+        let mut buffer = Vec::new();
+        let stream = response.bytes_stream().map(move |item| -> Result<Vec<OllamaPullProgress>, Box<dyn Error + Send + Sync>> {
+            let bytes = item.map_err(|e| Box::new(e) as Box<dyn Error + Send + Sync>)?;
+            buffer.extend_from_slice(&bytes);
+            let mut updates = Vec::new();
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line_bytes = buffer.drain(..pos + 1).collect::<Vec<u8>>();
+                let line = String::from_utf8_lossy(&line_bytes);
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<OllamaPullLine>(line) {
+                    Ok(OllamaPullLine { error: Some(error), .. }) => return Err(format!("Ollama pull failed: {}", error).into()),
+                    Ok(OllamaPullLine { progress: Some(progress), .. }) => updates.push(progress),
+                    _ => {}
+                }
+            }
+            Ok(updates)
+        });
+
+        Ok(stream)
+    }
+
+    pub async fn delete_model(&self, name: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.forget_capabilities(name);
+        let url = format!("{}/api/delete", self.base_url);
+        let response = self.client
+            .delete(&url)
+            .json(&serde_json::json!({ "model": name }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to connect to Ollama at {}: {}", url, e))?;
+        Self::check_status(response, &url).await?;
+        Ok(())
+    }
+
+    pub async fn show_model(&self, name: &str) -> Result<OllamaModelDetails, Box<dyn Error + Send + Sync>> {
+        let url = format!("{}/api/show", self.base_url);
+        let response = self.client
+            .post(&url)
+            .json(&serde_json::json!({ "model": name }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to connect to Ollama at {}: {}", url, e))?;
+        let response = Self::check_status(response, &url).await?;
+
+        let mut details: OllamaModelDetails = response.json().await.map_err(|e| {
+            format!("Failed to parse Ollama show response: {}. URL: {}", e, url)
+        })?;
+        details.context_length = context_length(&details.parameters, &details.model_info);
+        Ok(details)
+    }
+
+    /// Models currently loaded in memory (`/api/ps`).
+    pub async fn running_models(&self) -> Result<Vec<OllamaRunningModel>, Box<dyn Error + Send + Sync>> {
+        let url = format!("{}/api/ps", self.base_url);
+        let response = self.client.get(&url).send().await.map_err(|e| {
+            format!("Failed to connect to Ollama at {}: {}", url, e)
+        })?;
+        let response = Self::check_status(response, &url).await?;
+
+        let list: OllamaRunningList = response.json().await.map_err(|e| {
+            format!("Failed to parse Ollama ps response: {}. URL: {}", e, url)
+        })?;
+        Ok(list.models)
+    }
+
+    /// Loads or unloads a model without generating anything.
+    pub async fn set_keep_alive(&self, name: &str, keep_alive: serde_json::Value) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.generate(OllamaGenerateRequest {
+            model: name.to_string(),
+            prompt: String::new(),
+            suffix: None,
+            stream: false,
+            options: None,
+            keep_alive: Some(keep_alive),
+        }).await?;
+        Ok(())
+    }
+
+    pub async fn generate(&self, request: OllamaGenerateRequest) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let url = format!("{}/api/generate", self.base_url);
+        let response = self.client.post(&url).json(&request).send().await.map_err(|e| {
+            format!("Failed to connect to Ollama at {}: {}", url, e)
+        })?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("Ollama API error: {} (Status: {}, URL: {})", error_text, status, url).into());
+        }
+
+        let body: OllamaGenerateResponse = response.json().await.map_err(|e| {
+            format!("Failed to parse Ollama generate response: {}. URL: {}", e, url)
+        })?;
+        Ok(body.response)
+    }
+
     pub async fn chat_stream(
         &self,
         request: OllamaChatRequest,
-    ) -> Result<impl futures_util::Stream<Item = Result<String, Box<dyn Error + Send + Sync>>>, Box<dyn Error + Send + Sync>> {
+    ) -> Result<impl futures_util::Stream<Item = Result<StreamChunk, Box<dyn Error + Send + Sync>>>, Box<dyn Error + Send + Sync>> {
         let url = format!("{}/api/chat", self.base_url);
         
         let response = self.client
@@ -128,7 +400,7 @@ impl OllamaClient {
             match item {
                 Ok(bytes) => {
                     buffer.extend_from_slice(&bytes);
-                    let mut content = String::new();
+                    let mut content = StreamChunk::default();
                     
                     loop {
                         let line_end = buffer.iter().position(|&b| b == b'\n');
@@ -137,13 +409,7 @@ impl OllamaClient {
                             let line_str = String::from_utf8_lossy(&line_bytes);
                             let line = line_str.trim();
                             
-                            if !line.is_empty() {
-                                if let Ok(chunk) = serde_json::from_str::<OllamaChatResponseChunk>(line) {
-                                    if let Some(message) = chunk.message {
-                                        content.push_str(&message.content);
-                                    }
-                                }
-                            }
+                            parse_chat_line(line, &mut content);
                         } else {
                             break;
                         }
@@ -157,3 +423,39 @@ impl OllamaClient {
         Ok(stream)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_length_prefers_num_ctx() {
+        let mut info = serde_json::Map::new();
+        info.insert("llama.context_length".to_string(), serde_json::json!(131072));
+        assert_eq!(context_length("stop \"<|eot|>\"", &info), Some(131072));
+        assert_eq!(context_length("num_ctx                        8192\nstop x", &info), Some(8192));
+        assert_eq!(context_length("", &serde_json::Map::new()), None);
+    }
+
+    #[test]
+    fn thinking_is_read_apart_from_the_answer() {
+        let mut out = StreamChunk::default();
+        for line in [
+            r#"{"model":"qwen3","message":{"role":"assistant","content":"","thinking":"Two plus two"},"done":false}"#,
+            r#"{"model":"qwen3","message":{"role":"assistant","content":"4"},"done":false}"#,
+            r#"{"model":"qwen3","done":true,"done_reason":"stop"}"#,
+        ] {
+            parse_chat_line(line, &mut out);
+        }
+        assert_eq!(out.reasoning, "Two plus two");
+        assert_eq!(out.content, "4");
+    }
+
+    #[test]
+    fn pull_lines_carry_errors_or_progress() {
+        let line: OllamaPullLine = serde_json::from_str(r#"{"status":"pulling abc","digest":"sha256:abc","total":100,"completed":40}"#).unwrap();
+        assert_eq!(line.progress.unwrap().completed, Some(40));
+        let line: OllamaPullLine = serde_json::from_str(r#"{"error":"pull model manifest: file does not exist"}"#).unwrap();
+        assert!(line.error.is_some());
+    }
+}
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
 use futures_util::StreamExt;
+use std::collections::HashMap;
 use std::error::Error;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -35,11 +36,65 @@ pub struct ChoiceChunk {
 #[derive(Debug, Deserialize)]
 pub struct Delta {
     pub content: Option<String>,
+    /// Reasoning from DeepSeek and vLLM.
+    #[serde(default)]
+    pub reasoning_content: Option<String>,
+    /// Reasoning from OpenRouter, which may also send `reasoning_content`.
+    #[serde(default)]
+    pub reasoning: Option<String>,
+}
+
+impl Delta {
+    /// Reasoning text from either field, once when both carry the same text.
+    fn reasoning_text(&self) -> Option<String> {
+        match (&self.reasoning_content, &self.reasoning) {
+            (Some(a), Some(b)) if a == b => Some(a.clone()),
+            (Some(a), Some(b)) => Some(format!("{}{}", a, b)),
+            (a, b) => a.clone().or_else(|| b.clone()),
+        }
+    }
+}
+
+/// Decodes one SSE line of a chat completion stream into `out`.
+fn parse_stream_line(line: &str, out: &mut StreamChunk) {
+    let Some(data) = line.strip_prefix("data: ") else { return };
+    if data == "[DONE]" {
+        return;
+    }
+    if let Ok(chunk) = serde_json::from_str::<ChatResponseChunk>(data) {
+        if let Some(choice) = chunk.choices.first() {
+            if let Some(delta_content) = &choice.delta.content {
+                out.content.push_str(delta_content);
+            }
+            if let Some(reasoning) = choice.delta.reasoning_text() {
+                out.reasoning.push_str(&reasoning);
+            }
+        }
+    }
+}
+
+/// One decoded piece of a streamed response. Reasoning is kept apart from the
+/// answer so it can be shown without being sent back to the model.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamChunk {
+    pub content: String,
+    pub reasoning: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelListResponse {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelEntry {
+    id: String,
 }
 
 pub struct OpenAIClient {
     api_key: String,
     base_url: String,
+    headers: HashMap<String, String>,
     client: Client,
 }
 
@@ -47,20 +102,55 @@ impl OpenAIClient {
     pub fn new(api_key: String, base_url: Option<String>) -> Self {
         Self {
             api_key,
-            base_url: base_url.unwrap_or_else(|| "https://api.openai.com/v1".to_string()),
+            base_url: base_url
+                .map(|url| url.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| "https://api.openai.com/v1".to_string()),
+            headers: HashMap::new(),
             client: Client::new(),
         }
     }
 
+    /// Extra headers sent with every request, e.g. for gateways that need their own auth.
+    pub fn with_headers(mut self, headers: HashMap<String, String>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    fn authorized(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        // Local servers often run without a key; don't send an empty bearer token
+        let mut request = if self.api_key.is_empty() {
+            request
+        } else {
+            request.header("Authorization", format!("Bearer {}", self.api_key))
+        };
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        request
+    }
+
+    /// Model ids from the `/models` endpoint.
+    pub async fn list_models(&self) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let url = format!("{}/models", self.base_url);
+        let response = self.authorized(self.client.get(&url)).send().await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await?;
+            return Err(format!("OpenAI API error: {} (Status: {}, URL: {})", error_text, status, url).into());
+        }
+
+        let list: ModelListResponse = response.json().await?;
+        Ok(list.data.into_iter().map(|m| m.id).collect())
+    }
+
     pub async fn chat_stream(
         &self,
         request: ChatRequest,
-    ) -> Result<impl futures_util::Stream<Item = Result<String, Box<dyn Error + Send + Sync>>>, Box<dyn Error + Send + Sync>> {
+    ) -> Result<impl futures_util::Stream<Item = Result<StreamChunk, Box<dyn Error + Send + Sync>>>, Box<dyn Error + Send + Sync>> {
         let url = format!("{}/chat/completions", self.base_url);
         
-        let response = self.client
-            .post(url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
+        let response = self.authorized(self.client.post(url))
             .json(&request)
             .send()
             .await?;
@@ -75,7 +165,7 @@ impl OpenAIClient {
             match item {
                 Ok(bytes) => {
                     buffer.extend_from_slice(&bytes);
-                    let mut content = String::new();
+                    let mut content = StreamChunk::default();
                     
                     loop {
                         let line_end = buffer.iter().position(|&b| b == b'\n');
@@ -84,20 +174,7 @@ impl OpenAIClient {
                             let line_str = String::from_utf8_lossy(&line_bytes);
                             let line = line_str.trim();
                             
-                            if line.starts_with("data: ") {
-                                let data = &line[6..];
-                                if data == "[DONE]" {
-                                    continue;
-                                }
-                                
-                                if let Ok(chunk) = serde_json::from_str::<ChatResponseChunk>(data) {
-                                    if let Some(choice) = chunk.choices.get(0) {
-                                        if let Some(delta_content) = &choice.delta.content {
-                                            content.push_str(delta_content);
-                                        }
-                                    }
-                                }
-                            }
+                            parse_stream_line(line, &mut content);
                         } else {
                             break;
                         }
@@ -111,3 +188,31 @@ impl OpenAIClient {
         Ok(stream)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(lines: &[&str]) -> StreamChunk {
+        let mut out = StreamChunk::default();
+        for line in lines {
+            parse_stream_line(line, &mut out);
+        }
+        out
+    }
+
+    #[test]
+    fn reasoning_is_read_from_either_field() {
+        let chunk = parse(&[
+            r#"data: {"choices":[{"delta":{"reasoning_content":"Let me "},"finish_reason":null}]}"#,
+            r#"data: {"choices":[{"delta":{"reasoning":"think."},"finish_reason":null}]}"#,
+            r#"data: {"choices":[{"delta":{"reasoning":" Done.","reasoning_content":" Done.","content":"4"},"finish_reason":null}]}"#,
+            "data: [DONE]",
+        ]);
+        assert_eq!(chunk.reasoning, "Let me think. Done.");
+        assert_eq!(chunk.content, "4");
+
+        let chunk = parse(&[r#"data: {"choices":[{"delta":{"content":"Hi"},"finish_reason":null}]}"#, ": keep-alive"]);
+        assert_eq!(chunk, StreamChunk { content: "Hi".to_string(), reasoning: String::new() });
+    }
+}
@@ -0,0 +1,212 @@
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use crate::agent::AgentState;
+use crate::settings::ProviderProfile;
+use crate::agent::openai::{OpenAIClient, ChatRequest, ChatMessage, StreamChunk};
+use crate::agent::gemini::{self, GeminiClient, GeminiRequest, GeminiContent, GeminiPart, GeminiConfig, GeminiThinkingConfig};
+use crate::agent::ollama::{OllamaClient, OllamaChatRequest, OllamaOptions};
+
+/// Snapshot of the provider credentials held in `AgentState`, so a run does not
+/// keep the state locks while streaming.
+#[derive(Debug, Clone, Default)]
+pub struct ProviderConfig {
+    pub openai_api_key: Option<String>,
+    pub gemini_api_key: Option<String>,
+    pub base_url: Option<String>,
+    pub ollama_base_url: Option<String>,
+    /// Per-request Ollama `keep_alive`; the server default applies when unset.
+    pub ollama_keep_alive: Option<serde_json::Value>,
+    /// OpenAI-compatible profile selected for the conversation; overrides model-name routing.
+    pub profile: Option<ProviderProfile>,
+}
+
+/// The API a model is served through.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// OpenAI or an OpenAI-compatible profile.
+    OpenAi,
+    Gemini,
+    Ollama,
+}
+
+impl ProviderConfig {
+    /// A selected profile is always OpenAI-compatible; otherwise the model name decides.
+    pub fn backend(&self, model: &str) -> Backend {
+        if self.profile.is_some() || model.contains("gpt") {
+            Backend::OpenAi
+        } else if model.contains("gemini") {
+            Backend::Gemini
+        } else {
+            Backend::Ollama
+        }
+    }
+
+    pub fn from_state(state: &AgentState) -> Self {
+        Self {
+            openai_api_key: state.openai_api_key.lock().unwrap().clone(),
+            gemini_api_key: state.gemini_api_key.lock().unwrap().clone(),
+            base_url: state.base_url.lock().unwrap().clone(),
+            ollama_base_url: state.ollama_base_url.lock().unwrap().clone(),
+            ollama_keep_alive: None,
+            profile: None,
+        }
+    }
+}
+
+/// One piece of a streamed turn. Reasoning from thinking models is kept apart
+/// from the answer so it can be shown separately and left out of history.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamDelta<'a> {
+    Content(&'a str),
+    Reasoning(&'a str),
+}
+
+/// Callback receiving each delta of a streamed turn.
+pub type ChunkCallback<'a> = dyn FnMut(StreamDelta<'_>) -> Result<(), String> + Send + 'a;
+
+/// The model backend behind an agent run. Implemented by the real provider
+/// configuration and by the replay provider used in tests.
+#[async_trait]
+pub trait ModelProvider: Send + Sync {
+    /// Streams one turn for `messages` (system prompt first) and returns the full response.
+    async fn stream_turn(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        on_chunk: &mut ChunkCallback<'_>,
+    ) -> Result<String, String>;
+}
+
+#[async_trait]
+impl ModelProvider for ProviderConfig {
+    async fn stream_turn(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        on_chunk: &mut ChunkCallback<'_>,
+    ) -> Result<String, String> {
+        stream_completion(self, model, messages, |delta| on_chunk(delta)).await
+    }
+}
+
+/// Forwards a provider chunk to `on_chunk`, reasoning first, and appends the
+/// answer text to `full_response`.
+fn forward_chunk<F>(chunk: &StreamChunk, full_response: &mut String, on_chunk: &mut F) -> Result<(), String>
+where
+    F: FnMut(StreamDelta<'_>) -> Result<(), String>,
+{
+    if !chunk.reasoning.is_empty() {
+        on_chunk(StreamDelta::Reasoning(&chunk.reasoning))?;
+    }
+    if !chunk.content.is_empty() {
+        full_response.push_str(&chunk.content);
+        on_chunk(StreamDelta::Content(&chunk.content))?;
+    }
+    Ok(())
+}
+
+/// Streams a single model turn and returns the full response text.
+///
+/// `messages[0]` must be the system prompt. `on_chunk` is called for every
+/// delta as it arrives; returning an error aborts the stream. The returned
+/// text holds the answer only, without reasoning.
+pub async fn stream_completion<F>(
+    config: &ProviderConfig,
+    model: &str,
+    messages: &[ChatMessage],
+    mut on_chunk: F,
+) -> Result<String, String>
+where
+    F: FnMut(StreamDelta<'_>) -> Result<(), String>,
+{
+    let mut full_response = String::new();
+
+    let backend = config.backend(model);
+    if backend == Backend::OpenAi {
+        let client = match &config.profile {
+            Some(profile) => OpenAIClient::new(profile.resolved_api_key().unwrap_or_default(), Some(profile.base_url.clone()))
+                .with_headers(profile.headers.clone()),
+            None => {
+                let key = config.openai_api_key.clone().ok_or("OpenAI API key not configured")?;
+                OpenAIClient::new(key, config.base_url.clone())
+            }
+        };
+        let request = ChatRequest {
+            model: model.to_string(),
+            messages: messages.to_vec(),
+            stream: true,
+            temperature: None,
+            max_tokens: None,
+        };
+
+        let mut stream = client.chat_stream(request).await.map_err(|e| e.to_string())?;
+        while let Some(chunk_res) = stream.next().await {
+            match chunk_res {
+                Ok(chunk) => forward_chunk(&chunk, &mut full_response, &mut on_chunk)?,
+                Err(e) => {
+                    return Err(format!("OpenAI stream error: {}", e));
+                }
+            }
+        }
+    } else if backend == Backend::Gemini {
+        let key = config.gemini_api_key.clone().ok_or("Gemini API key not configured")?;
+        let client = GeminiClient::new(key);
+        let system_prompt = messages.first().map(|m| m.content.clone()).unwrap_or_default();
+
+        // Skip the first message (system prompt) as it is passed via system_instruction
+        let gemini_messages: Vec<GeminiContent> = messages.iter().skip(1).map(|m| GeminiContent {
+            role: if m.role == "user" { "user".to_string() } else { "model".to_string() },
+            parts: vec![GeminiPart::text(m.content.clone())],
+        }).collect();
+
+        let request = GeminiRequest {
+            contents: gemini_messages,
+            system_instruction: Some(GeminiContent {
+                role: "user".to_string(), // Role doesn't matter much for system instruction in API
+                parts: vec![GeminiPart::text(system_prompt)],
+            }),
+            generation_config: Some(GeminiConfig {
+                temperature: None,
+                max_output_tokens: None,
+                thinking_config: gemini::supports_thinking(model).then_some(GeminiThinkingConfig { include_thoughts: true }),
+            }),
+        };
+
+        let mut stream = client.chat_stream(model, request).await.map_err(|e| e.to_string())?;
+        while let Some(chunk_res) = stream.next().await {
+            match chunk_res {
+                Ok(chunk) => forward_chunk(&chunk, &mut full_response, &mut on_chunk)?,
+                Err(e) => {
+                    return Err(format!("Gemini stream error: {}", e));
+                }
+            }
+        }
+    } else {
+        // Assume Ollama
+        let client = OllamaClient::new(config.ollama_base_url.clone());
+        let request = OllamaChatRequest {
+            model: model.to_string(),
+            messages: messages.to_vec(),
+            stream: true,
+            options: Some(OllamaOptions {
+                temperature: None,
+                num_predict: None,
+                stop: None,
+            }),
+            keep_alive: config.ollama_keep_alive.clone(),
+            think: client.think_for(model).await,
+        };
+
+        let mut stream = client.chat_stream(request).await.map_err(|e| e.to_string())?;
+        while let Some(chunk_res) = stream.next().await {
+            match chunk_res {
+                Ok(chunk) => forward_chunk(&chunk, &mut full_response, &mut on_chunk)?,
+                Err(e) => {
+                    return Err(format!("Ollama stream error: {}", e));
+                }
+            }
+        }
+    }
+
+    Ok(full_response)
+}
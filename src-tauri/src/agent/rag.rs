@@ -207,6 +207,12 @@ impl RagEngine {
         }
     }
 
+    /// Symbols indexed for one file, keyed by the path as it was indexed.
+    pub fn file_symbols(&self, file_path: &str) -> Vec<IndexedSymbol> {
+        let index = self.index.read().unwrap_or_else(|e| e.into_inner());
+        index.file_symbols.get(file_path).cloned().unwrap_or_default()
+    }
+
     pub fn search(&self, query: &str) -> Vec<IndexedSymbol> {
         let index = self.index.read().unwrap_or_else(|_| self.index.read().unwrap());
         let matcher = SkimMatcherV2::default();
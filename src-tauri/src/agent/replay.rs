@@ -0,0 +1,139 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use crate::agent::openai::ChatMessage;
+use crate::agent::provider::{ChunkCallback, ModelProvider, StreamDelta};
+
+/// Provider responses captured from a real run, replayed turn by turn.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Recording {
+    #[serde(default)]
+    pub model: Option<String>,
+    pub turns: Vec<RecordedTurn>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedTurn {
+    /// The streamed deltas; the full response is their concatenation.
+    pub chunks: Vec<String>,
+    /// Last message the model was answering, kept to make recordings readable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// Reasoning deltas from thinking models, replayed before the answer.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reasoning: Vec<String>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read_to_string(path).map_err(|e| format!("Failed to read recording {}: {}", path.display(), e))?;
+        serde_json::from_str(&data).map_err(|e| format!("Failed to parse recording {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let data = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, data).map_err(|e| e.to_string())
+    }
+}
+
+/// Answers each turn with the next recorded response, without any network access.
+pub struct ReplayProvider {
+    turns: Vec<RecordedTurn>,
+    next: Mutex<usize>,
+    requests: Mutex<Vec<Vec<ChatMessage>>>,
+}
+
+impl ReplayProvider {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            turns: recording.turns,
+            next: Mutex::new(0),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Every history sent to the provider, in order.
+    pub fn requests(&self) -> Vec<Vec<ChatMessage>> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl ModelProvider for ReplayProvider {
+    async fn stream_turn(
+        &self,
+        _model: &str,
+        messages: &[ChatMessage],
+        on_chunk: &mut ChunkCallback<'_>,
+    ) -> Result<String, String> {
+        self.requests.lock().unwrap().push(messages.to_vec());
+
+        let turn = {
+            let mut next = self.next.lock().unwrap();
+            let turn = self.turns.get(*next).cloned();
+            *next += 1;
+            turn
+        };
+        let turn = turn.ok_or_else(|| format!("Replay exhausted after {} turns", self.turns.len()))?;
+
+        for text in &turn.reasoning {
+            on_chunk(StreamDelta::Reasoning(text))?;
+        }
+        let mut full_response = String::new();
+        for chunk in &turn.chunks {
+            full_response.push_str(chunk);
+            on_chunk(StreamDelta::Content(chunk))?;
+        }
+        Ok(full_response)
+    }
+}
+
+/// Passes turns through to a real provider and writes them to `path` as they complete.
+pub struct RecordingProvider {
+    inner: Arc<dyn ModelProvider>,
+    path: PathBuf,
+    recording: Mutex<Recording>,
+}
+
+impl RecordingProvider {
+    pub fn new(inner: Arc<dyn ModelProvider>, path: PathBuf, model: &str) -> Self {
+        Self {
+            inner,
+            path,
+            recording: Mutex::new(Recording { model: Some(model.to_string()), turns: Vec::new() }),
+        }
+    }
+}
+
+#[async_trait]
+impl ModelProvider for RecordingProvider {
+    async fn stream_turn(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        on_chunk: &mut ChunkCallback<'_>,
+    ) -> Result<String, String> {
+        let mut chunks = Vec::new();
+        let mut reasoning = Vec::new();
+        let response = self.inner.stream_turn(model, messages, &mut |delta| {
+            match delta {
+                StreamDelta::Content(text) => chunks.push(text.to_string()),
+                StreamDelta::Reasoning(text) => reasoning.push(text.to_string()),
+            }
+            on_chunk(delta)
+        }).await?;
+
+        let mut recording = self.recording.lock().unwrap();
+        recording.turns.push(RecordedTurn {
+            chunks,
+            prompt: messages.last().map(|m| m.content.clone()),
+            reasoning,
+        });
+        recording.save(&self.path)?;
+        Ok(response)
+    }
+}
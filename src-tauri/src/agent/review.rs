@@ -0,0 +1,375 @@
+use std::path::Path;
+use futures_util::stream::{self, StreamExt};
+use git2::{DiffOptions, Repository};
+use serde::{Deserialize, Serialize};
+use crate::agent::openai::ChatMessage;
+use crate::agent::provider::{stream_completion, ProviderConfig};
+use crate::agent::rag::RagEngine;
+use crate::problems::Problem;
+
+const MAX_FILES: usize = 40;
+const MAX_FILE_DIFF_CHARS: usize = 8000;
+const MAX_CONTEXT_LINES: u32 = 60;
+const CONCURRENT_REVIEWS: usize = 4;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewFinding {
+    /// Path relative to the repository root.
+    pub file: String,
+    pub line: u32,
+    /// `error`, `warning` or `info`.
+    pub severity: String,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl ReviewFinding {
+    pub fn to_problem(&self, id: u32) -> Problem {
+        let file = Path::new(&self.file)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.file.clone());
+        let message = match &self.suggestion {
+            Some(fix) => format!("{} Suggested fix: {}", self.message, fix),
+            None => self.message.clone(),
+        };
+        Problem {
+            id,
+            problem_type: self.severity.clone(),
+            file,
+            path: self.file.clone(),
+            line: self.line,
+            column: 1,
+            message,
+            code: None,
+            source: "ai-review".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeReviewResult {
+    pub findings: Vec<ReviewFinding>,
+    pub files_reviewed: usize,
+    /// Files left out because of the file cap, or deleted files.
+    pub files_skipped: Vec<String>,
+    /// Per-file failures; the remaining files are still reviewed.
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Hunk {
+    new_start: u32,
+    new_lines: u32,
+    header: String,
+    lines: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct FileChange {
+    path: String,
+    hunks: Vec<Hunk>,
+}
+
+impl FileChange {
+    /// Snaps `line` into the nearest changed range, so findings always point at reviewed code.
+    fn clamp_line(&self, line: u32) -> u32 {
+        let mut best = (u32::MAX, line);
+        for hunk in &self.hunks {
+            let start = hunk.new_start.max(1);
+            let end = start + hunk.new_lines.saturating_sub(1);
+            if line >= start && line <= end {
+                return line;
+            }
+            let (distance, snapped) = if line < start { (start - line, start) } else { (line - end, end) };
+            if distance < best.0 {
+                best = (distance, snapped);
+            }
+        }
+        best.1
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawFinding {
+    #[serde(default)]
+    line: Option<u32>,
+    #[serde(default)]
+    severity: Option<String>,
+    message: String,
+    #[serde(default, alias = "fix")]
+    suggestion: Option<String>,
+}
+
+/// Collects changed files: `base...HEAD` when a base is given, else the working tree against HEAD.
+fn collect_changes(repo_path: &str, base: Option<&str>) -> Result<(Vec<FileChange>, Vec<String>), String> {
+    let repo = Repository::open(repo_path).map_err(|e| e.to_string())?;
+
+    let diff = match base {
+        Some(base) => {
+            let base_commit = repo.revparse_single(base).and_then(|o| o.peel_to_commit())
+                .map_err(|e| format!("Unknown base '{}': {}", base, e))?;
+            let head_commit = repo.head().and_then(|h| h.peel_to_commit()).map_err(|e| e.to_string())?;
+            let merge_base = repo.merge_base(base_commit.id(), head_commit.id()).map_err(|e| e.to_string())?;
+            let base_tree = repo.find_commit(merge_base).and_then(|c| c.tree()).map_err(|e| e.to_string())?;
+            let head_tree = head_commit.tree().map_err(|e| e.to_string())?;
+            repo.diff_tree_to_tree(Some(&base_tree), Some(&head_tree), None).map_err(|e| e.to_string())?
+        }
+        None => {
+            let head_tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+            let mut opts = DiffOptions::new();
+            opts.include_untracked(true).recurse_untracked_dirs(true).show_untracked_content(true);
+            repo.diff_tree_to_workdir_with_index(head_tree.as_ref(), Some(&mut opts)).map_err(|e| e.to_string())?
+        }
+    };
+
+    let mut changes: Vec<FileChange> = Vec::new();
+    let mut skipped = Vec::new();
+
+    diff.print(git2::DiffFormat::Patch, |delta, hunk, line| {
+        let path = match delta.new_file().path().or(delta.old_file().path()) {
+            Some(path) => path.to_string_lossy().replace('\\', "/"),
+            None => return true,
+        };
+        if delta.status() == git2::Delta::Deleted {
+            if !skipped.contains(&path) {
+                skipped.push(path);
+            }
+            return true;
+        }
+
+        if changes.last().map(|c| c.path != path).unwrap_or(true) {
+            changes.push(FileChange { path, hunks: Vec::new() });
+        }
+        let change = changes.last_mut().unwrap();
+        let content = String::from_utf8_lossy(line.content()).trim_end_matches('\n').to_string();
+
+        match line.origin() {
+            'H' => {
+                if let Some(hunk) = hunk {
+                    change.hunks.push(Hunk {
+                        new_start: hunk.new_start(),
+                        new_lines: hunk.new_lines(),
+                        header: content,
+                        lines: Vec::new(),
+                    });
+                }
+            }
+            origin @ ('+' | '-' | ' ') => {
+                if let Some(current) = change.hunks.last_mut() {
+                    let number = line.new_lineno().map(|n| n.to_string()).unwrap_or_default();
+                    current.lines.push(format!("{:>5} {}{}", number, origin, content));
+                }
+            }
+            _ => {}
+        }
+        true
+    }).map_err(|e| e.to_string())?;
+
+    changes.retain(|c| !c.hunks.is_empty());
+    if changes.len() > MAX_FILES {
+        skipped.extend(changes.drain(MAX_FILES..).map(|c| c.path));
+    }
+    Ok((changes, skipped))
+}
+
+/// The innermost indexed symbol around each hunk, with its source, so the
+/// model sees the whole function rather than a few lines of context.
+fn surrounding_code(rag_engine: &RagEngine, repo_path: &str, change: &FileChange) -> String {
+    let full_path = Path::new(repo_path).join(&change.path);
+    let symbols = rag_engine.file_symbols(&full_path.to_string_lossy());
+    if symbols.is_empty() {
+        return String::new();
+    }
+    let source = match std::fs::read_to_string(&full_path) {
+        Ok(source) => source,
+        Err(_) => return String::new(),
+    };
+    let source_lines: Vec<&str> = source.lines().collect();
+
+    let mut seen = Vec::new();
+    let mut out = String::new();
+    for hunk in &change.hunks {
+        let enclosing = symbols.iter()
+            .filter(|s| s.start_line <= hunk.new_start && s.end_line >= hunk.new_start)
+            .min_by_key(|s| s.end_line.saturating_sub(s.start_line));
+        let symbol = match enclosing {
+            Some(symbol) if !seen.contains(&symbol.name) => symbol,
+            _ => continue,
+        };
+        seen.push(symbol.name.clone());
+
+        let start = symbol.start_line.max(1) as usize - 1;
+        let end = (symbol.end_line.min(symbol.start_line + MAX_CONTEXT_LINES) as usize).min(source_lines.len());
+        if start >= end {
+            continue;
+        }
+        out.push_str(&format!("{} `{}` (lines {}-{}):\n", symbol.kind, symbol.name, symbol.start_line, symbol.end_line));
+        for (offset, text) in source_lines[start..end].iter().enumerate() {
+            out.push_str(&format!("{:>5}  {}\n", start + offset + 1, text));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn render_hunks(change: &FileChange) -> String {
+    let mut out = String::new();
+    for hunk in &change.hunks {
+        let mut block = format!("{}\n", hunk.header);
+        for line in &hunk.lines {
+            block.push_str(line);
+            block.push('\n');
+        }
+        if out.len() + block.len() > MAX_FILE_DIFF_CHARS {
+            out.push_str("... (remaining hunks omitted)\n");
+            break;
+        }
+        out.push_str(&block);
+    }
+    out
+}
+
+fn normalize_severity(severity: Option<&str>) -> &'static str {
+    match severity.map(|s| s.to_lowercase()).as_deref() {
+        Some("error" | "critical" | "high" | "bug") => "error",
+        Some("warning" | "warn" | "medium") => "warning",
+        _ => "info",
+    }
+}
+
+/// Parses the model's JSON array, tolerating prose or fences around it.
+fn parse_findings(reply: &str, change: &FileChange) -> Result<Vec<ReviewFinding>, String> {
+    let start = reply.find('[');
+    let end = reply.rfind(']');
+    let json = match (start, end) {
+        (Some(start), Some(end)) if start < end => &reply[start..=end],
+        _ => return Err(format!("{}: the model did not return a JSON array", change.path)),
+    };
+    let raw: Vec<RawFinding> = serde_json::from_str(json)
+        .map_err(|e| format!("{}: invalid review JSON: {}", change.path, e))?;
+
+    Ok(raw.into_iter()
+        .filter(|f| !f.message.trim().is_empty())
+        .map(|f| ReviewFinding {
+            file: change.path.clone(),
+            line: change.clamp_line(f.line.unwrap_or(0)),
+            severity: normalize_severity(f.severity.as_deref()).to_string(),
+            message: f.message.trim().to_string(),
+            suggestion: f.suggestion.map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+        })
+        .collect())
+}
+
+async fn review_file(
+    config: &ProviderConfig,
+    model: &str,
+    rag_engine: &RagEngine,
+    repo_path: &str,
+    change: &FileChange,
+) -> Result<Vec<ReviewFinding>, String> {
+    let context = surrounding_code(rag_engine, repo_path, change);
+    let mut prompt = format!("File: {}\n\n", change.path);
+    if !context.is_empty() {
+        prompt.push_str(&format!("Surrounding code:\n{}\n", context));
+    }
+    prompt.push_str(&format!("Diff (new line numbers on the left):\n{}", render_hunks(change)));
+
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: "You are a careful code reviewer. Review only the added or changed lines ('+') of the diff; \
+                      use the surrounding code for context. Report real problems: bugs, edge cases, error handling, \
+                      security, concurrency and clear maintainability issues. Skip style nitpicks.\n\
+                      Reply with a JSON array only. Each item: {\"line\": <new line number>, \"severity\": \"error\" | \"warning\" | \"info\", \
+                      \"message\": \"...\", \"suggestion\": \"optional concrete fix\"}. Reply [] if there is nothing to report.".to_string(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: prompt,
+        },
+    ];
+
+    let reply = stream_completion(config, model, &messages, |_| Ok(())).await
+        .map_err(|e| format!("{}: {}", change.path, e))?;
+    parse_findings(&reply, change)
+}
+
+/// Reviews the diff of `repo_path` file by file with up to four concurrent model calls.
+pub async fn review(
+    config: &ProviderConfig,
+    model: &str,
+    rag_engine: &RagEngine,
+    repo_path: &str,
+    base: Option<&str>,
+) -> Result<CodeReviewResult, String> {
+    let (changes, files_skipped) = collect_changes(repo_path, base)?;
+    if changes.is_empty() {
+        return Err("No changes to review".to_string());
+    }
+
+    let results: Vec<Result<Vec<ReviewFinding>, String>> = stream::iter(changes.iter())
+        .map(|change| review_file(config, model, rag_engine, repo_path, change))
+        .buffer_unordered(CONCURRENT_REVIEWS)
+        .collect()
+        .await;
+
+    let mut findings = Vec::new();
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(file_findings) => findings.extend(file_findings),
+            Err(e) => errors.push(e),
+        }
+    }
+    if findings.is_empty() && errors.len() == changes.len() {
+        return Err(errors.join("\n"));
+    }
+
+    findings.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+    Ok(CodeReviewResult {
+        findings,
+        files_reviewed: changes.len() - errors.len(),
+        files_skipped,
+        errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change() -> FileChange {
+        FileChange {
+            path: "src/lib.rs".to_string(),
+            hunks: vec![
+                Hunk { new_start: 10, new_lines: 5, header: "@@ -10,3 +10,5 @@".to_string(), lines: Vec::new() },
+                Hunk { new_start: 40, new_lines: 2, header: "@@ -38,2 +40,2 @@".to_string(), lines: Vec::new() },
+            ],
+        }
+    }
+
+    #[test]
+    fn parses_findings_around_prose() {
+        let reply = "Here you go:\n```json\n[{\"line\": 12, \"severity\": \"High\", \"message\": \"unwrap on user input\", \"fix\": \"use ?\"},\
+                     {\"line\": 100, \"severity\": \"nit\", \"message\": \"naming\"}]\n```";
+        let findings = parse_findings(reply, &change()).unwrap();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].severity, "error");
+        assert_eq!(findings[0].suggestion.as_deref(), Some("use ?"));
+        assert_eq!(findings[1].severity, "info");
+        assert_eq!(findings[1].line, 41);
+    }
+
+    #[test]
+    fn clamps_lines_to_nearest_hunk() {
+        let change = change();
+        assert_eq!(change.clamp_line(12), 12);
+        assert_eq!(change.clamp_line(1), 10);
+        assert_eq!(change.clamp_line(30), 40);
+        assert_eq!(change.clamp_line(100), 41);
+        assert_eq!(change.clamp_line(20), 14);
+    }
+}
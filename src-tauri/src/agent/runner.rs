@@ -0,0 +1,382 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use serde::Serialize;
+use tauri::{Window, Emitter};
+use crate::agent::approval::ApprovalRegistry;
+use crate::agent::openai::ChatMessage;
+use crate::agent::provider::{ModelProvider, StreamDelta};
+use crate::agent::git_tools::GIT_WRITE_TOOLS;
+use crate::agent::rag::RagEngine;
+use crate::agent::subagent::SubAgent;
+use crate::agent::system_prompt::{generate_system_prompt, SystemPromptContext};
+use crate::agent::trace::{TraceEntry, TraceRecorder};
+use crate::agent::tools::{ToolCall, ToolExecutor, parse_tool_calls_with, format_tool_output};
+use crate::mcp::McpManager;
+use crate::mcp::manager::READ_RESOURCE_TOOL;
+use crate::settings::WebFetchSettings;
+use crate::mcp::types::ApprovalMode;
+
+pub const DEFAULT_MAX_ITERATIONS: usize = 10;
+
+/// Receives the `agent-event` payloads of a run: the app window, or stdout in headless mode.
+pub trait AgentEventSink: Send + Sync {
+    fn emit_event(&self, event: serde_json::Value) -> Result<(), String>;
+}
+
+impl AgentEventSink for Window {
+    fn emit_event(&self, event: serde_json::Value) -> Result<(), String> {
+        self.emit("agent-event", event).map_err(|e| e.to_string())
+    }
+}
+
+/// What happens to tool calls whose MCP server asks for approval.
+#[derive(Clone)]
+pub enum ApprovalPolicy {
+    /// Ask the user through an `agent-tool-approval` event.
+    Interactive(Arc<ApprovalRegistry>),
+    /// Approve everything (headless `--approve-all`).
+    ApproveAll,
+    /// Reject everything that needs approval.
+    RejectAll,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentRunOutcome {
+    /// The last assistant response, if the run produced one.
+    pub final_response: Option<String>,
+    /// Whether the model finished with `## FINAL ANSWER` before the iteration cap.
+    pub completed: bool,
+    pub iterations: usize,
+    /// Id of the stored trace, when the run was traced.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+}
+
+/// One agent conversation turn: the model/tool loop behind `agentrouter_chat_stream`.
+pub struct AgentRun<'a> {
+    pub sink: &'a dyn AgentEventSink,
+    pub provider: Arc<dyn ModelProvider>,
+    pub model: String,
+    pub workspace_path: Option<PathBuf>,
+    pub rag_engine: Arc<RagEngine>,
+    pub mcp: Arc<McpManager>,
+    pub approval: ApprovalPolicy,
+    pub max_iterations: usize,
+    pub web_fetch: WebFetchSettings,
+    /// Directory to write the run's trace to; `None` disables tracing.
+    pub trace_dir: Option<PathBuf>,
+    /// Offer `git_stage` and `git_commit`; each call still needs approval.
+    pub git_writes: bool,
+}
+
+impl<'a> AgentRun<'a> {
+    fn emit(&self, event_type: &str, payload: serde_json::Value) -> Result<(), String> {
+        self.sink.emit_event(serde_json::json!({ "type": event_type, "payload": payload }))
+    }
+
+    /// Returns the reason a call was rejected, or `None` if it may run.
+    async fn check_approval(&self, call_id: &str, call: &ToolCall) -> Result<Option<&'static str>, String> {
+        let mode = if GIT_WRITE_TOOLS.contains(&call.name.as_str()) {
+            if !self.git_writes {
+                return Ok(Some("git write tools are disabled in settings"));
+            }
+            ApprovalMode::Ask
+        } else if call.name == READ_RESOURCE_TOOL {
+            let server = call.parameters.get("server").and_then(|v| v.as_str()).unwrap_or_default();
+            match self.mcp.resource_approval(server).await {
+                Some(mode) => mode,
+                None => return Ok(None),
+            }
+        } else {
+            match self.mcp.approval_for(&call.name).await {
+                Some(mode) => mode,
+                None => return Ok(None),
+            }
+        };
+
+        Ok(match (mode, &self.approval) {
+            (ApprovalMode::Auto, _) => None,
+            (ApprovalMode::Deny, _) => Some("calls to this server are disabled"),
+            (ApprovalMode::Ask, ApprovalPolicy::ApproveAll) => None,
+            (ApprovalMode::Ask, ApprovalPolicy::RejectAll) => Some("approval required but not available in this run"),
+            (ApprovalMode::Ask, ApprovalPolicy::Interactive(approvals)) => {
+                let rx = approvals.register(call_id);
+                self.emit("agent-tool-approval", serde_json::json!({
+                    "id": call_id,
+                    "name": call.name,
+                    "parameters": call.parameters,
+                    "status": "awaiting_approval"
+                }))?;
+                if approvals.wait(call_id, rx).await { None } else { Some("rejected by the user") }
+            }
+        })
+    }
+
+    pub async fn run(&self, messages: Vec<ChatMessage>) -> Result<AgentRunOutcome, String> {
+        // Tracing must never fail a run, so trace errors are only reported
+        let trace = self.trace_dir.as_ref().and_then(|dir| {
+            TraceRecorder::start(dir, &self.model, self.workspace_path.as_deref())
+                .map_err(|e| self.emit("agent-trace-error", serde_json::json!({ "error": e })))
+                .ok()
+        });
+
+        let mut iteration = 0;
+        let result = self.run_loop(messages, trace.as_ref(), &mut iteration).await;
+
+        let trace_id = trace.map(|trace| {
+            let saved = match &result {
+                Ok(outcome) => trace.finish(Some(outcome.completed), outcome.iterations),
+                Err(e) => {
+                    trace.record(TraceEntry::Error { iteration, message: e.clone() });
+                    trace.finish(None, iteration)
+                }
+            };
+            if let Err(e) = saved {
+                let _ = self.emit("agent-trace-error", serde_json::json!({ "error": e }));
+            }
+            trace.id()
+        });
+        result.map(|outcome| AgentRunOutcome { trace_id, ..outcome })
+    }
+
+    async fn run_loop(&self, messages: Vec<ChatMessage>, trace: Option<&TraceRecorder>, current_iteration: &mut usize) -> Result<AgentRunOutcome, String> {
+        let mut extra_tools: Vec<String> = self.mcp.agent_tools().await.into_iter().map(|(name, _)| name).collect();
+        if !self.mcp.agent_resources().await.is_empty() {
+            extra_tools.push(READ_RESOURCE_TOOL.to_string());
+        }
+        // The eval harness replays edits in scratch workspaces
+        #[cfg(test)]
+        extra_tools.push("write_file".to_string());
+
+        let context = SystemPromptContext {
+            user_os: std::env::consts::OS.to_string(),
+            user_query: messages.last().map(|m| m.content.clone()),
+            workspace: self.workspace_path.as_ref().and_then(|p| p.to_str()).map(|s| s.to_string()),
+            external_tools: self.mcp.prompt_lines().await,
+            git_writes: self.git_writes,
+        };
+        let system_prompt = generate_system_prompt(context);
+
+        let mut full_messages = vec![ChatMessage {
+            role: "system".to_string(),
+            content: system_prompt,
+        }];
+        full_messages.extend(messages);
+
+        let mut final_response = None;
+        let mut completed = false;
+        // History entries already written to the trace
+        let mut traced = 0;
+
+        loop {
+            *current_iteration += 1;
+            if *current_iteration > self.max_iterations {
+                *current_iteration = self.max_iterations;
+                break;
+            }
+
+            if let Some(trace) = trace {
+                trace.record(TraceEntry::Prompt { iteration: *current_iteration, messages: full_messages[traced..].to_vec() });
+            }
+
+            let started = Instant::now();
+            let mut reasoning = String::new();
+            let full_response = self.provider.stream_turn(&self.model, &full_messages, &mut |delta| match delta {
+                StreamDelta::Content(text) => self.emit("chunk", serde_json::json!(text)),
+                StreamDelta::Reasoning(text) => {
+                    reasoning.push_str(text);
+                    self.emit("reasoning", serde_json::json!(text))
+                }
+            }).await?;
+
+            if let Some(trace) = trace {
+                trace.record(TraceEntry::Response {
+                    iteration: *current_iteration,
+                    content: full_response.clone(),
+                    reasoning,
+                    duration_ms: started.elapsed().as_millis() as u64,
+                });
+            }
+
+            // Add assistant response to history
+            full_messages.push(ChatMessage {
+                role: "assistant".to_string(),
+                content: full_response.clone(),
+            });
+            traced = full_messages.len();
+            final_response = Some(full_response.clone());
+
+            // Parse and execute tools
+            let tool_calls = parse_tool_calls_with(&full_response, &extra_tools);
+
+            // Check if we should break the loop
+            if tool_calls.is_empty() {
+                // If the response contains a final answer marker, we're definitely done
+                if full_response.contains("## FINAL ANSWER") {
+                    completed = true;
+                    break;
+                }
+
+                // If no tool calls and no final answer, nudge the model to continue
+                if *current_iteration < self.max_iterations {
+                    full_messages.push(ChatMessage {
+                        role: "user".to_string(),
+                        content: "Your response did not include any tool calls or a ## FINAL ANSWER. If you are finished, please provide the ## FINAL ANSWER. If not, please use the appropriate tool to proceed.".to_string(),
+                    });
+                    continue;
+                }
+
+                break;
+            }
+
+            let tool_outputs = self.execute_tools(tool_calls, *current_iteration, trace).await?;
+
+            // Add tool outputs to history as a user message to prompt the model to continue
+            if !tool_outputs.is_empty() {
+                let tool_response_content = tool_outputs.join("\n\n");
+                full_messages.push(ChatMessage {
+                    role: "user".to_string(),
+                    content: format!("Tool execution results:\n{}\n\nPlease analyze these results and take the next step.", tool_response_content),
+                });
+            }
+        }
+
+        Ok(AgentRunOutcome {
+            final_response,
+            completed,
+            iterations: *current_iteration,
+            trace_id: None,
+        })
+    }
+
+    async fn execute_tools(&self, tool_calls: Vec<ToolCall>, iteration: usize, trace: Option<&TraceRecorder>) -> Result<Vec<String>, String> {
+        let executor = ToolExecutor::new(self.workspace_path.clone(), self.rag_engine.clone())
+            .with_mcp(self.mcp.clone())
+            .with_web_fetch(self.web_fetch.clone())
+            .with_git_writes(self.git_writes);
+        let mut tool_outputs = Vec::new();
+
+        // Delegated tasks run as concurrent sub-agents after the direct tool calls
+        let (delegated, tool_calls): (Vec<ToolCall>, Vec<ToolCall>) = tool_calls
+            .into_iter()
+            .partition(|call| call.name == "delegate_task");
+
+        let record = |call_id: &str, name: &str, parameters: &serde_json::Value, outcome: Result<&str, (&str, &str)>, started: Instant| {
+            if let Some(trace) = trace {
+                let (status, result, error) = match outcome {
+                    Ok(result) => ("completed", Some(result.to_string()), None),
+                    Err((status, error)) => (status, None, Some(error.to_string())),
+                };
+                trace.record(TraceEntry::ToolCall {
+                    iteration,
+                    id: call_id.to_string(),
+                    name: name.to_string(),
+                    parameters: parameters.clone(),
+                    status: status.to_string(),
+                    result,
+                    error,
+                    duration_ms: started.elapsed().as_millis() as u64,
+                    subagent_id: None,
+                });
+            }
+        };
+
+        for call in tool_calls {
+            let call_id = uuid::Uuid::new_v4().to_string();
+            let tool_name = call.name.clone();
+            let parameters = call.parameters.clone();
+            let started = Instant::now();
+
+            // Emit tool call started event
+            self.emit("agent-tool-start", serde_json::json!({
+                "id": call_id,
+                "name": tool_name,
+                "parameters": call.parameters,
+                "status": "executing",
+                "timestamp": chrono::Utc::now().timestamp()
+            }))?;
+
+            if let Some(reason) = self.check_approval(&call_id, &call).await? {
+                self.emit("agent-tool-error", serde_json::json!({
+                    "id": call_id,
+                    "name": tool_name,
+                    "error": reason,
+                    "status": "rejected"
+                }))?;
+                record(&call_id, &tool_name, &parameters, Err(("rejected", reason)), started);
+                tool_outputs.push(format!("Tool '{}' error: {}", tool_name, reason));
+                continue;
+            }
+
+            match executor.execute(call).await {
+                Ok(result) => {
+                    // Emit tool result event
+                    self.emit("agent-tool-res", serde_json::json!({
+                        "id": call_id,
+                        "name": tool_name,
+                        "result": result,
+                        "status": "completed"
+                    }))?;
+                    record(&call_id, &tool_name, &parameters, Ok(result.as_str()), started);
+                    tool_outputs.push(format!("[{}] result:\n{}", tool_name, format_tool_output(&tool_name, &result)));
+                }
+                Err(e) => {
+                    let err_msg = e.to_string();
+                    // Emit tool error event
+                    self.emit("agent-tool-error", serde_json::json!({
+                        "id": call_id,
+                        "name": tool_name,
+                        "error": err_msg,
+                        "status": "error"
+                    }))?;
+                    record(&call_id, &tool_name, &parameters, Err(("error", err_msg.as_str())), started);
+                    tool_outputs.push(format!("Tool '{}' error: {}", tool_name, err_msg));
+                }
+            }
+        }
+
+        if !delegated.is_empty() {
+            let subagent = SubAgent {
+                sink: self.sink,
+                provider: self.provider.as_ref(),
+                model: &self.model,
+                workspace_path: self.workspace_path.clone(),
+                rag_engine: self.rag_engine.clone(),
+                trace,
+                iteration,
+            };
+
+            let runs = delegated.into_iter().map(|call| {
+                let call_id = uuid::Uuid::new_v4().to_string();
+                let _ = self.emit("agent-tool-start", serde_json::json!({
+                    "id": call_id,
+                    "name": "delegate_task",
+                    "parameters": call.parameters,
+                    "status": "executing",
+                    "timestamp": chrono::Utc::now().timestamp()
+                }));
+                let subagent = &subagent;
+                let record = &record;
+                async move {
+                    let started = Instant::now();
+                    let parameters = call.parameters.clone();
+                    let result = subagent.run(&call_id, call).await;
+                    let _ = self.emit("agent-tool-res", serde_json::json!({
+                        "id": call_id,
+                        "name": "delegate_task",
+                        "result": result,
+                        "status": "completed"
+                    }));
+                    record(&call_id, "delegate_task", &parameters, Ok(result.as_str()), started);
+                    result
+                }
+            }).collect::<Vec<_>>();
+
+            tool_outputs.extend(futures_util::future::join_all(runs).await);
+        }
+
+        Ok(tool_outputs)
+    }
+}
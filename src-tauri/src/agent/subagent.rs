@@ -0,0 +1,194 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use crate::agent::openai::ChatMessage;
+use crate::agent::provider::{ModelProvider, StreamDelta};
+use crate::agent::rag::RagEngine;
+use crate::agent::runner::AgentEventSink;
+use crate::agent::system_prompt::generate_subagent_prompt;
+use crate::agent::trace::{TraceEntry, TraceRecorder};
+use crate::agent::tools::{ToolCall, ToolExecutor, parse_tool_calls, format_tool_output, is_read_only_tool};
+
+const DEFAULT_SUBAGENT_ITERATIONS: usize = 6;
+const MAX_SUBAGENT_ITERATIONS: usize = 10;
+
+/// A focused sub-run spawned by the `delegate_task` tool.
+///
+/// Sub-agents get their own history, only read-only tools and their own
+/// iteration cap. Every event they emit carries `runId` so the UI can group it
+/// under the parent tool call.
+pub struct SubAgent<'a> {
+    pub sink: &'a dyn AgentEventSink,
+    pub provider: &'a dyn ModelProvider,
+    pub model: &'a str,
+    pub workspace_path: Option<PathBuf>,
+    pub rag_engine: Arc<RagEngine>,
+    /// Trace of the parent run; sub-agent tool calls are recorded with their run id.
+    pub trace: Option<&'a TraceRecorder>,
+    /// Parent iteration that delegated the task.
+    pub iteration: usize,
+}
+
+fn emit(sink: &dyn AgentEventSink, run_id: &str, event_type: &str, payload: serde_json::Value) -> Result<(), String> {
+    sink.emit_event(serde_json::json!({
+        "type": event_type,
+        "runId": run_id,
+        "payload": payload
+    }))
+}
+
+/// Pulls the summary out of a sub-agent response, preferring the `## FINAL ANSWER` section.
+fn extract_summary(response: &str) -> String {
+    match response.find("## FINAL ANSWER") {
+        Some(idx) => response[idx + "## FINAL ANSWER".len()..]
+            .trim_start_matches(':')
+            .trim()
+            .to_string(),
+        None => response.trim().to_string(),
+    }
+}
+
+impl<'a> SubAgent<'a> {
+    /// Runs the delegated task and returns the text handed back to the parent agent.
+    /// Failures are reported in the returned text rather than aborting the parent run.
+    pub async fn run(&self, parent_call_id: &str, call: ToolCall) -> String {
+        let task = match call.parameters.get("task").and_then(|v| v.as_str()) {
+            Some(task) if !task.trim().is_empty() => task.to_string(),
+            _ => return "Tool 'delegate_task' error: Missing task parameter".to_string(),
+        };
+        let max_iterations = call.parameters.get("max_iterations")
+            .and_then(|v| v.as_u64())
+            .map(|n| (n as usize).clamp(1, MAX_SUBAGENT_ITERATIONS))
+            .unwrap_or(DEFAULT_SUBAGENT_ITERATIONS);
+
+        let run_id = uuid::Uuid::new_v4().to_string();
+        let _ = emit(self.sink, &run_id, "subagent-start", serde_json::json!({
+            "parentId": parent_call_id,
+            "task": task,
+            "maxIterations": max_iterations,
+            "timestamp": chrono::Utc::now().timestamp()
+        }));
+
+        let (summary, iterations, status) = match self.run_loop(&run_id, &task, max_iterations).await {
+            Ok((summary, iterations, finished)) => {
+                (summary, iterations, if finished { "completed" } else { "incomplete" })
+            }
+            Err(e) => (format!("Sub-agent failed: {}", e), 0, "error"),
+        };
+
+        let _ = emit(self.sink, &run_id, "subagent-end", serde_json::json!({
+            "parentId": parent_call_id,
+            "summary": summary,
+            "iterations": iterations,
+            "status": status
+        }));
+
+        format!("[delegate_task] result ({}):\nTask: {}\n{}", status, task, summary)
+    }
+
+    async fn run_loop(&self, run_id: &str, task: &str, max_iterations: usize) -> Result<(String, usize, bool), String> {
+        let workspace = self.workspace_path.as_ref().and_then(|p| p.to_str()).map(|s| s.to_string());
+        let mut history = vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: generate_subagent_prompt(workspace.as_deref()),
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: task.to_string(),
+            },
+        ];
+
+        let executor = ToolExecutor::new(self.workspace_path.clone(), self.rag_engine.clone());
+        let mut last_response = String::new();
+
+        for iteration in 1..=max_iterations {
+            let response = self.provider.stream_turn(self.model, &history, &mut |delta| match delta {
+                StreamDelta::Content(text) => emit(self.sink, run_id, "subagent-chunk", serde_json::json!(text)),
+                StreamDelta::Reasoning(text) => emit(self.sink, run_id, "subagent-reasoning", serde_json::json!(text)),
+            }).await?;
+
+            history.push(ChatMessage {
+                role: "assistant".to_string(),
+                content: response.clone(),
+            });
+            last_response = response.clone();
+
+            let tool_calls = parse_tool_calls(&response);
+            if tool_calls.is_empty() {
+                if response.contains("## FINAL ANSWER") {
+                    return Ok((extract_summary(&response), iteration, true));
+                }
+                history.push(ChatMessage {
+                    role: "user".to_string(),
+                    content: "Continue investigating with the read-only tools, or reply with ## FINAL ANSWER followed by your summary.".to_string(),
+                });
+                continue;
+            }
+
+            let mut tool_outputs = Vec::new();
+            for call in tool_calls {
+                let call_id = uuid::Uuid::new_v4().to_string();
+                let tool_name = call.name.clone();
+
+                if !is_read_only_tool(&tool_name) {
+                    tool_outputs.push(format!("Tool '{}' error: not available to sub-agents (read-only tools only)", tool_name));
+                    continue;
+                }
+
+                emit(self.sink, run_id, "agent-tool-start", serde_json::json!({
+                    "id": call_id,
+                    "name": tool_name,
+                    "parameters": call.parameters,
+                    "status": "executing",
+                    "timestamp": chrono::Utc::now().timestamp()
+                }))?;
+
+                let started = std::time::Instant::now();
+                let parameters = call.parameters.clone();
+                let outcome = executor.execute(call).await.map_err(|e| e.to_string());
+                if let Some(trace) = self.trace {
+                    trace.record(TraceEntry::ToolCall {
+                        iteration: self.iteration,
+                        id: call_id.clone(),
+                        name: tool_name.clone(),
+                        parameters,
+                        status: if outcome.is_ok() { "completed" } else { "error" }.to_string(),
+                        result: outcome.as_ref().ok().cloned(),
+                        error: outcome.as_ref().err().cloned(),
+                        duration_ms: started.elapsed().as_millis() as u64,
+                        subagent_id: Some(run_id.to_string()),
+                    });
+                }
+
+                match outcome {
+                    Ok(result) => {
+                        emit(self.sink, run_id, "agent-tool-res", serde_json::json!({
+                            "id": call_id,
+                            "name": tool_name,
+                            "result": result,
+                            "status": "completed"
+                        }))?;
+                        tool_outputs.push(format!("[{}] result:\n{}", tool_name, format_tool_output(&tool_name, &result)));
+                    }
+                    Err(err_msg) => {
+                        emit(self.sink, run_id, "agent-tool-error", serde_json::json!({
+                            "id": call_id,
+                            "name": tool_name,
+                            "error": err_msg,
+                            "status": "error"
+                        }))?;
+                        tool_outputs.push(format!("Tool '{}' error: {}", tool_name, err_msg));
+                    }
+                }
+            }
+
+            history.push(ChatMessage {
+                role: "user".to_string(),
+                content: format!("Tool execution results:\n{}\n\nPlease analyze these results and take the next step.", tool_outputs.join("\n\n")),
+            });
+        }
+
+        // Iteration cap reached: hand back whatever the last turn produced
+        Ok((extract_summary(&last_response), max_iterations, false))
+    }
+}
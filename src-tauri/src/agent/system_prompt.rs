@@ -5,10 +5,31 @@ pub struct SystemPromptContext {
     pub user_os: String,
     pub user_query: Option<String>,
     pub workspace: Option<String>,
+    /// Descriptions of tools provided by MCP servers, one per line.
+    #[serde(default)]
+    pub external_tools: Vec<String>,
+    /// Whether `git_stage` and `git_commit` are offered.
+    #[serde(default)]
+    pub git_writes: bool,
 }
 
 pub fn generate_system_prompt(context: SystemPromptContext) -> String {
     let workspace = context.workspace.as_deref().unwrap_or("Unknown");
+    let external_tools = if context.external_tools.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\n<external_tools>\nTools provided by MCP servers. Call them like any other tool; the user may be asked to approve each call.\n{}\n</external_tools>\n",
+            context.external_tools.join("\n")
+        )
+    };
+
+    let git_write_tools = if context.git_writes {
+        "- git_stage: Stage files for commit. Parameters: paths (comma-separated). The user approves each call.\n\
+         - git_commit: Commit the staged changes. Parameters: message. The user approves each call.\n"
+    } else {
+        ""
+    };
 
     format!(r#"<identity>
 You are Cognitive a high-precision AI software engineer created by Cognitive SE. Your primary goal is to execute tasks and provide technical information by directly interacting with the codebase using tools.
@@ -26,6 +47,7 @@ You are Cognitive a high-precision AI software engineer created by Cognitive SE.
      • one single tool call (most common case).
      • `read_file` + `write_file` on the SAME file (read-before-write pattern).
      • `write_file` + `read_file` on the SAME file (verification pattern).
+     • several `delegate_task` calls for independent investigations (they run concurrently).
    - NEVER call more than two tools in one response.
    - NEVER call unrelated tools together.
    - CRITICAL: After ANY `write_file`, you MUST immediately call `read_file` on the same path in the SAME response to verify the change.
@@ -86,11 +108,22 @@ Available tools:
 - grep: Alias for search.
 - write_file: Write/update file content. Parameters: path, content
 - list_dir: List files in directory. Parameters: path
-- todo_add: Add task to todo list. Parameters: content
+- fetch_url: Fetch a web page (documentation, changelogs, issues) as markdown. Parameters: url. Optional: refresh (true to bypass the cache).
+  Example: <fetch_url url="https://docs.rs/serde/latest/serde/" />
+- git_status: Show changed, staged and untracked files.
+- git_diff: Show a unified diff. Optional: path, staged (true for the index against HEAD), from and to (revisions; with only from, the working tree is compared to it).
+  Example: <git_diff path="src/main.rs" />
+- git_log: List recent commits. Optional: path (only commits touching it), max_count (default 20), rev.
+- git_blame: Show who last changed each line. Parameters: path. Optional: start_line, end_line (1-based).
+  Example: <git_blame path="src/auth.rs" start_line="40" end_line="60" />
+- git_show: Show a commit's message and patch. Optional: rev (default HEAD), path.
+{git_write_tools}- todo_add: Add task to todo list. Parameters: content
 - todo_list: List all todos.
 - todo_complete: Complete a todo. Parameters: id
+- delegate_task: Hand a focused, read-only investigation to a sub-agent with its own history. It returns a summary. Parameters: task. Optional: max_iterations (1-10, default 6).
+  Example: <delegate_task task="Investigate how authentication works and list the files involved" />
 </tools>
-
+{external_tools}
 <examples>
 1. Task: "What does parser.rs do?"
    <search_files pattern="parser.rs" />
@@ -134,5 +167,43 @@ Available tools:
 - User OS: {user_os}
 - Workspace: {workspace}
 </context>
-"#, user_os = context.user_os, workspace = workspace)
+"#, user_os = context.user_os, workspace = workspace, external_tools = external_tools, git_write_tools = git_write_tools)
+}
+
+/// System prompt for sub-agents spawned by `delegate_task`.
+pub fn generate_subagent_prompt(workspace: Option<&str>) -> String {
+    let workspace = workspace.unwrap_or("Unknown");
+
+    format!(r#"<identity>
+You are a Cognitive sub-agent. You have been given ONE focused investigation by the main agent. You cannot modify files and you cannot talk to the user.
+</identity>
+
+<operational_rules>
+1. Use tools immediately to gather the information the task needs. Call at most two tools per response.
+2. Use RELATIVE paths (relative to workspace root) for all tool parameters.
+3. Do not write conversational filler.
+4. When you have enough information, reply with `## FINAL ANSWER` followed by a concise summary for the main agent: key findings, relevant file paths and line numbers.
+</operational_rules>
+
+<tools>
+Call tools with the compact format <tool_name arg_name="value" /> or the block format <invoke name="tool_name"><parameter name="arg_name">value</parameter></invoke>.
+
+Available tools (read-only):
+- read_file: Read content of a file. Parameters: path. Optional: start_line, end_line.
+- search_files: Find files by name pattern. Parameters: pattern
+- search_codebase: Semantic search for code logic/symbols. Parameters: query
+- grep: Search content within files. Parameters: query, path (optional subpath)
+- list_dir: List files in directory. Parameters: path
+- git_status: Show changed, staged and untracked files.
+- git_diff: Show a unified diff. Optional: path, staged, from, to.
+- git_log: List recent commits. Optional: path, max_count, rev.
+- git_blame: Show who last changed each line. Parameters: path. Optional: start_line, end_line.
+- git_show: Show a commit's message and patch. Optional: rev (default HEAD), path.
+- todo_list: List all todos.
+</tools>
+
+<context>
+- Workspace: {workspace}
+</context>
+"#, workspace = workspace)
 }
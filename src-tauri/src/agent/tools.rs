@@ -2,12 +2,28 @@ use serde::{Deserialize, Serialize};
 use std::error::Error;
 use crate::fs;
 use std::path::PathBuf;
+use crate::agent::fetch::WebFetcher;
+use crate::agent::git_tools;
 use crate::agent::rag::RagEngine;
+use crate::mcp::McpManager;
+use crate::mcp::manager::{MCP_TOOL_PREFIX, READ_RESOURCE_TOOL};
+use crate::settings::WebFetchSettings;
 use std::sync::Arc;
 use quick_xml::reader::Reader;
 use quick_xml::events::Event;
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Tools the model is allowed to call; anything else in its output is ignored.
+pub const ALLOWED_TOOLS: &[&str] = &["search_codebase", "index_codebase", "read_file", "search_files", "find_by_name", "grep", "list_dir", "fetch_url", "git_status", "git_diff", "git_log", "git_blame", "git_show", "git_stage", "git_commit", "todo_list", "todo_add", "todo_complete", "todo_delete", "todo_clear", "delegate_task"];
+
+/// Tools available to sub-agents spawned by `delegate_task`, along with `git_tools::GIT_READ_TOOLS`.
+pub const READ_ONLY_TOOLS: &[&str] = &["search_codebase", "read_file", "search_files", "find_by_name", "grep", "list_dir", "todo_list"];
+
+/// Whether sub-agents may call `name`.
+pub fn is_read_only_tool(name: &str) -> bool {
+    READ_ONLY_TOOLS.contains(&name) || git_tools::GIT_READ_TOOLS.contains(&name)
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ToolCall {
     pub name: String,
     pub parameters: serde_json::Value,
@@ -35,14 +51,46 @@ fn parse_value(s: &str) -> serde_json::Value {
 pub struct ToolExecutor {
     pub workspace_path: Option<PathBuf>,
     pub rag_engine: Arc<RagEngine>,
+    pub mcp: Option<Arc<McpManager>>,
+    pub web_fetch: WebFetchSettings,
+    pub git_writes: bool,
 }
 
 impl ToolExecutor {
     pub fn new(workspace_path: Option<PathBuf>, rag_engine: Arc<RagEngine>) -> Self {
-        Self { workspace_path, rag_engine }
+        Self { workspace_path, rag_engine, mcp: None, web_fetch: WebFetchSettings::default(), git_writes: false }
+    }
+
+    /// Routes `mcp__<server>__<tool>` calls to the workspace's MCP servers.
+    pub fn with_mcp(mut self, mcp: Arc<McpManager>) -> Self {
+        self.mcp = Some(mcp);
+        self
+    }
+
+    /// Domain allow-list and offline mode for `fetch_url`.
+    pub fn with_web_fetch(mut self, settings: WebFetchSettings) -> Self {
+        self.web_fetch = settings;
+        self
+    }
+
+    /// Enables `git_stage` and `git_commit`.
+    pub fn with_git_writes(mut self, enabled: bool) -> Self {
+        self.git_writes = enabled;
+        self
     }
 
     pub async fn execute(&self, call: ToolCall) -> Result<String, Box<dyn Error + Send + Sync>> {
+        if call.name.starts_with(MCP_TOOL_PREFIX) {
+            let mcp = self.mcp.as_ref().ok_or("MCP tools are not available here")?;
+            return Ok(mcp.call_tool(&call.name, call.parameters).await?);
+        }
+        if call.name == READ_RESOURCE_TOOL {
+            let mcp = self.mcp.as_ref().ok_or("MCP resources are not available here")?;
+            let server = call.parameters.get("server").and_then(|v| v.as_str()).ok_or("Missing server parameter")?;
+            let uri = call.parameters.get("uri").and_then(|v| v.as_str()).ok_or("Missing uri parameter")?;
+            return Ok(mcp.read_resource(server, uri).await?);
+        }
+
         let resolve_path = |path: &str| -> String {
             let path_buf = PathBuf::from(path);
             if path_buf.is_absolute() {
@@ -128,6 +176,50 @@ impl ToolExecutor {
                 fs::write_file(full_path, content.to_string()).map_err(|e| e.to_string())?;
                 Ok("File written successfully".to_string())
             }
+            "fetch_url" => {
+                let url = call.parameters.get("url").and_then(|v| v.as_str()).ok_or("Missing url parameter")?;
+                let refresh = call.parameters.get("refresh").and_then(|v| v.as_bool()).unwrap_or(false);
+                let fetcher = WebFetcher::for_workspace(self.workspace_path.as_deref(), self.web_fetch.clone());
+                let page = fetcher.fetch(url, refresh).await?;
+                Ok(page.to_tool_output())
+            }
+            "git_status" | "git_diff" | "git_log" | "git_blame" | "git_show" | "git_stage" | "git_commit" => {
+                let workspace = self.workspace_path.as_ref().ok_or("No workspace open")?;
+                let str_param = |key: &str| call.parameters.get(key).and_then(|v| v.as_str()).filter(|s| !s.is_empty());
+                let num_param = |key: &str| call.parameters.get(key).and_then(|v| v.as_u64()).map(|n| n as usize);
+                if git_tools::GIT_WRITE_TOOLS.contains(&call.name.as_str()) && !self.git_writes {
+                    return Err(format!("{} is disabled; enable agent git writes in settings", call.name).into());
+                }
+
+                let output = match call.name.as_str() {
+                    "git_status" => git_tools::status(workspace),
+                    "git_diff" => git_tools::diff(
+                        workspace,
+                        str_param("path"),
+                        call.parameters.get("staged").and_then(|v| v.as_bool()).unwrap_or(false),
+                        str_param("from"),
+                        str_param("to"),
+                    ),
+                    "git_log" => git_tools::log(workspace, str_param("path"), num_param("max_count"), str_param("rev")),
+                    "git_blame" => git_tools::blame(
+                        workspace,
+                        str_param("path").ok_or("Missing path parameter")?,
+                        num_param("start_line"),
+                        num_param("end_line"),
+                    ),
+                    "git_show" => git_tools::show(workspace, str_param("rev"), str_param("path")),
+                    "git_stage" => {
+                        let paths: Vec<String> = match call.parameters.get("paths").or_else(|| call.parameters.get("path")) {
+                            Some(serde_json::Value::Array(items)) => items.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect(),
+                            Some(serde_json::Value::String(list)) => list.split([',', '\n']).map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+                            _ => return Err("Missing paths parameter".into()),
+                        };
+                        git_tools::stage(workspace, &paths)
+                    }
+                    _ => git_tools::commit(workspace, str_param("message").ok_or("Missing message parameter")?),
+                };
+                Ok(output?)
+            }
             "todo_add" => {
                 let content = call.parameters.get("content").and_then(|v| v.as_str()).ok_or("Missing content parameter")?;
                 let workspace = self.workspace_path.as_ref().ok_or("No workspace open")?;
@@ -197,14 +289,76 @@ impl ToolExecutor {
                     Err(format!("Todo not found: {}", id).into())
                 }
             }
+            "delegate_task" => Err("delegate_task can only be used from an agent run".into()),
             _ => Err(format!("Unknown tool: {}", call.name).into()),
         }
     }
 }
 
+/// Formats a tool result for the model's history, truncating very large
+/// outputs and turning search results into compact lists.
+pub fn format_tool_output(tool_name: &str, result: &str) -> String {
+    // Limit the result size for the LLM context to avoid hitting limits
+    let history_result = if result.len() > 100000 {
+        let mut cut = 100000;
+        while !result.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        format!("{}... (truncated, total length: {})", &result[..cut], result.len())
+    } else {
+        result.to_string()
+    };
+
+    if tool_name == "search_files" || tool_name == "find_by_name" {
+        if let Ok(files) = serde_json::from_str::<Vec<serde_json::Value>>(&history_result) {
+            if files.is_empty() {
+                "No files found matching the pattern.".to_string()
+            } else {
+                let file_list = files.iter()
+                    .filter_map(|f| f.get("path").and_then(|p| p.as_str()))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                format!("Found files:\n{}", file_list)
+            }
+        } else {
+            history_result
+        }
+    } else if tool_name == "search_codebase" {
+        if let Ok(symbols) = serde_json::from_str::<Vec<serde_json::Value>>(&history_result) {
+            if symbols.is_empty() {
+                "No symbols found matching the query.".to_string()
+            } else {
+                let symbol_list = symbols.iter()
+                    .take(15) // Limit to top 15 for brevity
+                    .map(|s| {
+                        let name = s.get("name").and_then(|v| v.as_str()).unwrap_or("?");
+                        let kind = s.get("kind").and_then(|v| v.as_str()).unwrap_or("?");
+                        let path = s.get("file_path").and_then(|v| v.as_str()).unwrap_or("?");
+                        let line = s.get("start_line").and_then(|v| v.as_u64()).unwrap_or(0);
+                        format!("{} ({}) in {} (line {})", name, kind, path, line)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                format!("Found symbols:\n{}", symbol_list)
+            }
+        } else {
+            history_result
+        }
+    } else {
+        history_result
+    }
+}
+
 pub fn parse_tool_calls(text: &str) -> Vec<ToolCall> {
+    parse_tool_calls_with(text, &[])
+}
+
+/// Like `parse_tool_calls`, additionally accepting the given tool names (e.g. MCP tools).
+pub fn parse_tool_calls_with(text: &str, extra_tools: &[String]) -> Vec<ToolCall> {
     let mut calls = Vec::new();
-    let allowed_tools = ["search_codebase", "index_codebase", "read_file", "search_files", "find_by_name", "grep", "list_dir", "todo_list", "todo_add", "todo_complete", "todo_delete", "todo_clear"];
+    let allowed_tools: Vec<&str> = ALLOWED_TOOLS.iter().copied()
+        .chain(extra_tools.iter().map(|t| t.as_str()))
+        .collect();
 
     // 1. Парсинг XML-подобного формата с помощью quick-xml
     let mut reader = Reader::from_str(text);
@@ -303,7 +457,7 @@ pub fn parse_tool_calls(text: &str) -> Vec<ToolCall> {
             if text.as_bytes()[end_idx] == b'}' {
                 let json_str = &text[start_idx..=end_idx];
                 if let Ok(v) = serde_json::from_str::<serde_json::Value>(json_str) {
-                    if let Some(call) = try_parse_json_tool_call(&v) {
+                    if let Some(call) = try_parse_json_tool_call(&v, &allowed_tools) {
                         if allowed_tools.contains(&call.name.as_str()) && !calls.iter().any(|c| c.name == call.name && c.parameters == call.parameters) {
                             calls.push(call);
                         }
@@ -329,8 +483,7 @@ pub fn parse_tool_calls(text: &str) -> Vec<ToolCall> {
     calls
 }
 
-fn try_parse_json_tool_call(v: &serde_json::Value) -> Option<ToolCall> {
-    let allowed_tools = ["search_codebase", "index_codebase", "read_file", "search_files", "find_by_name", "grep", "list_dir", "todo_list", "todo_add", "todo_complete", "todo_delete", "todo_clear"];
+fn try_parse_json_tool_call(v: &serde_json::Value, allowed_tools: &[&str]) -> Option<ToolCall> {
 
     // Вариант 1: {"name": "...", "parameters": {...}}
     if let (Some(name), Some(params)) = (v.get("name").and_then(|n| n.as_str()), v.get("parameters")) {
@@ -352,7 +505,7 @@ fn try_parse_json_tool_call(v: &serde_json::Value) -> Option<ToolCall> {
     }
     // Вариант 3: {"tool_call": {"name": "...", "parameters": {...}}}
     if let Some(inner) = v.get("tool_call") {
-        return try_parse_json_tool_call(inner);
+        return try_parse_json_tool_call(inner, allowed_tools);
     }
     None
 }
@@ -361,6 +514,16 @@ fn try_parse_json_tool_call(v: &serde_json::Value) -> Option<ToolCall> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_read_only_tools() {
+        assert!(is_read_only_tool("read_file"));
+        assert!(is_read_only_tool("git_log"));
+        assert!(!is_read_only_tool("git_commit"));
+        assert!(!is_read_only_tool("write_file"));
+        // Sub-agents parse with `parse_tool_calls`, which drops anything else
+        assert!(READ_ONLY_TOOLS.iter().chain(git_tools::GIT_READ_TOOLS).all(|tool| ALLOWED_TOOLS.contains(tool)));
+    }
+
     #[test]
     fn test_parse_xml_invoke() {
         let text = r#"Здесь какой-то текст.
@@ -385,13 +548,13 @@ mod tests {
 
     #[test]
     fn test_parse_xml_entities() {
-        let text = r#"<invoke name="write_file">
-    <parameter name="path">test.txt</parameter>
-    <parameter name="content">A &lt; B &amp;&amp; C &gt; D</parameter>
+        let text = r#"<invoke name="grep">
+    <parameter name="path">src</parameter>
+    <parameter name="query">A &lt; B &amp;&amp; C &gt; D</parameter>
 </invoke>"#;
         let calls = parse_tool_calls(text);
         assert_eq!(calls.len(), 1);
-        assert_eq!(calls[0].parameters["content"], "A < B && C > D");
+        assert_eq!(calls[0].parameters["query"], "A < B && C > D");
     }
 
     #[test]
@@ -0,0 +1,264 @@
+//! Per-run audit trail of the agent loop.
+//!
+//! Events sent to the window are fire-and-forget; the trace keeps what the
+//! model was asked, what it answered and what every tool call did, so a run can
+//! be inspected after the fact. Traces are stored as one JSON file per run in
+//! `<workspace>/.cognitive/runs/`.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::agent::openai::ChatMessage;
+
+/// Older traces beyond this count are removed when a new run starts.
+const MAX_TRACES: usize = 200;
+const PROMPT_PREVIEW_CHARS: usize = 200;
+
+pub fn traces_dir(workspace: &Path) -> PathBuf {
+    workspace.join(".cognitive").join("runs")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum TraceEntry {
+    /// Messages added to the history before a model turn.
+    Prompt {
+        iteration: usize,
+        messages: Vec<ChatMessage>,
+    },
+    Response {
+        iteration: usize,
+        content: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        reasoning: String,
+        duration_ms: u64,
+    },
+    ToolCall {
+        iteration: usize,
+        id: String,
+        name: String,
+        parameters: serde_json::Value,
+        /// `completed`, `error` or `rejected`.
+        status: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        result: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        duration_ms: u64,
+        /// Set for calls made by a `delegate_task` sub-agent.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        subagent_id: Option<String>,
+    },
+    Error {
+        iteration: usize,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunTrace {
+    pub id: String,
+    pub model: String,
+    pub workspace: Option<String>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub completed: Option<bool>,
+    #[serde(default)]
+    pub iterations: usize,
+    pub entries: Vec<TraceEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunSummary {
+    pub id: String,
+    pub model: String,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Whether the run reached a final answer; `None` while running or if it failed.
+    pub completed: Option<bool>,
+    pub iterations: usize,
+    /// Start of the user's request.
+    pub prompt: String,
+    pub tool_calls: usize,
+    pub errors: usize,
+}
+
+impl RunTrace {
+    pub fn summary(&self) -> RunSummary {
+        let prompt = self.entries.iter()
+            .find_map(|e| match e {
+                TraceEntry::Prompt { messages, .. } => messages.iter().find(|m| m.role == "user").map(|m| m.content.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+        let prompt = match prompt.char_indices().nth(PROMPT_PREVIEW_CHARS) {
+            Some((idx, _)) => format!("{}…", &prompt[..idx]),
+            None => prompt,
+        };
+
+        RunSummary {
+            id: self.id.clone(),
+            model: self.model.clone(),
+            started_at: self.started_at,
+            finished_at: self.finished_at,
+            completed: self.completed,
+            iterations: self.iterations,
+            prompt,
+            tool_calls: self.entries.iter().filter(|e| matches!(e, TraceEntry::ToolCall { .. })).count(),
+            errors: self.entries.iter().filter(|e| match e {
+                TraceEntry::Error { .. } => true,
+                TraceEntry::ToolCall { status, .. } => status != "completed",
+                _ => false,
+            }).count(),
+        }
+    }
+}
+
+/// Collects the trace of one run in memory. The file is written when the run
+/// starts and again when it finishes, not on every entry.
+pub struct TraceRecorder {
+    path: PathBuf,
+    trace: Mutex<RunTrace>,
+}
+
+impl TraceRecorder {
+    pub fn start(dir: &Path, model: &str, workspace: Option<&Path>) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        prune(dir, MAX_TRACES.saturating_sub(1));
+
+        let started_at = chrono::Utc::now();
+        // Timestamp prefix keeps file names in chronological order
+        let id = format!("{}-{}", started_at.format("%Y%m%dT%H%M%S"), &uuid::Uuid::new_v4().simple().to_string()[..8]);
+        let recorder = Self {
+            path: dir.join(format!("{}.json", id)),
+            trace: Mutex::new(RunTrace {
+                id,
+                model: model.to_string(),
+                workspace: workspace.map(|w| w.to_string_lossy().to_string()),
+                started_at,
+                finished_at: None,
+                completed: None,
+                iterations: 0,
+                entries: Vec::new(),
+            }),
+        };
+        recorder.save()?;
+        Ok(recorder)
+    }
+
+    pub fn id(&self) -> String {
+        self.trace.lock().unwrap().id.clone()
+    }
+
+    pub fn record(&self, entry: TraceEntry) {
+        self.trace.lock().unwrap().entries.push(entry);
+    }
+
+    /// Marks the run as finished and writes the trace; `completed` is `None`
+    /// when it ended with an error.
+    pub fn finish(&self, completed: Option<bool>, iterations: usize) -> Result<(), String> {
+        {
+            let mut trace = self.trace.lock().unwrap();
+            trace.finished_at = Some(chrono::Utc::now());
+            trace.completed = completed;
+            trace.iterations = iterations;
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let data = {
+            let trace = self.trace.lock().unwrap();
+            serde_json::to_string_pretty(&*trace).map_err(|e| e.to_string())?
+        };
+        std::fs::write(&self.path, data)
+            .map_err(|e| format!("Failed to write agent trace {}: {}", self.path.display(), e))
+    }
+}
+
+fn trace_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .collect())
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Keeps only the newest `keep` traces.
+fn prune(dir: &Path, keep: usize) {
+    let files = trace_files(dir);
+    if files.len() > keep {
+        for path in &files[..files.len() - keep] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+pub fn load_trace(dir: &Path, id: &str) -> Result<RunTrace, String> {
+    if !valid_id(id) {
+        return Err(format!("Invalid run id: {}", id));
+    }
+    let path = dir.join(format!("{}.json", id));
+    let data = std::fs::read_to_string(&path).map_err(|e| format!("Run {} not found: {}", id, e))?;
+    serde_json::from_str(&data).map_err(|e| format!("Failed to parse trace {}: {}", path.display(), e))
+}
+
+/// Summaries of the stored runs, newest first.
+pub fn list_runs(dir: &Path) -> Vec<RunSummary> {
+    trace_files(dir)
+        .into_iter()
+        .rev()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .filter_map(|data| serde_json::from_str::<RunTrace>(&data).ok())
+        .map(|trace| trace.summary())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_and_lists_runs() {
+        let dir = std::env::temp_dir().join(format!("cognitive-trace-{}", uuid::Uuid::new_v4()));
+        let recorder = TraceRecorder::start(&dir, "replay", None).unwrap();
+        recorder.record(TraceEntry::Prompt {
+            iteration: 1,
+            messages: vec![ChatMessage { role: "user".to_string(), content: "Where is main?".to_string() }],
+        });
+        recorder.record(TraceEntry::ToolCall {
+            iteration: 1,
+            id: "1".to_string(),
+            name: "read_file".to_string(),
+            parameters: serde_json::json!({ "path": "missing.rs" }),
+            status: "error".to_string(),
+            result: None,
+            error: Some("not found".to_string()),
+            duration_ms: 3,
+            subagent_id: None,
+        });
+        recorder.finish(Some(false), 1).unwrap();
+
+        let runs = list_runs(&dir);
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].prompt, "Where is main?");
+        assert_eq!((runs[0].tool_calls, runs[0].errors), (1, 1));
+
+        let trace = load_trace(&dir, &recorder.id()).unwrap();
+        assert!(matches!(&trace.entries[1], TraceEntry::ToolCall { name, .. } if name == "read_file"));
+        assert!(load_trace(&dir, "../settings").is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
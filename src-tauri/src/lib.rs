@@ -11,6 +11,7 @@ mod settings;
 mod timeline;
 mod lsp;
 mod agent;
+mod mcp;
 mod storage;
 
 use std::sync::Mutex;
@@ -62,7 +63,28 @@ fn get_initial_state(state: tauri::State<Mutex<AppState>>) -> InitialState {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let args: Vec<String> = std::env::args().collect();
+
+    // `cognitive agent ...` runs a single agent task without a window
+    if args.get(1).map(|a| a == "agent").unwrap_or(false) {
+        std::process::exit(agent::headless::run(&args[2..]));
+    }
+
     let initial_state = parse_cli_args();
+
+    // `--mcp-server` serves the workspace's tools over stdio instead of opening a window
+    if args.iter().any(|a| a == "--mcp-server") {
+        let workspace = initial_state.workspace.clone()
+            .map(std::path::PathBuf::from)
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| std::path::PathBuf::from("."));
+        let allow_writes = args.iter().any(|a| a == "--mcp-allow-writes");
+        if let Err(e) = mcp::server::run_stdio(workspace, allow_writes) {
+            eprintln!("MCP server error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
     
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
@@ -76,8 +98,9 @@ pub fn run() {
         .manage(settings::SettingsState::new())
         .manage(session::SessionState::new())
         .manage(command_palette::CommandPaletteState::new())
-        .manage(lsp::LspState::new())
+        .manage(lsp::manager::LspManager::new())
         .manage(agent::AgentState::default())
+        .manage(mcp::McpServerState::default())
         .manage(Mutex::new(AppState { 
             initial_state,
         }))
@@ -181,6 +204,7 @@ pub fn run() {
             problems::invalidate_problems_cache,
             problems::get_problems_cache_stats,
             problems::check_files,
+            problems::clear_review_problems,
             outline::get_outline,
             outline::get_outline_from_content,
             timeline::timeline_save_snapshot,
@@ -254,6 +278,23 @@ pub fn run() {
             lsp::lsp_initialize,
             lsp::lsp_did_open,
             lsp::lsp_did_change,
+            lsp::lsp_did_close,
+            lsp::lsp_did_save,
+            lsp::lsp_close_workspace,
+            lsp::lsp_restart_server,
+            lsp::lsp_pull_workspace_diagnostics,
+            lsp::lsp_list_servers,
+            lsp::lsp_check_server,
+            lsp::features::lsp_completion,
+            lsp::features::lsp_hover,
+            lsp::features::lsp_signature_help,
+            lsp::features::lsp_definition,
+            lsp::features::lsp_type_definition,
+            lsp::features::lsp_references,
+            lsp::features::lsp_document_highlight,
+            lsp::features::lsp_rename,
+            lsp::features::lsp_code_action,
+            lsp::workspace_edit::lsp_apply_workspace_edit,
             lsp::css_lsp_initialize,
             lsp::css_lsp_did_open,
             lsp::css_lsp_did_change,
@@ -262,11 +303,40 @@ pub fn run() {
             agent::agentrouter_set_workspace,
             agent::agentrouter_index_codebase,
             agent::agentrouter_list_ollama_models,
+            agent::agentrouter_pull_ollama_model,
+            agent::agentrouter_delete_ollama_model,
+            agent::agentrouter_show_ollama_model,
+            agent::agentrouter_list_running_ollama_models,
+            agent::agentrouter_set_ollama_keep_alive,
             agent::agent_execute_tool,
+            agent::agent_list_runs,
+            agent::agent_export_run,
             agent::agentrouter_get_system_prompt,
+            agent::agentrouter_list_profile_models,
             agent::agentrouter_chat_complete,
-            agent::agentrouter_chat_stream
+            agent::agentrouter_chat_stream,
+            agent::agent_respond_tool_approval,
+            agent::ai_inline_complete,
+            agent::ai_generate_commit_message,
+            agent::ai_review_changes,
+            agent::ai_edit_selection,
+            mcp::mcp_list_servers,
+            mcp::mcp_reload,
+            mcp::mcp_set_server_enabled,
+            mcp::mcp_set_server_approval,
+            mcp::mcp_call_tool,
+            mcp::mcp_read_resource,
+            mcp::mcp_server_start,
+            mcp::mcp_server_stop,
+            mcp::mcp_server_status
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                // Let language servers shut down cleanly instead of being orphaned
+                let lsp = app.state::<lsp::manager::LspManager>();
+                tauri::async_runtime::block_on(lsp.shutdown_all(app));
+            }
+        });
 }
@@ -1,140 +1,115 @@
-use std::process::{Command, Stdio, Child};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::{Command, Stdio, Child, ChildStdin};
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::io::{BufRead, BufReader, Write, BufWriter, Read};
 use std::thread;
+use std::time::Duration;
+use lsp_types::notification::Notification as LspNotification;
+use lsp_types::request::Request as LspRequest;
+use serde_json::{json, Value};
 use tauri::{AppHandle, Emitter};
-use serde_json::json;
-use crate::lsp::protocol::*;
-
-#[derive(Debug, Clone)]
-pub enum LanguageServerType {
-    TypeScript,
-    Go,
-    Python,
-}
+use tokio::sync::{oneshot, OnceCell};
+use crate::lsp::{diagnostics, document_uri};
+use crate::lsp::documents::{content_changes, OpenDocument, SyncOptions};
+use crate::settings::LanguageServerSettings;
+
+/// How long a request waits for the server before it is cancelled.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// `initialize` may index the whole workspace first.
+const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(120);
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+/// Time a server gets to exit after the `exit` notification before it is killed.
+const EXIT_GRACE: Duration = Duration::from_secs(1);
+const EXIT_POLL: Duration = Duration::from_millis(50);
+
+const METHOD_NOT_FOUND: i64 = -32601;
 
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value, String>>>>>;
+type Writer = Arc<Mutex<Option<BufWriter<ChildStdin>>>>;
+
+/// Connection to one language server process.
+///
+/// Requests are correlated with their responses by id: `request` registers a
+/// oneshot channel that the stdout reader thread completes.
 pub struct LspClient {
-    process: Option<Child>,
-    writer: Option<Arc<Mutex<BufWriter<std::process::ChildStdin>>>>,
-    sequence_id: u64,
-    server_type: Option<LanguageServerType>,
+    server_id: String,
+    process: Mutex<Option<Child>>,
+    writer: Writer,
+    next_id: AtomicU64,
+    pending: Pending,
+    /// Answers `workspace/configuration` requests, looked up by section.
+    configuration: Arc<Value>,
+    initialization_options: Option<Value>,
+    capabilities: OnceCell<lsp_types::ServerCapabilities>,
+    /// Open documents by URI, with the text last sent so changes can be diffed.
+    documents: Mutex<HashMap<String, OpenDocument>>,
+    /// Result ids of the last pulled diagnostic reports, by URI.
+    diagnostic_result_ids: Mutex<HashMap<String, String>>,
 }
 
 impl LspClient {
-    pub fn new() -> Self {
-        Self {
-            process: None,
-            writer: None,
-            sequence_id: 0,
-            server_type: None,
-        }
-    }
-
-    pub fn start(&mut self, app_handle: AppHandle, server_type: LanguageServerType) -> Result<(), String> {
-        self.server_type = Some(server_type.clone());
-        
-        let mut cmd = match server_type {
-            LanguageServerType::TypeScript => {
-                let mut c = Command::new("npx");
-                c.args(&["typescript-language-server", "--stdio"]);
-                c
-            }
-            LanguageServerType::Go => {
-                let c = Command::new("gopls");
-                c
-            }
-            LanguageServerType::Python => {
-                let c = Command::new("pylsp");
-                c
-            }
+    /// Spawns the server. `on_exit` runs once its stdout closes, whether it
+    /// crashed or was stopped.
+    pub fn start(app_handle: AppHandle, config: &LanguageServerSettings, on_exit: impl FnOnce() + Send + 'static) -> Result<Self, String> {
+        // Through the shell on Windows so `.cmd` shims such as `npx` resolve
+        #[cfg(target_os = "windows")]
+        let mut cmd = {
+            let mut c = Command::new("cmd");
+            c.arg("/C").arg(&config.command);
+            c
         };
-        
-        cmd.stdin(Stdio::piped())
+        #[cfg(not(target_os = "windows"))]
+        let mut cmd = Command::new(&config.command);
+
+        cmd.args(&config.args)
+           .envs(&config.env)
+           .stdin(Stdio::piped())
            .stdout(Stdio::piped())
            .stderr(Stdio::piped());
 
-        #[cfg(target_os = "windows")]
-        {
-            cmd = match server_type {
-                LanguageServerType::TypeScript => {
-                    let mut c = Command::new("cmd");
-                    c.args(&["/C", "npx", "typescript-language-server", "--stdio"]);
-                    c
-                }
-                LanguageServerType::Go => {
-                    let mut c = Command::new("cmd");
-                    c.args(&["/C", "gopls"]);
-                    c
-                }
-                LanguageServerType::Python => {
-                    let mut c = Command::new("cmd");
-                    c.args(&["/C", "pylsp"]);
-                    c
-                }
-            };
-            cmd.stdin(Stdio::piped());
-            cmd.stdout(Stdio::piped());
-            cmd.stderr(Stdio::piped());
-        }
-
-        let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn LSP: {}", e))?;
+        let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn {}: {}", config.command, e))?;
         
         let stdin = child.stdin.take().ok_or("Failed to open stdin")?;
         let stdout = child.stdout.take().ok_or("Failed to open stdout")?;
         let stderr = child.stderr.take().ok_or("Failed to open stderr")?;
 
-        let writer = Arc::new(Mutex::new(BufWriter::new(stdin)));
-        self.writer = Some(writer.clone());
-        self.process = Some(child);
+        let client = Self {
+            server_id: config.id.clone(),
+            process: Mutex::new(Some(child)),
+            writer: Arc::new(Mutex::new(Some(BufWriter::new(stdin)))),
+            next_id: AtomicU64::new(1),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            configuration: Arc::new(config.settings.clone().unwrap_or(Value::Null)),
+            initialization_options: config.initialization_options.clone(),
+            capabilities: OnceCell::new(),
+            documents: Mutex::new(HashMap::new()),
+            diagnostic_result_ids: Mutex::new(HashMap::new()),
+        };
 
         // Stdout reader (LSP messages)
         let app_handle_clone = app_handle.clone();
+        let writer = client.writer.clone();
+        let pending = client.pending.clone();
+        let configuration = client.configuration.clone();
+        let server_id = client.server_id.clone();
         thread::spawn(move || {
             let mut reader = BufReader::new(stdout);
-            loop {
-                let mut header = String::new();
-                if reader.read_line(&mut header).unwrap_or(0) == 0 {
-                    break;
-                }
-
-                if header.trim().is_empty() {
-                    continue;
-                }
-
-                let parts: Vec<&str> = header.split(": ").collect();
-                if parts.len() == 2 && parts[0] == "Content-Length" {
-                    let len: usize = parts[1].trim().parse().unwrap_or(0);
-                    // Skip empty line
-                    let mut empty = String::new();
-                    reader.read_line(&mut empty).unwrap();
-
-                    let mut body_buf = vec![0; len];
-                    if reader.read_exact(&mut body_buf).is_ok() {
-                        let body_str = String::from_utf8_lossy(&body_buf);
-                        if let Ok(msg) = serde_json::from_str::<LspMessage>(&body_str) {
-                            match msg {
-                                LspMessage::Notification(notif) => {
-                                    if notif.method == "textDocument/publishDiagnostics" {
-                                        if let Some(params) = notif.params {
-                                           if let Ok(diag_params) = serde_json::from_value::<PublishDiagnosticsParams>(params) {
-                                                let _ = app_handle_clone.emit("lsp:diagnostics", diag_params);
-                                           }
-                                        }
-                                    }
-                                }
-                                LspMessage::Response(resp) => {
-                                    let _ = app_handle_clone.emit("lsp:response", format!("LSP Response: {:?}", resp));
-                                }
-                                _ => {
-                                    let _ = app_handle_clone.emit("lsp:log", format!("LSP Message: {:?}", msg));
-                                }
-                            }
-                        } else {
-                            let _ = app_handle_clone.emit("lsp:log", format!("Failed to parse LSP message: {}", body_str));
-                        }
+            while let Ok(Some(body)) = read_message(&mut reader) {
+                match serde_json::from_slice::<Value>(&body) {
+                    Ok(msg) => handle_message(&app_handle_clone, &server_id, &writer, &pending, &configuration, msg),
+                    Err(_) => {
+                        let _ = app_handle_clone.emit("lsp:log", format!("Failed to parse LSP message: {}", String::from_utf8_lossy(&body)));
                     }
                 }
             }
+            // Nobody will answer the outstanding requests any more
+            for (_, tx) in pending.lock().unwrap().drain() {
+                let _ = tx.send(Err("Language server exited".to_string()));
+            }
+            on_exit();
         });
 
         // Stderr reader (Logs)
@@ -149,113 +124,499 @@ impl LspClient {
             }
         });
 
-        Ok(())
+        Ok(client)
     }
 
-    fn write(&mut self, method: &str, params: serde_json::Value, is_notification: bool) -> Result<(), String> {
-        if let Some(writer) = &self.writer {
-            self.sequence_id += 1;
-            let msg = if is_notification {
-                json!({
-                    "jsonrpc": "2.0",
-                    "method": method,
-                    "params": params
-                })
-            } else {
-                 json!({
-                    "jsonrpc": "2.0",
-                    "id": self.sequence_id,
-                    "method": method,
-                    "params": params
-                })
-            };
+    fn send(&self, msg: Value) -> Result<(), String> {
+        write_message(&self.writer, &msg)
+    }
+
+    fn notify_value(&self, method: &str, params: Value) -> Result<(), String> {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params
+        }))
+    }
 
-            let body = msg.to_string();
-            let content = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
-            
-            let mut w = writer.lock().map_err(|_| "Failed to lock writer")?;
-            w.write_all(content.as_bytes()).map_err(|e| e.to_string())?;
-            w.flush().map_err(|e| e.to_string())?;
+    /// Sends a request and waits for its result, cancelling it after `timeout`.
+    pub async fn request_value(&self, method: &str, params: Value, timeout: Duration) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        // Registered before sending, so a fast response is not lost
+        self.pending.lock().unwrap().insert(id, tx);
+
+        let sent = self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params
+        }));
+        if let Err(e) = sent {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err("Language server exited".to_string()),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                let _ = self.notify_value("$/cancelRequest", json!({ "id": id }));
+                Err(format!("{} timed out after {}s", method, timeout.as_secs()))
+            }
         }
-        Ok(())
     }
 
-    pub fn initialize(&mut self, root_uri: String) -> Result<(), String> {
-        let params = InitializeParams {
-            process_id: Some(std::process::id()),
-            root_uri: Some(root_uri.clone()),
-            capabilities: ClientCapabilities {
-                text_document: Some(TextDocumentClientCapabilities {
-                    completion: Some(CompletionClientCapabilities {
-                        completion_item: Some(CompletionItemCapabilities {
-                            snippet_support: Some(true),
-                        }),
-                    }),
-                    publish_diagnostics: Some(PublishDiagnosticsCapabilities {
-                        tag_support: Some(DiagnosticTagSupport {
-                            value_set: vec![1, 2], // 1: Unnecessary, 2: Deprecated
-                        }),
-                    }),
-                }),
-                workspace: Some(WorkspaceClientCapabilities {
-                    workspace_folders: Some(false),
+    pub fn notify<N: LspNotification>(&self, params: N::Params) -> Result<(), String> {
+        let params = serde_json::to_value(params).map_err(|e| e.to_string())?;
+        self.notify_value(N::METHOD, params)
+    }
+
+    /// Typed request, e.g. `client.request::<lsp_types::request::HoverRequest>(params)`.
+    pub async fn request<R: LspRequest>(&self, params: R::Params) -> Result<R::Result, String> {
+        let params = serde_json::to_value(params).map_err(|e| e.to_string())?;
+        let result = self.request_value(R::METHOD, params, REQUEST_TIMEOUT).await?;
+        serde_json::from_value(result).map_err(|e| format!("Invalid {} response: {}", R::METHOD, e))
+    }
+
+    /// Performs the `initialize` handshake once; concurrent callers wait for the same result.
+    pub async fn initialize(&self, root: &Path) -> Result<&lsp_types::ServerCapabilities, String> {
+        self.capabilities.get_or_try_init(|| async {
+            let root_uri = document_uri(&root.to_string_lossy())?;
+            let name = root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            #[allow(deprecated)]
+            let params = lsp_types::InitializeParams {
+                process_id: Some(std::process::id()),
+                root_uri: Some(root_uri.clone()),
+                workspace_folders: Some(vec![lsp_types::WorkspaceFolder { uri: root_uri, name }]),
+                initialization_options: self.initialization_options.clone(),
+                capabilities: client_capabilities(),
+                client_info: Some(lsp_types::ClientInfo {
+                    name: "Cognitive".to_string(),
+                    version: Some(env!("CARGO_PKG_VERSION").to_string()),
                 }),
-            },
-        };
-        self.write("initialize", serde_json::to_value(params).unwrap(), false)?;
-        // Send initialized notification immediately after
-        self.write("initialized", json!({}), true)?;
-        
-        // Request diagnostics for current workspace
-        self.write("textDocument/diagnostic", json!({
-            "textDocument": {
-                "uri": root_uri
+                ..Default::default()
+            };
+            let params = serde_json::to_value(params).map_err(|e| e.to_string())?;
+            let result = self.request_value(lsp_types::request::Initialize::METHOD, params, INITIALIZE_TIMEOUT).await?;
+            let result: lsp_types::InitializeResult = serde_json::from_value(result)
+                .map_err(|e| format!("Invalid initialize response: {}", e))?;
+            self.notify::<lsp_types::notification::Initialized>(lsp_types::InitializedParams {})?;
+            // Some servers (the vscode-langservers among them) only read settings pushed to them
+            if !self.configuration.is_null() {
+                self.notify::<lsp_types::notification::DidChangeConfiguration>(lsp_types::DidChangeConfigurationParams {
+                    settings: (*self.configuration).clone(),
+                })?;
             }
-        }), true)?;
-        
-        Ok(())
+            Ok(result.capabilities)
+        }).await
     }
 
-    pub fn did_open(&mut self, uri: String, content: String) -> Result<(), String> {
-        // Определяем язык по расширению файла
-        let language_id = if uri.ends_with(".ts") || uri.ends_with(".tsx") {
-            "typescript"
-        } else if uri.ends_with(".js") || uri.ends_with(".jsx") {
-            "javascript"
-        } else if uri.ends_with(".go") {
-            "go"
-        } else if uri.ends_with(".py") {
-            "python"
-        } else {
-            // Определяем на основе типа сервера
-            match self.server_type {
-                Some(LanguageServerType::Go) => "go",
-                Some(LanguageServerType::Python) => "python",
-                _ => "typescript" // По умолчанию
-            }
+    pub fn server_id(&self) -> &str {
+        &self.server_id
+    }
+
+    /// Server capabilities, once `initialize` has completed.
+    pub fn capabilities(&self) -> Option<&lsp_types::ServerCapabilities> {
+        self.capabilities.get()
+    }
+
+    pub fn diagnostic_result_id(&self, uri: &str) -> Option<String> {
+        self.diagnostic_result_ids.lock().unwrap().get(uri).cloned()
+    }
+
+    pub fn diagnostic_result_ids(&self) -> Vec<(String, String)> {
+        self.diagnostic_result_ids.lock().unwrap().iter().map(|(uri, id)| (uri.clone(), id.clone())).collect()
+    }
+
+    pub fn set_diagnostic_result_id(&self, uri: String, result_id: Option<String>) {
+        let mut ids = self.diagnostic_result_ids.lock().unwrap();
+        match result_id {
+            Some(id) => ids.insert(uri, id),
+            None => ids.remove(&uri),
         };
-        
-        let params = DidOpenTextDocumentParams {
-            text_document: TextDocumentItem {
+    }
+
+    fn sync_options(&self) -> SyncOptions {
+        SyncOptions::from_capabilities(self.capabilities.get())
+    }
+
+    pub fn is_open(&self, uri: &lsp_types::Uri) -> bool {
+        self.documents.lock().unwrap().contains_key(uri.as_str())
+    }
+
+    /// Version of an open document as last sent to the server.
+    pub fn document_version(&self, uri: &lsp_types::Uri) -> Option<i32> {
+        self.documents.lock().unwrap().get(uri.as_str()).map(|doc| doc.version)
+    }
+
+    /// Version and text of an open document as last sent to the server.
+    pub fn open_document(&self, uri: &lsp_types::Uri) -> Option<(i32, String)> {
+        self.documents.lock().unwrap().get(uri.as_str()).map(|doc| (doc.version, doc.text.clone()))
+    }
+
+    pub fn did_open(&self, uri: lsp_types::Uri, language_id: &str, version: i32, content: String) -> Result<(), String> {
+        self.documents.lock().unwrap().insert(uri.as_str().to_string(), OpenDocument {
+            language_id: language_id.to_string(),
+            version,
+            text: content.clone(),
+        });
+        if !self.sync_options().open_close {
+            return Ok(());
+        }
+        self.notify::<lsp_types::notification::DidOpenTextDocument>(lsp_types::DidOpenTextDocumentParams {
+            text_document: lsp_types::TextDocumentItem {
                 uri,
-                language_id: language_id.into(),
-                version: 1,
+                language_id: language_id.to_string(),
+                version,
                 text: content,
             },
+        })
+    }
+
+    /// Sends the change from the last known text to `content`, as a single
+    /// range when the server syncs incrementally.
+    pub fn did_change(&self, uri: lsp_types::Uri, content: String, version: i32) -> Result<(), String> {
+        let changes = {
+            let mut documents = self.documents.lock().unwrap();
+            let doc = documents.get_mut(uri.as_str()).ok_or_else(|| format!("{} is not open", uri.as_str()))?;
+            let changes = content_changes(&doc.text, &content, self.sync_options().change);
+            doc.version = version;
+            doc.text = content;
+            changes
         };
-        self.write("textDocument/didOpen", serde_json::to_value(params).unwrap(), true)
+        let Some(content_changes) = changes else {
+            return Ok(());
+        };
+        self.notify::<lsp_types::notification::DidChangeTextDocument>(lsp_types::DidChangeTextDocumentParams {
+            text_document: lsp_types::VersionedTextDocumentIdentifier { uri, version },
+            content_changes,
+        })
     }
 
-    pub fn did_change(&mut self, uri: String, content: String, version: i32) -> Result<(), String> {
-         let params = DidChangeTextDocumentParams {
-            text_document: VersionedTextDocumentIdentifier {
-                uri,
-                version,
-            },
-            content_changes: vec![TextDocumentContentChangeEvent {
-                text: content
-            }],
+    pub fn did_save(&self, uri: lsp_types::Uri, content: Option<String>) -> Result<(), String> {
+        let Some(include_text) = self.sync_options().save else {
+            return Ok(());
+        };
+        let text = match content {
+            Some(text) if include_text => Some(text),
+            _ if include_text => self.documents.lock().unwrap().get(uri.as_str()).map(|doc| doc.text.clone()),
+            _ => None,
         };
-        self.write("textDocument/didChange", serde_json::to_value(params).unwrap(), true)
+        self.notify::<lsp_types::notification::DidSaveTextDocument>(lsp_types::DidSaveTextDocumentParams {
+            text_document: lsp_types::TextDocumentIdentifier { uri },
+            text,
+        })
+    }
+
+    /// Closes the document if it is open; the server can drop its state for it.
+    pub fn did_close(&self, uri: lsp_types::Uri) -> Result<(), String> {
+        self.diagnostic_result_ids.lock().unwrap().remove(uri.as_str());
+        if self.documents.lock().unwrap().remove(uri.as_str()).is_none() || !self.sync_options().open_close {
+            return Ok(());
+        }
+        self.notify::<lsp_types::notification::DidCloseTextDocument>(lsp_types::DidCloseTextDocumentParams {
+            text_document: lsp_types::TextDocumentIdentifier { uri },
+        })
+    }
+
+    /// Takes over the documents `previous` had open, to be sent by `reopen_documents`.
+    pub fn adopt_documents(&self, previous: &LspClient) {
+        let documents = previous.documents.lock().unwrap().clone();
+        self.documents.lock().unwrap().extend(documents);
+    }
+
+    /// Sends `didOpen` for every tracked document, after a restart.
+    pub fn reopen_documents(&self) -> Result<(), String> {
+        if !self.sync_options().open_close {
+            return Ok(());
+        }
+        let documents: Vec<(String, OpenDocument)> = self.documents.lock().unwrap()
+            .iter()
+            .map(|(uri, doc)| (uri.clone(), doc.clone()))
+            .collect();
+        for (uri, doc) in documents {
+            let uri = lsp_types::Uri::from_str(&uri).map_err(|e| format!("Invalid document URI {}: {}", uri, e))?;
+            self.notify::<lsp_types::notification::DidOpenTextDocument>(lsp_types::DidOpenTextDocumentParams {
+                text_document: lsp_types::TextDocumentItem {
+                    uri,
+                    language_id: doc.language_id,
+                    version: doc.version,
+                    text: doc.text,
+                },
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.process.lock().unwrap().as_mut().is_some_and(|p| matches!(p.try_wait(), Ok(None)))
+    }
+
+    /// `shutdown` then `exit`, as the protocol asks; the process is killed if
+    /// it does not exit promptly.
+    pub async fn shutdown(&self) {
+        if self.capabilities.initialized() && self.is_running() {
+            let _ = self.request_value(lsp_types::request::Shutdown::METHOD, Value::Null, SHUTDOWN_TIMEOUT).await;
+        }
+        if let Some(child) = self.exit() {
+            let _ = tokio::task::spawn_blocking(move || reap(child)).await;
+        }
+    }
+
+    /// Sends `exit` and closes stdin; returns the process if it is still running.
+    fn exit(&self) -> Option<Child> {
+        let _ = self.notify::<lsp_types::notification::Exit>(());
+        self.writer.lock().unwrap().take();
+        let mut child = self.process.lock().unwrap().take()?;
+        matches!(child.try_wait(), Ok(None)).then_some(child)
+    }
+}
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        // Dropping must not block, so a server still running waits out its grace period elsewhere
+        if let Some(child) = self.exit() {
+            thread::spawn(move || reap(child));
+        }
+    }
+}
+
+/// Waits out the exit grace period, then kills the server; blocks the calling thread.
+fn reap(mut child: Child) {
+    let mut waited = Duration::ZERO;
+    while matches!(child.try_wait(), Ok(None)) {
+        if waited >= EXIT_GRACE {
+            let _ = child.kill();
+            let _ = child.wait();
+            return;
+        }
+        thread::sleep(EXIT_POLL);
+        waited += EXIT_POLL;
+    }
+}
+
+fn client_capabilities() -> lsp_types::ClientCapabilities {
+    use lsp_types::*;
+    ClientCapabilities {
+        text_document: Some(TextDocumentClientCapabilities {
+            synchronization: Some(TextDocumentSyncClientCapabilities {
+                did_save: Some(true),
+                ..Default::default()
+            }),
+            completion: Some(CompletionClientCapabilities {
+                completion_item: Some(CompletionItemCapability {
+                    snippet_support: Some(true),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            hover: Some(HoverClientCapabilities {
+                content_format: Some(vec![MarkupKind::Markdown, MarkupKind::PlainText]),
+                ..Default::default()
+            }),
+            signature_help: Some(SignatureHelpClientCapabilities {
+                signature_information: Some(SignatureInformationSettings {
+                    documentation_format: Some(vec![MarkupKind::Markdown, MarkupKind::PlainText]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            definition: Some(GotoCapability { link_support: Some(true), ..Default::default() }),
+            type_definition: Some(GotoCapability { link_support: Some(true), ..Default::default() }),
+            references: Some(DynamicRegistrationClientCapabilities::default()),
+            document_highlight: Some(DynamicRegistrationClientCapabilities::default()),
+            rename: Some(RenameClientCapabilities::default()),
+            code_action: Some(CodeActionClientCapabilities {
+                code_action_literal_support: Some(CodeActionLiteralSupport {
+                    code_action_kind: CodeActionKindLiteralSupport {
+                        value_set: [
+                            CodeActionKind::QUICKFIX,
+                            CodeActionKind::REFACTOR,
+                            CodeActionKind::REFACTOR_EXTRACT,
+                            CodeActionKind::REFACTOR_INLINE,
+                            CodeActionKind::REFACTOR_REWRITE,
+                            CodeActionKind::SOURCE,
+                            CodeActionKind::SOURCE_ORGANIZE_IMPORTS,
+                        ].iter().map(|kind| kind.as_str().to_string()).collect(),
+                    },
+                }),
+                ..Default::default()
+            }),
+            diagnostic: Some(DiagnosticClientCapabilities {
+                related_document_support: Some(true),
+                ..Default::default()
+            }),
+            publish_diagnostics: Some(PublishDiagnosticsClientCapabilities {
+                tag_support: Some(TagSupport {
+                    value_set: vec![DiagnosticTag::UNNECESSARY, DiagnosticTag::DEPRECATED],
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+        workspace: Some(WorkspaceClientCapabilities {
+            workspace_edit: Some(WorkspaceEditClientCapabilities {
+                document_changes: Some(true),
+                resource_operations: Some(vec![ResourceOperationKind::Create, ResourceOperationKind::Rename, ResourceOperationKind::Delete]),
+                failure_handling: Some(FailureHandlingKind::Abort),
+                ..Default::default()
+            }),
+            workspace_folders: Some(true),
+            configuration: Some(true),
+            ..Default::default()
+        }),
+        window: Some(WindowClientCapabilities {
+            work_done_progress: Some(true),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn write_message(writer: &Writer, msg: &Value) -> Result<(), String> {
+    let body = msg.to_string();
+    let content = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+
+    let mut guard = writer.lock().map_err(|_| "Failed to lock writer")?;
+    let w = guard.as_mut().ok_or("Language server is not running")?;
+    w.write_all(content.as_bytes()).map_err(|e| e.to_string())?;
+    w.flush().map_err(|e| e.to_string())
+}
+
+/// Reads one framed message body; `None` at end of stream.
+fn read_message(reader: &mut impl BufRead) -> std::io::Result<Option<Vec<u8>>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            // Blank line ends the headers; stray blank lines before them are skipped
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length.unwrap_or(0)];
+    reader.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+/// Payload of `lsp:progress`: a work-done progress `begin`, `report` or `end`
+/// from a server, e.g. while it indexes the workspace.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressEvent {
+    pub server_id: String,
+    pub token: lsp_types::ProgressToken,
+    #[serde(flatten)]
+    pub progress: lsp_types::WorkDoneProgress,
+}
+
+fn handle_message(app: &AppHandle, server_id: &str, writer: &Writer, pending: &Pending, configuration: &Value, msg: Value) {
+    let method = msg.get("method").and_then(Value::as_str);
+    let id = msg.get("id").cloned();
+
+    match (method, id) {
+        // Server-to-client request
+        (Some(method), Some(id)) => {
+            let params = msg.get("params").cloned().unwrap_or(Value::Null);
+            let reply = match answer_server_request(method, &params, configuration) {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+            };
+            if let Err(e) = write_message(writer, &reply) {
+                let _ = app.emit("lsp:log", format!("Failed to answer {}: {}", method, e));
+            }
+        }
+        (Some(method), None) => {
+            if method == "textDocument/publishDiagnostics" {
+                if let Some(params) = msg.get("params") {
+                    if let Ok(diag_params) = serde_json::from_value::<lsp_types::PublishDiagnosticsParams>(params.clone()) {
+                        diagnostics::publish(app, server_id, diag_params);
+                    }
+                }
+            } else if method == "$/progress" {
+                // Partial results are not requested, so only work-done progress arrives here
+                if let Some(Ok(params)) = msg.get("params").map(|p| serde_json::from_value::<lsp_types::ProgressParams>(p.clone())) {
+                    let lsp_types::ProgressParamsValue::WorkDone(progress) = params.value;
+                    let _ = app.emit("lsp:progress", ProgressEvent { server_id: server_id.to_string(), token: params.token, progress });
+                }
+            } else {
+                let _ = app.emit("lsp:log", format!("LSP notification: {}", method));
+            }
+        }
+        (None, Some(id)) => {
+            let Some(tx) = id.as_u64().and_then(|id| pending.lock().unwrap().remove(&id)) else {
+                return;
+            };
+            let result = match msg.get("error") {
+                Some(error) => Err(error.get("message").and_then(Value::as_str).unwrap_or("Unknown LSP error").to_string()),
+                None => Ok(msg.get("result").cloned().unwrap_or(Value::Null)),
+            };
+            let _ = tx.send(result);
+        }
+        (None, None) => {
+            let _ = app.emit("lsp:log", format!("Unexpected LSP message: {}", msg));
+        }
+    }
+}
+
+/// Result for a request the server sends to us, or a JSON-RPC error.
+fn answer_server_request(method: &str, params: &Value, configuration: &Value) -> Result<Value, (i64, String)> {
+    match method {
+        "workspace/configuration" => {
+            let items = params.get("items").and_then(Value::as_array).cloned().unwrap_or_default();
+            Ok(Value::Array(items.iter()
+                .map(|item| match item.get("section").and_then(Value::as_str) {
+                    Some(section) => section.split('.')
+                        .try_fold(configuration, |value, key| value.get(key))
+                        .cloned()
+                        .unwrap_or(Value::Null),
+                    None => configuration.clone(),
+                })
+                .collect()))
+        }
+        // Accepted so servers proceed; there is nothing to set up on our side
+        "client/registerCapability" | "client/unregisterCapability" | "window/workDoneProgress/create" => Ok(Value::Null),
+        "window/showMessageRequest" => Ok(Value::Null),
+        _ => Err((METHOD_NOT_FOUND, format!("Unhandled method: {}", method))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_framed_messages() {
+        let body = r#"{"jsonrpc":"2.0","id":1,"result":null}"#;
+        let data = format!("Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n{}", body.len(), body);
+        let mut reader = std::io::Cursor::new(data.into_bytes());
+        assert_eq!(read_message(&mut reader).unwrap().unwrap(), body.as_bytes());
+        assert!(read_message(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn answers_configuration_by_section() {
+        let configuration = json!({ "python": { "analysis": { "typeCheckingMode": "strict" } } });
+        let params = json!({ "items": [{ "section": "python.analysis" }, { "section": "gopls" }] });
+        assert_eq!(
+            answer_server_request("workspace/configuration", &params, &configuration).unwrap(),
+            json!([{ "typeCheckingMode": "strict" }, null])
+        );
+        assert_eq!(answer_server_request("window/workDoneProgress/create", &json!({ "token": 1 }), &Value::Null).unwrap(), Value::Null);
+        assert_eq!(answer_server_request("workspace/unknown", &Value::Null, &Value::Null).unwrap_err().0, METHOD_NOT_FOUND);
     }
 }
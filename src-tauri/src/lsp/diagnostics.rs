@@ -0,0 +1,209 @@
+//! Pull diagnostics (`textDocument/diagnostic`, `workspace/diagnostic`).
+//!
+//! Servers that advertise a `diagnosticProvider` report diagnostics when asked
+//! instead of publishing them. Full reports go through `publish` like pushed
+//! ones; the result ids they carry let the server answer "unchanged" next time.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use lsp_types::request::{DocumentDiagnosticRequest, WorkspaceDiagnosticRequest};
+use lsp_types::{
+    DiagnosticOptions, DiagnosticServerCapabilities, DocumentDiagnosticParams, DocumentDiagnosticReport,
+    DocumentDiagnosticReportKind, DocumentDiagnosticReportResult, FullDocumentDiagnosticReport,
+    PreviousResultId, PublishDiagnosticsParams, ServerCapabilities, TextDocumentIdentifier, Uri,
+    WorkspaceDiagnosticParams, WorkspaceDiagnosticReportResult, WorkspaceDocumentDiagnosticReport,
+};
+use tauri::{AppHandle, Emitter};
+use crate::lsp::client::LspClient;
+
+/// Typing produces a change per keystroke; only the last one is pulled for.
+const PULL_DELAY: Duration = Duration::from_millis(300);
+
+pub fn diagnostic_options(capabilities: &ServerCapabilities) -> Option<&DiagnosticOptions> {
+    match capabilities.diagnostic_provider.as_ref()? {
+        DiagnosticServerCapabilities::Options(options) => Some(options),
+        DiagnosticServerCapabilities::RegistrationOptions(registration) => Some(&registration.diagnostic_options),
+    }
+}
+
+/// What a report means for the editor: diagnostics to publish, and the
+/// result id to send back next time, by URI.
+#[derive(Default)]
+struct Outcome {
+    publish: Vec<PublishDiagnosticsParams>,
+    result_ids: Vec<(String, Option<String>)>,
+}
+
+impl Outcome {
+    fn full(&mut self, uri: Uri, version: Option<i32>, report: FullDocumentDiagnosticReport) {
+        self.result_ids.push((uri.as_str().to_string(), report.result_id));
+        self.publish.push(PublishDiagnosticsParams { uri, diagnostics: report.items, version });
+    }
+
+    fn related(&mut self, related: Option<HashMap<Uri, DocumentDiagnosticReportKind>>) {
+        for (uri, report) in related.unwrap_or_default() {
+            match report {
+                DocumentDiagnosticReportKind::Full(report) => self.full(uri, None, report),
+                DocumentDiagnosticReportKind::Unchanged(report) => self.result_ids.push((uri.as_str().to_string(), Some(report.result_id))),
+            }
+        }
+    }
+}
+
+fn document_outcome(uri: Uri, result: DocumentDiagnosticReportResult) -> Outcome {
+    let mut outcome = Outcome::default();
+    match result {
+        DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) => {
+            outcome.full(uri, None, report.full_document_diagnostic_report);
+            outcome.related(report.related_documents);
+        }
+        DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(report)) => {
+            outcome.result_ids.push((uri.as_str().to_string(), Some(report.unchanged_document_diagnostic_report.result_id)));
+            outcome.related(report.related_documents);
+        }
+        DocumentDiagnosticReportResult::Partial(partial) => outcome.related(partial.related_documents),
+    }
+    outcome
+}
+
+fn workspace_outcome(result: WorkspaceDiagnosticReportResult) -> Outcome {
+    let items = match result {
+        WorkspaceDiagnosticReportResult::Report(report) => report.items,
+        WorkspaceDiagnosticReportResult::Partial(partial) => partial.items,
+    };
+    let mut outcome = Outcome::default();
+    for item in items {
+        match item {
+            WorkspaceDocumentDiagnosticReport::Full(report) => {
+                outcome.full(report.uri, report.version.map(|v| v as i32), report.full_document_diagnostic_report);
+            }
+            WorkspaceDocumentDiagnosticReport::Unchanged(report) => {
+                outcome.result_ids.push((report.uri.as_str().to_string(), Some(report.unchanged_document_diagnostic_report.result_id)));
+            }
+        }
+    }
+    outcome
+}
+
+/// Records diagnostics in the problems store and forwards them to the editor.
+pub fn publish(app: &AppHandle, server_id: &str, params: PublishDiagnosticsParams) {
+    crate::problems::record_lsp_diagnostics(server_id, &params);
+    let _ = app.emit("lsp:diagnostics", params);
+}
+
+fn apply(app: &AppHandle, client: &LspClient, outcome: Outcome) {
+    for (uri, result_id) in outcome.result_ids {
+        client.set_diagnostic_result_id(uri, result_id);
+    }
+    for params in outcome.publish {
+        publish(app, client.server_id(), params);
+    }
+}
+
+/// Pulls diagnostics for one document; does nothing for servers that publish instead.
+pub async fn pull_document(app: &AppHandle, client: &LspClient, uri: Uri) -> Result<(), String> {
+    let Some(identifier) = client.capabilities().and_then(diagnostic_options).map(|o| o.identifier.clone()) else {
+        return Ok(());
+    };
+    let previous_result_id = client.diagnostic_result_id(uri.as_str());
+    let result = client.request::<DocumentDiagnosticRequest>(DocumentDiagnosticParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        identifier,
+        previous_result_id,
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    }).await?;
+    apply(app, client, document_outcome(uri, result));
+    Ok(())
+}
+
+/// Pulls diagnostics for `uri` shortly after a change, unless it changed again since.
+pub fn schedule_document_pull(app: &AppHandle, client: Arc<LspClient>, uri: Uri, version: i32) {
+    if client.capabilities().and_then(diagnostic_options).is_none() {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(PULL_DELAY).await;
+        if client.document_version(&uri) != Some(version) {
+            return;
+        }
+        if let Err(e) = pull_document(&app, &client, uri).await {
+            let _ = app.emit("lsp:log", format!("Failed to pull diagnostics: {}", e));
+        }
+    });
+}
+
+/// Pulls diagnostics for the whole workspace from servers that support it.
+pub async fn pull_workspace(app: &AppHandle, client: &LspClient) -> Result<(), String> {
+    let Some(options) = client.capabilities().and_then(diagnostic_options).filter(|o| o.workspace_diagnostics) else {
+        return Ok(());
+    };
+    let previous_result_ids = client.diagnostic_result_ids().into_iter()
+        .filter_map(|(uri, value)| Some(PreviousResultId { uri: uri.parse().ok()?, value }))
+        .collect();
+    let result = client.request::<WorkspaceDiagnosticRequest>(WorkspaceDiagnosticParams {
+        identifier: options.identifier.clone(),
+        previous_result_ids,
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    }).await?;
+    apply(app, client, workspace_outcome(result));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{
+        Diagnostic, Range, RelatedFullDocumentDiagnosticReport, UnchangedDocumentDiagnosticReport,
+        WorkspaceDiagnosticReport, WorkspaceFullDocumentDiagnosticReport, WorkspaceUnchangedDocumentDiagnosticReport,
+    };
+
+    fn uri(path: &str) -> Uri {
+        format!("file://{}", path).parse().unwrap()
+    }
+
+    fn full(result_id: &str, message: &str) -> FullDocumentDiagnosticReport {
+        FullDocumentDiagnosticReport {
+            result_id: Some(result_id.to_string()),
+            items: vec![Diagnostic::new_simple(Range::default(), message.to_string())],
+        }
+    }
+
+    #[test]
+    fn publishes_full_reports_and_keeps_result_ids() {
+        let related = HashMap::from([(
+            uri("/repo/b.ts"),
+            DocumentDiagnosticReportKind::Unchanged(UnchangedDocumentDiagnosticReport { result_id: "b1".to_string() }),
+        )]);
+        let outcome = document_outcome(uri("/repo/a.ts"), DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(
+            RelatedFullDocumentDiagnosticReport { related_documents: Some(related), full_document_diagnostic_report: full("a2", "unused") },
+        )));
+        assert_eq!(outcome.publish.len(), 1);
+        assert_eq!(outcome.publish[0].diagnostics[0].message, "unused");
+        assert_eq!(outcome.result_ids, vec![
+            ("file:///repo/a.ts".to_string(), Some("a2".to_string())),
+            ("file:///repo/b.ts".to_string(), Some("b1".to_string())),
+        ]);
+
+        let outcome = workspace_outcome(WorkspaceDiagnosticReportResult::Report(WorkspaceDiagnosticReport {
+            items: vec![
+                WorkspaceDocumentDiagnosticReport::Full(WorkspaceFullDocumentDiagnosticReport {
+                    uri: uri("/repo/c.ts"),
+                    version: Some(3),
+                    full_document_diagnostic_report: full("c1", "missing"),
+                }),
+                WorkspaceDocumentDiagnosticReport::Unchanged(WorkspaceUnchangedDocumentDiagnosticReport {
+                    uri: uri("/repo/a.ts"),
+                    version: None,
+                    unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport { result_id: "a2".to_string() },
+                }),
+            ],
+        }));
+        assert_eq!(outcome.publish.len(), 1);
+        assert_eq!(outcome.publish[0].version, Some(3));
+        assert_eq!(outcome.result_ids.len(), 2);
+    }
+}
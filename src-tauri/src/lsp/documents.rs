@@ -0,0 +1,131 @@
+//! Documents open on a language server and how their changes are sent.
+//!
+//! The editor hands over whole file contents; for servers that accept
+//! incremental sync the change is narrowed to the one range that differs.
+
+use lsp_types::{
+    Position, Range, ServerCapabilities, TextDocumentContentChangeEvent, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextDocumentSyncSaveOptions,
+};
+
+#[derive(Clone)]
+pub struct OpenDocument {
+    pub language_id: String,
+    pub version: i32,
+    pub text: String,
+}
+
+/// The parts of a server's `textDocumentSync` capability the client acts on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncOptions {
+    pub open_close: bool,
+    pub change: TextDocumentSyncKind,
+    /// `Some(include_text)` when the server wants `didSave`.
+    pub save: Option<bool>,
+}
+
+impl SyncOptions {
+    /// Servers that did not say otherwise get full sync with open/close and save.
+    pub fn from_capabilities(capabilities: Option<&ServerCapabilities>) -> Self {
+        let full = Self { open_close: true, change: TextDocumentSyncKind::FULL, save: Some(false) };
+        match capabilities.and_then(|c| c.text_document_sync.as_ref()) {
+            None => full,
+            Some(TextDocumentSyncCapability::Kind(change)) => Self { change: *change, ..full },
+            Some(TextDocumentSyncCapability::Options(options)) => Self {
+                open_close: options.open_close.unwrap_or(false),
+                change: options.change.unwrap_or(TextDocumentSyncKind::NONE),
+                save: match &options.save {
+                    None | Some(TextDocumentSyncSaveOptions::Supported(false)) => None,
+                    Some(TextDocumentSyncSaveOptions::Supported(true)) => Some(false),
+                    Some(TextDocumentSyncSaveOptions::SaveOptions(save)) => Some(save.include_text.unwrap_or(false)),
+                },
+            },
+        }
+    }
+}
+
+/// Change events turning `old` into `new`, or `None` when the server does
+/// not want changes or nothing changed.
+pub fn content_changes(old: &str, new: &str, kind: TextDocumentSyncKind) -> Option<Vec<TextDocumentContentChangeEvent>> {
+    if kind == TextDocumentSyncKind::NONE || old == new {
+        return None;
+    }
+    if kind != TextDocumentSyncKind::INCREMENTAL {
+        return Some(vec![TextDocumentContentChangeEvent { range: None, range_length: None, text: new.to_string() }]);
+    }
+
+    let mut prefix = old.char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map(|((idx, _), _)| idx)
+        .unwrap_or_else(|| old.len().min(new.len()));
+    // Positions inside a CRLF are ambiguous to servers
+    if old[..prefix].ends_with('\r') {
+        prefix -= 1;
+    }
+
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old[prefix..].chars().rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .scan(0, |total, len| {
+            *total += len;
+            (*total <= max_suffix).then_some(*total)
+        })
+        .last()
+        .unwrap_or(0);
+    if old[old.len() - suffix..].starts_with('\n') && old[..old.len() - suffix].ends_with('\r') {
+        suffix -= 1;
+    }
+
+    let start = position_at(old, prefix);
+    let end = position_at(old, old.len() - suffix);
+    Some(vec![TextDocumentContentChangeEvent {
+        range: Some(Range { start, end }),
+        range_length: None,
+        text: new[prefix..new.len() - suffix].to_string(),
+    }])
+}
+
+/// LSP position (UTF-16 character offset) of byte `offset` in `text`.
+fn position_at(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn incremental(old: &str, new: &str) -> TextDocumentContentChangeEvent {
+        content_changes(old, new, TextDocumentSyncKind::INCREMENTAL).unwrap().remove(0)
+    }
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Option<Range> {
+        Some(Range { start: Position::new(start.0, start.1), end: Position::new(end.0, end.1) })
+    }
+
+    #[test]
+    fn narrows_changes_to_the_edited_range() {
+        let change = incremental("let a = 1;\nlet 😀 = 2;\n", "let a = 1;\nlet 😀 = 42;\n");
+        assert_eq!(change.range, range((1, 9), (1, 9)));
+        assert_eq!(change.text, "4");
+
+        let change = incremental("aaa\r\nbbb", "aaa\r\nccc\r\nbbb");
+        assert_eq!(change.range, range((1, 0), (1, 0)));
+        assert_eq!(change.text, "ccc\r\n");
+
+        let change = incremental("abab", "ab");
+        assert_eq!(change.range, range((0, 2), (0, 4)));
+        assert_eq!(change.text, "");
+
+        assert!(content_changes("same", "same", TextDocumentSyncKind::INCREMENTAL).is_none());
+        assert!(content_changes("a", "b", TextDocumentSyncKind::NONE).is_none());
+        assert_eq!(content_changes("a", "b", TextDocumentSyncKind::FULL).unwrap()[0].range, None);
+    }
+}
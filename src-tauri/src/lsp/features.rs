@@ -0,0 +1,126 @@
+//! Language feature requests (completion, hover, navigation, rename, code actions).
+//!
+//! Positions are LSP positions: zero-based line and UTF-16 character offset.
+//! Each command returns the server's result as-is, `null` when it has none.
+
+use lsp_types::request::{
+    CodeActionRequest, Completion, DocumentHighlightRequest, GotoDefinition, GotoTypeDefinition,
+    HoverRequest, References, Rename, SignatureHelpRequest,
+};
+use lsp_types::{
+    CodeActionContext, CodeActionParams, CodeActionResponse, CompletionParams, CompletionResponse,
+    Diagnostic, DocumentHighlight, DocumentHighlightParams, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverParams, Location, Position, Range, ReferenceContext,
+    ReferenceParams, RenameParams, SignatureHelp, SignatureHelpParams, TextDocumentIdentifier,
+    TextDocumentPositionParams, WorkspaceEdit,
+};
+use tauri::{AppHandle, State};
+use crate::lsp::document_uri;
+use crate::lsp::manager::LspManager;
+
+fn position_params(path: &str, line: u32, character: u32) -> Result<TextDocumentPositionParams, String> {
+    Ok(TextDocumentPositionParams {
+        text_document: TextDocumentIdentifier { uri: document_uri(path)? },
+        position: Position { line, character },
+    })
+}
+
+#[tauri::command]
+pub async fn lsp_completion(state: State<'_, LspManager>, app: AppHandle, path: String, line: u32, character: u32) -> Result<Option<CompletionResponse>, String> {
+    let client = state.server_for(&app, &path).await?;
+    client.request::<Completion>(CompletionParams {
+        text_document_position: position_params(&path, line, character)?,
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: None,
+    }).await
+}
+
+#[tauri::command]
+pub async fn lsp_hover(state: State<'_, LspManager>, app: AppHandle, path: String, line: u32, character: u32) -> Result<Option<Hover>, String> {
+    let client = state.server_for(&app, &path).await?;
+    client.request::<HoverRequest>(HoverParams {
+        text_document_position_params: position_params(&path, line, character)?,
+        work_done_progress_params: Default::default(),
+    }).await
+}
+
+#[tauri::command]
+pub async fn lsp_signature_help(state: State<'_, LspManager>, app: AppHandle, path: String, line: u32, character: u32) -> Result<Option<SignatureHelp>, String> {
+    let client = state.server_for(&app, &path).await?;
+    client.request::<SignatureHelpRequest>(SignatureHelpParams {
+        context: None,
+        text_document_position_params: position_params(&path, line, character)?,
+        work_done_progress_params: Default::default(),
+    }).await
+}
+
+#[tauri::command]
+pub async fn lsp_definition(state: State<'_, LspManager>, app: AppHandle, path: String, line: u32, character: u32) -> Result<Option<GotoDefinitionResponse>, String> {
+    let client = state.server_for(&app, &path).await?;
+    client.request::<GotoDefinition>(GotoDefinitionParams {
+        text_document_position_params: position_params(&path, line, character)?,
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    }).await
+}
+
+#[tauri::command]
+pub async fn lsp_type_definition(state: State<'_, LspManager>, app: AppHandle, path: String, line: u32, character: u32) -> Result<Option<GotoDefinitionResponse>, String> {
+    let client = state.server_for(&app, &path).await?;
+    client.request::<GotoTypeDefinition>(GotoDefinitionParams {
+        text_document_position_params: position_params(&path, line, character)?,
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    }).await
+}
+
+#[tauri::command]
+pub async fn lsp_references(state: State<'_, LspManager>, app: AppHandle, path: String, line: u32, character: u32, include_declaration: Option<bool>) -> Result<Option<Vec<Location>>, String> {
+    let client = state.server_for(&app, &path).await?;
+    client.request::<References>(ReferenceParams {
+        text_document_position: position_params(&path, line, character)?,
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: ReferenceContext { include_declaration: include_declaration.unwrap_or(true) },
+    }).await
+}
+
+#[tauri::command]
+pub async fn lsp_document_highlight(state: State<'_, LspManager>, app: AppHandle, path: String, line: u32, character: u32) -> Result<Option<Vec<DocumentHighlight>>, String> {
+    let client = state.server_for(&app, &path).await?;
+    client.request::<DocumentHighlightRequest>(DocumentHighlightParams {
+        text_document_position_params: position_params(&path, line, character)?,
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    }).await
+}
+
+/// The returned edit is not applied; the caller decides what to do with it.
+#[tauri::command]
+pub async fn lsp_rename(state: State<'_, LspManager>, app: AppHandle, path: String, line: u32, character: u32, new_name: String) -> Result<Option<WorkspaceEdit>, String> {
+    let client = state.server_for(&app, &path).await?;
+    client.request::<Rename>(RenameParams {
+        text_document_position: position_params(&path, line, character)?,
+        new_name,
+        work_done_progress_params: Default::default(),
+    }).await
+}
+
+/// Code actions for `range`; `diagnostics` are the ones overlapping it, so
+/// servers can offer quick fixes for them.
+#[tauri::command]
+pub async fn lsp_code_action(state: State<'_, LspManager>, app: AppHandle, path: String, range: Range, diagnostics: Option<Vec<Diagnostic>>, only: Option<Vec<String>>) -> Result<Option<CodeActionResponse>, String> {
+    let client = state.server_for(&app, &path).await?;
+    client.request::<CodeActionRequest>(CodeActionParams {
+        text_document: TextDocumentIdentifier { uri: document_uri(&path)? },
+        range,
+        context: CodeActionContext {
+            diagnostics: diagnostics.unwrap_or_default(),
+            only: only.map(|kinds| kinds.into_iter().map(Into::into).collect()),
+            trigger_kind: None,
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    }).await
+}
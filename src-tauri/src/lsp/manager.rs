@@ -0,0 +1,523 @@
+//! Runs one language server per (server, project root).
+//!
+//! Servers are started lazily when the first document they handle is opened
+//! and stopped after a period without traffic. A server that exits on its own
+//! is restarted with exponential backoff and gets its open documents back.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use futures_util::future::join_all;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use crate::lsp::client::LspClient;
+use crate::lsp::diagnostics::{pull_workspace, schedule_document_pull};
+use crate::lsp::registry::{find_root, language_id, ServerRegistry};
+use crate::lsp::document_uri;
+use crate::settings::{LanguageServerSettings, SettingsState};
+
+const IDLE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+const REAP_INTERVAL: Duration = Duration::from_secs(60);
+/// Restarts in a row before a crashing server is given up on.
+const MAX_RESTARTS: u32 = 5;
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(30);
+/// A server that ran this long before exiting counts as healthy again.
+const STABLE_AFTER: Duration = Duration::from_secs(120);
+
+/// Identifies one process of a server, so the exit of a replaced or stopped
+/// process does not trigger a restart.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ServerKey {
+    pub server_id: String,
+    pub root: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServerStatus {
+    Starting,
+    Running,
+    Exited,
+    Restarting,
+    Failed,
+    Stopped,
+}
+
+/// Payload of `lsp:status`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStatusEvent {
+    pub server_id: String,
+    pub root: String,
+    pub status: ServerStatus,
+    pub message: Option<String>,
+}
+
+fn emit_status(app: &AppHandle, key: &ServerKey, status: ServerStatus, message: Option<String>) {
+    let _ = app.emit("lsp:status", ServerStatusEvent {
+        server_id: key.server_id.clone(),
+        root: key.root.to_string_lossy().to_string(),
+        status,
+        message,
+    });
+}
+
+struct ServerEntry {
+    client: Arc<LspClient>,
+    config: LanguageServerSettings,
+    generation: u64,
+    last_used: Instant,
+    started: Instant,
+    /// Restarts since the server last ran for `STABLE_AFTER`.
+    restarts: u32,
+    /// Gave up after `MAX_RESTARTS`; cleared by `restart_server`.
+    failed: bool,
+}
+
+type Servers = Arc<Mutex<HashMap<ServerKey, ServerEntry>>>;
+
+#[derive(Default)]
+pub struct LspManager {
+    servers: Servers,
+    workspaces: Mutex<Vec<PathBuf>>,
+    reaper_started: AtomicBool,
+    /// Registry built for the settings generation it is paired with.
+    registry: Mutex<Option<(u64, Arc<ServerRegistry>)>>,
+    /// Root per (server, document directory), valid for the settings
+    /// generation it is paired with and the registered workspaces.
+    roots: Mutex<(u64, HashMap<(String, PathBuf), PathBuf>)>,
+    /// Servers that could not be started, with the error, so they are not
+    /// spawned again on every keystroke. Valid for the settings generation it
+    /// is paired with, or until `restart_server`.
+    spawn_failures: Mutex<(u64, HashMap<ServerKey, String>)>,
+}
+
+impl LspManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a workspace root; documents under it share its servers.
+    pub fn add_workspace(&self, root: &Path) {
+        let mut workspaces = self.workspaces.lock().unwrap();
+        if !workspaces.iter().any(|w| w == root) {
+            workspaces.push(root.to_path_buf());
+            self.roots.lock().unwrap().1.clear();
+        }
+    }
+
+    /// Root for a document: the nearest directory with one of the server's root
+    /// markers inside its workspace, else the innermost registered workspace
+    /// containing it, else the document's directory.
+    ///
+    /// Cached per directory, since it is looked up on every keystroke.
+    fn root_for(&self, generation: u64, config: &LanguageServerSettings, path: &Path) -> PathBuf {
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let cache_key = (config.id.clone(), dir);
+        {
+            let mut roots = self.roots.lock().unwrap();
+            if roots.0 != generation {
+                *roots = (generation, HashMap::new());
+            }
+            if let Some(root) = roots.1.get(&cache_key) {
+                return root.clone();
+            }
+        }
+
+        let workspace = resolve_root(&self.workspaces.lock().unwrap(), path);
+        let root = find_root(&config.root_markers, path, Some(&workspace)).unwrap_or(workspace);
+        let mut roots = self.roots.lock().unwrap();
+        if roots.0 == generation {
+            roots.1.insert(cache_key, root.clone());
+        }
+        root
+    }
+
+    /// Server registry built from the current settings, rebuilt only after they change.
+    pub fn registry(app: &AppHandle) -> Arc<ServerRegistry> {
+        Self::current_registry(app).1
+    }
+
+    fn current_registry(app: &AppHandle) -> (u64, Arc<ServerRegistry>) {
+        let manager = app.state::<LspManager>();
+        let store = app.state::<SettingsState>();
+        let store = store.store.lock().unwrap();
+        let generation = store.generation();
+        let mut cached = manager.registry.lock().unwrap();
+        if let Some((built_for, registry)) = cached.as_ref() {
+            if *built_for == generation {
+                return (generation, registry.clone());
+            }
+        }
+        let registry = Arc::new(ServerRegistry::from_settings(&store.get_settings().lsp));
+        *cached = Some((generation, registry.clone()));
+        (generation, registry)
+    }
+
+    /// Running server for `key`, started and initialized if needed.
+    pub async fn server(&self, app: &AppHandle, key: &ServerKey, config: &LanguageServerSettings) -> Result<Arc<LspClient>, String> {
+        let settings_generation = app.state::<SettingsState>().store.lock().unwrap().generation();
+        {
+            let mut failures = self.spawn_failures.lock().unwrap();
+            if failures.0 != settings_generation {
+                *failures = (settings_generation, HashMap::new());
+            }
+            if let Some(error) = failures.1.get(key) {
+                return Err(error.clone());
+            }
+        }
+
+        let (client, started) = {
+            let mut servers = self.servers.lock().map_err(|_| "Failed to lock LSP servers")?;
+            match servers.get_mut(key) {
+                Some(entry) if entry.failed => {
+                    return Err(format!("{} kept exiting and was stopped; restart it to try again", key.server_id));
+                }
+                Some(entry) if !entry.client.is_running() => {
+                    return Err(format!("{} exited and is being restarted", key.server_id));
+                }
+                Some(entry) => {
+                    entry.last_used = Instant::now();
+                    (entry.client.clone(), false)
+                }
+                None => {
+                    let generation = NEXT_GENERATION.fetch_add(1, Ordering::SeqCst);
+                    let client = match spawn(app, &self.servers, key, config, generation) {
+                        Ok(client) => client,
+                        Err(e) => {
+                            let mut failures = self.spawn_failures.lock().unwrap();
+                            if failures.0 == settings_generation {
+                                failures.1.insert(key.clone(), e.clone());
+                            }
+                            emit_status(app, key, ServerStatus::Failed, Some(e.clone()));
+                            return Err(e);
+                        }
+                    };
+                    servers.insert(key.clone(), ServerEntry {
+                        client: client.clone(),
+                        config: config.clone(),
+                        generation,
+                        last_used: Instant::now(),
+                        started: Instant::now(),
+                        restarts: 0,
+                        failed: false,
+                    });
+                    (client, true)
+                }
+            }
+        };
+        // Outside the lock: only the first caller performs the handshake, the rest wait for it
+        client.initialize(&key.root).await?;
+        if started {
+            emit_status(app, key, ServerStatus::Running, None);
+        }
+
+        self.start_reaper(app);
+        Ok(client)
+    }
+
+    /// Restarts every instance of `server_id` with the current settings,
+    /// including ones that were given up on.
+    pub async fn restart_server(&self, app: &AppHandle, server_id: &str) -> Result<(), String> {
+        let config = Self::registry(app).get(server_id).cloned()
+            .ok_or_else(|| format!("Unknown language server: {}", server_id))?;
+        let had_failed = {
+            let mut failures = self.spawn_failures.lock().unwrap();
+            let before = failures.1.len();
+            failures.1.retain(|key, _| key.server_id != server_id);
+            failures.1.len() != before
+        };
+        let previous: Vec<(ServerKey, Arc<LspClient>, u64)> = {
+            let mut servers = self.servers.lock().map_err(|_| "Failed to lock LSP servers")?;
+            servers.iter_mut()
+                .filter(|(key, _)| key.server_id == server_id)
+                .map(|(key, entry)| {
+                    // Invalidate first so the old process exiting is not taken for a crash
+                    entry.generation = NEXT_GENERATION.fetch_add(1, Ordering::SeqCst);
+                    entry.config = config.clone();
+                    entry.restarts = 0;
+                    entry.failed = false;
+                    (key.clone(), entry.client.clone(), entry.generation)
+                })
+                .collect()
+        };
+        if previous.is_empty() {
+            // A server that failed to start is tried again by the next document that needs it
+            return if had_failed { Ok(()) } else { Err(format!("{} is not running", server_id)) };
+        }
+
+        for (key, old, generation) in previous {
+            old.shutdown().await;
+            if let Some(client) = respawn(app, &self.servers, &key, generation)? {
+                finish_restart(app, &key, &client).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Unregisters `root` and shuts down the servers running inside it.
+    pub async fn close_workspace(&self, app: &AppHandle, root: &Path) {
+        self.workspaces.lock().unwrap().retain(|w| w != root);
+        self.roots.lock().unwrap().1.clear();
+        self.shutdown_where(app, |key| key.root.starts_with(root)).await;
+    }
+
+    /// Shuts down every server, on app exit.
+    pub async fn shutdown_all(&self, app: &AppHandle) {
+        self.shutdown_where(app, |_| true).await;
+    }
+
+    async fn shutdown_where(&self, app: &AppHandle, matches: impl Fn(&ServerKey) -> bool) {
+        let stopping: Vec<(ServerKey, ServerEntry)> = {
+            let mut servers = self.servers.lock().unwrap();
+            let keys: Vec<ServerKey> = servers.keys().filter(|key| matches(key)).cloned().collect();
+            keys.into_iter().filter_map(|key| servers.remove_entry(&key)).collect()
+        };
+        join_all(stopping.iter().map(|(_, entry)| entry.client.shutdown())).await;
+        for (key, _) in &stopping {
+            crate::problems::clear_lsp_diagnostics(&key.server_id, &key.root);
+            emit_status(app, key, ServerStatus::Stopped, None);
+        }
+    }
+
+    /// Server responsible for `path`, or `None` when no server handles it.
+    pub fn key_for(&self, app: &AppHandle, path: &str) -> Option<(ServerKey, LanguageServerSettings)> {
+        let (generation, registry) = Self::current_registry(app);
+        let config = registry.server_for(path)?.clone();
+        let root = self.root_for(generation, &config, Path::new(path));
+        Some((ServerKey { server_id: config.id.clone(), root }, config))
+    }
+
+    /// Server for the document at `path`, started on demand.
+    pub async fn server_for(&self, app: &AppHandle, path: &str) -> Result<Arc<LspClient>, String> {
+        let (key, config) = self.key_for(app, path).ok_or_else(|| format!("No language server for {}", path))?;
+        self.server(app, &key, &config).await
+    }
+
+    pub async fn did_open(&self, app: &AppHandle, path: &str, content: String) -> Result<(), String> {
+        let Some((key, config)) = self.key_for(app, path) else {
+            return Ok(());
+        };
+        let client = self.server(app, &key, &config).await?;
+        let uri = document_uri(path)?;
+        client.did_open(uri.clone(), &document_language(path, &config), 1, content)?;
+        schedule_document_pull(app, client, uri, 1);
+        Ok(())
+    }
+
+    pub async fn did_change(&self, app: &AppHandle, path: &str, content: String, version: i32) -> Result<(), String> {
+        let Some((key, config)) = self.key_for(app, path) else {
+            return Ok(());
+        };
+        let client = self.server(app, &key, &config).await?;
+        let uri = document_uri(path)?;
+        // A server started after an idle shutdown has never seen this document
+        if client.is_open(&uri) {
+            client.did_change(uri.clone(), content, version)?;
+        } else {
+            client.did_open(uri.clone(), &document_language(path, &config), version, content)?;
+        }
+        schedule_document_pull(app, client, uri, version);
+        Ok(())
+    }
+
+    /// Closes `path` on the server that has it open; never starts a server.
+    pub fn did_close(&self, app: &AppHandle, path: &str) -> Result<(), String> {
+        match self.running_client(app, path) {
+            Some(client) => client.did_close(document_uri(path)?),
+            None => Ok(()),
+        }
+    }
+
+    /// `content` is the saved text; servers that ask for it otherwise get the last synced text.
+    pub fn did_save(&self, app: &AppHandle, path: &str, content: Option<String>) -> Result<(), String> {
+        let uri = document_uri(path)?;
+        let Some(client) = self.running_client(app, path).filter(|client| client.is_open(&uri)) else {
+            return Ok(());
+        };
+        client.did_save(uri, content)?;
+        // Saving can change diagnostics of other files, e.g. importers
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = pull_workspace(&app, &client).await {
+                let _ = app.emit("lsp:log", format!("Failed to pull workspace diagnostics: {}", e));
+            }
+        });
+        Ok(())
+    }
+
+    /// Pulls workspace diagnostics from the running servers under `root` that support it.
+    pub async fn pull_workspace_diagnostics(&self, app: &AppHandle, root: &Path) -> Result<(), String> {
+        let clients: Vec<Arc<LspClient>> = self.servers.lock().map_err(|_| "Failed to lock LSP servers")?
+            .iter()
+            .filter(|(key, entry)| key.root.starts_with(root) && entry.client.is_running())
+            .map(|(_, entry)| entry.client.clone())
+            .collect();
+        for result in join_all(clients.iter().map(|client| pull_workspace(app, client))).await {
+            result?;
+        }
+        Ok(())
+    }
+
+    fn running_client(&self, app: &AppHandle, path: &str) -> Option<Arc<LspClient>> {
+        let (key, _) = self.key_for(app, path)?;
+        let servers = self.servers.lock().ok()?;
+        servers.get(&key).map(|entry| entry.client.clone()).filter(|client| client.is_running())
+    }
+
+    /// Version and text of `path` last sent to a server, if it is open there.
+    pub fn open_document(&self, path: &str) -> Option<(i32, String)> {
+        let uri = document_uri(path).ok()?;
+        self.servers.lock().unwrap().values().find_map(|entry| entry.client.open_document(&uri))
+    }
+
+    /// Stops servers that have not been used for `IDLE_TIMEOUT`.
+    fn start_reaper(&self, app: &AppHandle) {
+        if self.reaper_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let servers = self.servers.clone();
+        let app = app.clone();
+        thread::spawn(move || loop {
+            thread::sleep(REAP_INTERVAL);
+            let idle: Vec<(ServerKey, ServerEntry)> = {
+                let mut servers = servers.lock().unwrap();
+                let keys: Vec<ServerKey> = servers.iter()
+                    .filter(|(_, entry)| entry.last_used.elapsed() >= IDLE_TIMEOUT)
+                    .map(|(key, _)| key.clone())
+                    .collect();
+                keys.into_iter().filter_map(|key| servers.remove_entry(&key)).collect()
+            };
+            // Stop outside the lock; shutdown waits for the process to exit
+            for (key, entry) in idle {
+                tauri::async_runtime::block_on(entry.client.shutdown());
+                crate::problems::clear_lsp_diagnostics(&key.server_id, &key.root);
+                emit_status(&app, &key, ServerStatus::Stopped, Some("Idle".to_string()));
+            }
+        });
+    }
+}
+
+/// Starts the process for `key`. If it exits while still registered under
+/// `generation`, a restart is scheduled.
+fn spawn(app: &AppHandle, servers: &Servers, key: &ServerKey, config: &LanguageServerSettings, generation: u64) -> Result<Arc<LspClient>, String> {
+    let on_exit = {
+        let (app, servers, key) = (app.clone(), servers.clone(), key.clone());
+        move || on_exit(app, servers, key, generation)
+    };
+    let client = LspClient::start(app.clone(), config, on_exit)
+        .map_err(|e| format!("Failed to start {}: {}", key.server_id, e))?;
+    let _ = app.emit("lsp:log", format!("Started {} for {}", key.server_id, key.root.display()));
+    emit_status(app, key, ServerStatus::Starting, None);
+    Ok(Arc::new(client))
+}
+
+fn on_exit(app: AppHandle, servers: Servers, key: ServerKey, generation: u64) {
+    let attempt = {
+        let mut servers = servers.lock().unwrap();
+        // Stopped on purpose or already replaced
+        let Some(entry) = servers.get_mut(&key).filter(|entry| entry.generation == generation) else {
+            return;
+        };
+        emit_status(&app, &key, ServerStatus::Exited, None);
+        if entry.started.elapsed() >= STABLE_AFTER {
+            entry.restarts = 0;
+        }
+        if entry.restarts >= MAX_RESTARTS {
+            entry.failed = true;
+            emit_status(&app, &key, ServerStatus::Failed, Some(format!("Exited {} times in a row", MAX_RESTARTS + 1)));
+            return;
+        }
+        entry.restarts += 1;
+        entry.restarts
+    };
+
+    let delay = restart_delay(attempt);
+    emit_status(&app, &key, ServerStatus::Restarting, Some(format!("Restarting in {}s (attempt {})", delay.as_secs(), attempt)));
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(delay).await;
+        match respawn(&app, &servers, &key, generation) {
+            Ok(Some(client)) => {
+                if let Err(e) = finish_restart(&app, &key, &client).await {
+                    let _ = app.emit("lsp:log", format!("{} failed to initialize after restart: {}", key.server_id, e));
+                }
+            }
+            Ok(None) => {}
+            // Could not even spawn: counts as another exit
+            Err(e) => {
+                let _ = app.emit("lsp:log", e);
+                on_exit(app, servers, key, generation);
+            }
+        }
+    });
+}
+
+/// Replaces the process of the entry for `key` if it is still `generation`.
+/// The new process inherits the old one's documents.
+fn respawn(app: &AppHandle, servers: &Servers, key: &ServerKey, generation: u64) -> Result<Option<Arc<LspClient>>, String> {
+    let mut guard = servers.lock().map_err(|_| "Failed to lock LSP servers")?;
+    let Some(entry) = guard.get_mut(key).filter(|entry| entry.generation == generation) else {
+        return Ok(None);
+    };
+    let next = NEXT_GENERATION.fetch_add(1, Ordering::SeqCst);
+    let client = spawn(app, servers, key, &entry.config, next)?;
+    client.adopt_documents(&entry.client);
+    entry.client = client.clone();
+    entry.generation = next;
+    entry.started = Instant::now();
+    Ok(Some(client))
+}
+
+async fn finish_restart(app: &AppHandle, key: &ServerKey, client: &LspClient) -> Result<(), String> {
+    client.initialize(&key.root).await?;
+    client.reopen_documents()?;
+    emit_status(app, key, ServerStatus::Running, None);
+    Ok(())
+}
+
+fn restart_delay(attempt: u32) -> Duration {
+    RESTART_DELAY.saturating_mul(1 << attempt.saturating_sub(1).min(16)).min(MAX_RESTART_DELAY)
+}
+
+/// Language id for `didOpen`; files matched only by a server's file patterns
+/// get the server's first language.
+fn document_language(path: &str, config: &LanguageServerSettings) -> String {
+    language_id(path).map(str::to_string)
+        .or_else(|| config.languages.first().cloned())
+        .unwrap_or_default()
+}
+
+fn resolve_root(workspaces: &[PathBuf], path: &Path) -> PathBuf {
+    workspaces.iter()
+        .filter(|w| path.starts_with(w))
+        .max_by_key(|w| w.components().count())
+        .cloned()
+        .unwrap_or_else(|| path.parent().map(Path::to_path_buf).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_innermost_workspace() {
+        let workspaces = vec![PathBuf::from("/repo"), PathBuf::from("/repo/services/api")];
+        assert_eq!(resolve_root(&workspaces, Path::new("/repo/services/api/main.go")), PathBuf::from("/repo/services/api"));
+        assert_eq!(resolve_root(&workspaces, Path::new("/repo/web/app.tsx")), PathBuf::from("/repo"));
+        assert_eq!(resolve_root(&workspaces, Path::new("/tmp/script.py")), PathBuf::from("/tmp"));
+
+        assert_eq!(language_id("/repo/web/app.jsx"), Some("javascriptreact"));
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_a_limit() {
+        let delays: Vec<u64> = (1..=7).map(|attempt| restart_delay(attempt).as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 30, 30]);
+    }
+}
@@ -1,82 +1,199 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use tauri::{AppHandle, State};
-use std::sync::Mutex;
-use crate::lsp::client::{LspClient, LanguageServerType};
+use serde::Serialize;
+use crate::lsp::manager::{LspManager, ServerKey};
+use crate::lsp::registry::{find_executable, EXTRACTED_SERVERS};
 
 pub mod client;
-pub mod protocol;
+pub mod diagnostics;
+pub mod documents;
+pub mod features;
+pub mod manager;
+pub mod registry;
+pub mod workspace_edit;
 
-pub struct LspState {
-    pub client: Mutex<LspClient>,
+pub(crate) fn to_file_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    // Spaces and other reserved characters are not valid in a URI
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    if encoded.starts_with('/') {
+        format!("file://{}", encoded)
+    } else {
+        format!("file:///{}", encoded)
+    }
 }
 
-impl LspState {
-    pub fn new() -> Self {
-        Self {
-            client: Mutex::new(LspClient::new()),
+/// Inverse of `to_file_uri`.
+pub(crate) fn file_uri_to_path(uri: &str) -> Result<PathBuf, String> {
+    let encoded = uri.strip_prefix("file://").ok_or_else(|| format!("Not a file URI: {}", uri))?;
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| encoded.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
         }
     }
+    let path = String::from_utf8(decoded).map_err(|_| format!("Invalid file URI: {}", uri))?;
+    // `file:///C:/dir` carries a drive letter after the leading slash
+    let is_drive = path.len() > 2 && path.as_bytes()[2] == b':' && path.as_bytes()[1].is_ascii_alphabetic();
+    Ok(PathBuf::from(if is_drive { &path[1..] } else { &path[..] }))
 }
 
-fn to_file_uri(path: &str) -> String {
-    let path = path.replace('\\', "/");
-    if path.starts_with('/') {
-        format!("file://{}", path)
-    } else {
-        format!("file:///{}", path)
-    }
+pub(crate) fn document_uri(path: &str) -> Result<lsp_types::Uri, String> {
+    lsp_types::Uri::from_str(&to_file_uri(path)).map_err(|e| format!("Invalid document path {}: {}", path, e))
 }
 
+/// Registers the workspace; servers start when matching documents are opened.
+/// `server_type` (a server id or language id) starts that server right away.
 #[tauri::command]
-pub fn lsp_initialize(state: State<LspState>, app: AppHandle, project_path: String, server_type: Option<String>) -> Result<(), String> {
-    let server_type = match server_type.as_deref() {
-        Some("go") => LanguageServerType::Go,
-        Some("python") => LanguageServerType::Python,
-        Some("typescript") | _ => LanguageServerType::TypeScript,
-    };
-    
-    let mut client = state.client.lock().map_err(|_| "Failed to lock LSP client")?;
-    client.start(app, server_type).map_err(|e| format!("Failed to start LSP: {}", e))?;
-    client.initialize(to_file_uri(&project_path))?;
+pub async fn lsp_initialize(state: State<'_, LspManager>, app: AppHandle, project_path: String, server_type: Option<String>) -> Result<(), String> {
+    let root = Path::new(&project_path);
+    state.add_workspace(root);
+
+    if let Some(name) = server_type {
+        let config = LspManager::registry(&app).by_name(&name).cloned()
+            .ok_or_else(|| format!("Unknown language server: {}", name))?;
+        state.server(&app, &ServerKey { server_id: config.id.clone(), root: root.to_path_buf() }, &config).await?;
+    }
     Ok(())
 }
 
 #[tauri::command]
-pub fn lsp_did_open(state: State<LspState>, path: String, content: String) -> Result<(), String> {
-    let uri = to_file_uri(&path);
-    let mut client = state.client.lock().map_err(|_| "Failed to lock LSP client")?;
-    client.did_open(uri, content)?;
-    Ok(())
+pub async fn lsp_did_open(state: State<'_, LspManager>, app: AppHandle, path: String, content: String) -> Result<(), String> {
+    state.did_open(&app, &path, content).await
+}
+
+#[tauri::command]
+pub async fn lsp_did_change(state: State<'_, LspManager>, app: AppHandle, path: String, content: String, version: i32) -> Result<(), String> {
+    state.did_change(&app, &path, content, version).await
 }
 
+/// Shuts down the servers of a workspace that is being closed.
 #[tauri::command]
-pub fn lsp_did_change(state: State<LspState>, path: String, content: String, version: i32) -> Result<(), String> {
-    let uri = to_file_uri(&path);
-    let mut client = state.client.lock().map_err(|_| "Failed to lock LSP client")?;
-    client.did_change(uri, content, version)?;
+pub async fn lsp_close_workspace(state: State<'_, LspManager>, app: AppHandle, project_path: String) -> Result<(), String> {
+    state.close_workspace(&app, Path::new(&project_path)).await;
     Ok(())
 }
 
-// CSS LSP Commands - placeholder implementations
+/// Pulls diagnostics for every file of the workspace from servers that
+/// support `workspace/diagnostic`; results arrive on `lsp:diagnostics`.
 #[tauri::command]
-pub fn css_lsp_initialize(_project_path: String) -> Result<(), String> {
-    // TODO: Implement CSS LSP initialization
-    Err("CSS LSP not implemented yet".to_string())
+pub async fn lsp_pull_workspace_diagnostics(state: State<'_, LspManager>, app: AppHandle, project_path: String) -> Result<(), String> {
+    state.pull_workspace_diagnostics(&app, Path::new(&project_path)).await
 }
 
+/// Restarts a server, e.g. after it was given up on or its settings changed.
 #[tauri::command]
-pub fn css_lsp_did_open(_path: String, _content: String) -> Result<(), String> {
-    // TODO: Implement CSS LSP did_open
-    Ok(())
+pub async fn lsp_restart_server(state: State<'_, LspManager>, app: AppHandle, server_id: String) -> Result<(), String> {
+    state.restart_server(&app, &server_id).await
 }
 
 #[tauri::command]
-pub fn css_lsp_did_change(_path: String, _content: String, _version: i32) -> Result<(), String> {
-    // TODO: Implement CSS LSP did_change
-    Ok(())
+pub fn lsp_did_close(state: State<'_, LspManager>, app: AppHandle, path: String) -> Result<(), String> {
+    state.did_close(&app, &path)
+}
+
+/// `content` is the text written to disk, sent to servers that ask for it on save.
+#[tauri::command]
+pub fn lsp_did_save(state: State<'_, LspManager>, app: AppHandle, path: String, content: Option<String>) -> Result<(), String> {
+    state.did_save(&app, &path, content)
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerAvailability {
+    pub id: String,
+    pub command: String,
+    pub languages: Vec<String>,
+    /// Where `command` resolves on `PATH`; `None` when it is not installed.
+    pub resolved_path: Option<String>,
+}
+
+fn availability(config: &crate::settings::LanguageServerSettings) -> ServerAvailability {
+    ServerAvailability {
+        id: config.id.clone(),
+        command: config.command.clone(),
+        languages: config.languages.clone(),
+        resolved_path: find_executable(&config.command).map(|p| p.to_string_lossy().to_string()),
+    }
 }
 
+/// Configured servers (settings and built-ins) and whether their commands are installed.
 #[tauri::command]
-pub fn css_lsp_did_close(_path: String) -> Result<(), String> {
-    // TODO: Implement CSS LSP did_close
+pub fn lsp_list_servers(app: AppHandle) -> Vec<ServerAvailability> {
+    LspManager::registry(&app).servers().map(availability).collect()
+}
+
+/// Checks whether the binary for `server_id` can be found.
+#[tauri::command]
+pub fn lsp_check_server(app: AppHandle, server_id: String) -> Result<ServerAvailability, String> {
+    let registry = LspManager::registry(&app);
+    let config = registry.get(&server_id).ok_or_else(|| format!("Unknown language server: {}", server_id))?;
+    Ok(availability(config))
+}
+
+// CSS, HTML and JSON documents go through the generic client, but only to the
+// vscode-langservers-extracted servers; other files are ignored.
+
+fn is_extracted_document(app: &AppHandle, path: &str) -> bool {
+    LspManager::registry(app).server_for(path).is_some_and(|s| EXTRACTED_SERVERS.contains(&s.id.as_str()))
+}
+
+/// Registers the workspace and starts the CSS server, so a missing
+/// vscode-langservers-extracted install is reported up front.
+#[tauri::command]
+pub async fn css_lsp_initialize(state: State<'_, LspManager>, app: AppHandle, project_path: String) -> Result<(), String> {
+    let root = Path::new(&project_path);
+    state.add_workspace(root);
+
+    let config = LspManager::registry(&app).get(EXTRACTED_SERVERS[0]).cloned()
+        .ok_or_else(|| format!("{} is disabled", EXTRACTED_SERVERS[0]))?;
+    if find_executable(&config.command).is_none() {
+        return Err(format!("{} not found; install it with `npm install -g vscode-langservers-extracted`", config.command));
+    }
+    state.server(&app, &ServerKey { server_id: config.id.clone(), root: root.to_path_buf() }, &config).await?;
     Ok(())
 }
+
+#[tauri::command]
+pub async fn css_lsp_did_open(state: State<'_, LspManager>, app: AppHandle, path: String, content: String) -> Result<(), String> {
+    if !is_extracted_document(&app, &path) {
+        return Ok(());
+    }
+    state.did_open(&app, &path, content).await
+}
+
+#[tauri::command]
+pub async fn css_lsp_did_change(state: State<'_, LspManager>, app: AppHandle, path: String, content: String, version: i32) -> Result<(), String> {
+    if !is_extracted_document(&app, &path) {
+        return Ok(());
+    }
+    state.did_change(&app, &path, content, version).await
+}
+
+#[tauri::command]
+pub fn css_lsp_did_close(state: State<'_, LspManager>, app: AppHandle, path: String) -> Result<(), String> {
+    if !is_extracted_document(&app, &path) {
+        return Ok(());
+    }
+    state.did_close(&app, &path)
+}
@@ -0,0 +1,294 @@
+//! Which language server handles which documents.
+//!
+//! Built-in defaults cover the common languages; `lsp.servers` in settings
+//! adds servers or replaces built-ins with the same id.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde_json::{json, Value};
+use crate::settings::{settings_schema, LanguageServerSettings, LspSettings};
+
+/// Servers from vscode-langservers-extracted, driven by the `css_lsp_*` commands.
+pub const EXTRACTED_SERVERS: &[&str] = &["vscode-css-language-server", "vscode-html-language-server", "vscode-json-language-server"];
+
+/// LSP `languageId` for a document path.
+pub fn language_id(path: &str) -> Option<&'static str> {
+    let path = Path::new(path);
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    // JSON files that allow comments
+    if (name.starts_with("tsconfig") || name.starts_with("jsconfig")) && name.ends_with(".json") {
+        return Some("jsonc");
+    }
+
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "typescriptreact",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "go" => "go",
+        "py" | "pyi" => "python",
+        "rs" => "rust",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => "cpp",
+        "m" => "objective-c",
+        "mm" => "objective-cpp",
+        "sh" | "bash" => "shellscript",
+        "yaml" | "yml" => "yaml",
+        "lua" => "lua",
+        "css" => "css",
+        "scss" => "scss",
+        "less" => "less",
+        "html" | "htm" => "html",
+        "json" => "json",
+        "jsonc" => "jsonc",
+        _ => return None,
+    })
+}
+
+fn server(id: &str, languages: &[&str], command: &str, args: &[&str], root_markers: &[&str]) -> LanguageServerSettings {
+    LanguageServerSettings {
+        id: id.to_string(),
+        languages: languages.iter().map(|s| s.to_string()).collect(),
+        file_patterns: Vec::new(),
+        command: command.to_string(),
+        args: args.iter().map(|s| s.to_string()).collect(),
+        env: HashMap::new(),
+        initialization_options: None,
+        settings: None,
+        root_markers: root_markers.iter().map(|s| s.to_string()).collect(),
+    }
+}
+
+/// Schemas the JSON server applies by file name. It only learns about them
+/// through `workspace/didChangeConfiguration`, which is sent after `initialize`.
+fn json_schemas() -> Value {
+    json!([
+        {
+            "fileMatch": ["package.json"],
+            "url": "https://json.schemastore.org/package.json"
+        },
+        {
+            "fileMatch": ["tsconfig.json", "tsconfig.*.json", "jsconfig.json"],
+            "url": "https://json.schemastore.org/tsconfig.json"
+        },
+        {
+            // User settings live in `<data dir>/Cognitive/config`, workspace settings in `.cognitive`
+            "fileMatch": ["**/Cognitive/config/settings.json", "**/.cognitive/settings.json"],
+            "schema": settings_schema()
+        }
+    ])
+}
+
+fn extracted_servers() -> Vec<LanguageServerSettings> {
+    let mut css = server("vscode-css-language-server", &["css", "scss", "less"], "vscode-css-language-server", &["--stdio"], &["package.json"]);
+    css.initialization_options = Some(json!({ "provideFormatter": true }));
+    css.settings = Some(json!({
+        "css": { "validate": true },
+        "scss": { "validate": true },
+        "less": { "validate": true }
+    }));
+
+    let mut html = server("vscode-html-language-server", &["html"], "vscode-html-language-server", &["--stdio"], &["package.json"]);
+    html.initialization_options = Some(json!({
+        "provideFormatter": true,
+        "embeddedLanguages": { "css": true, "javascript": true }
+    }));
+    html.settings = Some(json!({
+        "html": { "validate": { "scripts": true, "styles": true } },
+        "css": { "validate": true }
+    }));
+
+    let mut json_server = server("vscode-json-language-server", &["json", "jsonc"], "vscode-json-language-server", &["--stdio"], &["package.json"]);
+    json_server.initialization_options = Some(json!({
+        "provideFormatter": true,
+        // Let the server download schemas itself instead of asking us for their content
+        "handledSchemaProtocols": ["file", "http", "https"]
+    }));
+    json_server.settings = Some(json!({
+        "json": {
+            "validate": { "enable": true },
+            "schemas": json_schemas()
+        }
+    }));
+
+    vec![css, html, json_server]
+}
+
+pub fn builtin_servers() -> Vec<LanguageServerSettings> {
+    let mut servers = vec![
+        server(
+            "typescript-language-server",
+            &["typescript", "typescriptreact", "javascript", "javascriptreact"],
+            "npx",
+            &["typescript-language-server", "--stdio"],
+            &["tsconfig.json", "jsconfig.json", "package.json"],
+        ),
+        server("gopls", &["go"], "gopls", &[], &["go.work", "go.mod"]),
+        server("pylsp", &["python"], "pylsp", &[], &["pyproject.toml", "setup.py", "setup.cfg", "requirements.txt"]),
+        // Cargo.lock sits at the workspace root, so member crates share one server
+        server("rust-analyzer", &["rust"], "rust-analyzer", &[], &["Cargo.lock", "Cargo.toml"]),
+        server(
+            "clangd",
+            &["c", "cpp", "objective-c", "objective-cpp"],
+            "clangd",
+            &[],
+            &["compile_commands.json", "compile_flags.txt", ".clangd", "CMakeLists.txt"],
+        ),
+        server("bash-language-server", &["shellscript"], "bash-language-server", &["start"], &[]),
+        server("yaml-language-server", &["yaml"], "yaml-language-server", &["--stdio"], &[]),
+        server("lua-language-server", &["lua"], "lua-language-server", &[], &[".luarc.json"]),
+    ];
+    servers.extend(extracted_servers());
+    servers
+}
+
+pub struct ServerRegistry {
+    servers: Vec<(LanguageServerSettings, Option<GlobSet>)>,
+}
+
+impl ServerRegistry {
+    pub fn from_settings(settings: &LspSettings) -> Self {
+        let mut servers = settings.servers.clone();
+        servers.extend(builtin_servers().into_iter().filter(|b| !settings.servers.iter().any(|s| s.id == b.id)));
+        servers.retain(|s| !settings.disabled.contains(&s.id));
+
+        Self {
+            servers: servers.into_iter()
+                .map(|server| {
+                    let globs = build_globs(&server.file_patterns);
+                    (server, globs)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn servers(&self) -> impl Iterator<Item = &LanguageServerSettings> {
+        self.servers.iter().map(|(server, _)| server)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&LanguageServerSettings> {
+        self.servers().find(|s| s.id == id)
+    }
+
+    /// Server for a document: the first whose file patterns match, otherwise
+    /// the first that handles its language. Settings entries come before built-ins.
+    pub fn server_for(&self, path: &str) -> Option<&LanguageServerSettings> {
+        let normalized = path.replace('\\', "/");
+        self.servers.iter()
+            .find(|(_, globs)| globs.as_ref().is_some_and(|g| g.is_match(&normalized)))
+            .map(|(server, _)| server)
+            .or_else(|| {
+                let language = language_id(path)?;
+                self.servers().find(|s| s.languages.iter().any(|l| l == language))
+            })
+    }
+
+    /// Server by id, or the first one handling the language `name`.
+    pub fn by_name(&self, name: &str) -> Option<&LanguageServerSettings> {
+        self.get(name).or_else(|| self.servers().find(|s| s.languages.iter().any(|l| l == name)))
+    }
+}
+
+/// Invalid patterns are rejected by settings validation, so they are skipped here.
+fn build_globs(patterns: &[String]) -> Option<GlobSet> {
+    if patterns.is_empty() {
+        return None;
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        if let Ok(glob) = Glob::new(pattern) {
+            builder.add(glob);
+        }
+    }
+    builder.build().ok()
+}
+
+/// Project root for `path`: the nearest ancestor containing the first root
+/// marker found, trying markers in order. The search stops at `boundary`.
+pub fn find_root(root_markers: &[String], path: &Path, boundary: Option<&Path>) -> Option<PathBuf> {
+    root_markers.iter().find_map(|marker| {
+        path.ancestors()
+            .skip(1)
+            .take_while(|dir| boundary.is_none_or(|b| dir.starts_with(b)))
+            .find(|dir| dir.join(marker).exists())
+            .map(Path::to_path_buf)
+    })
+}
+
+/// Resolves `command` the way the shell would, through `PATH` (and `PATHEXT` on Windows).
+pub fn find_executable(command: &str) -> Option<PathBuf> {
+    let path = Path::new(command);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+
+    let extensions: Vec<String> = if cfg!(windows) {
+        std::env::var("PATHEXT")
+            .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+            .split(';')
+            .map(|ext| ext.to_string())
+            .chain(std::iter::once(String::new()))
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .flat_map(|dir| extensions.iter().map(move |ext| dir.join(format!("{}{}", command, ext))))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_override_builtins_and_patterns_win_over_languages() {
+        let mut custom = server("deno", &[], "deno", &["lsp"], &["deno.json"]);
+        custom.file_patterns = vec!["**/supabase/functions/**/*.ts".to_string()];
+        let settings = LspSettings {
+            servers: vec![custom, server("gopls", &["go"], "/opt/go/bin/gopls", &[], &["go.mod"])],
+            disabled: vec!["pylsp".to_string()],
+        };
+        let registry = ServerRegistry::from_settings(&settings);
+
+        assert_eq!(registry.server_for("/repo/supabase/functions/hello/index.ts").unwrap().id, "deno");
+        assert_eq!(registry.server_for("/repo/web/app.tsx").unwrap().id, "typescript-language-server");
+        assert_eq!(registry.server_for("/repo/main.go").unwrap().command, "/opt/go/bin/gopls");
+        assert!(registry.server_for("/repo/tool.py").is_none());
+        assert_eq!(registry.by_name("rust").unwrap().id, "rust-analyzer");
+        assert_eq!(registry.server_for("/repo/styles/app.scss").unwrap().id, "vscode-css-language-server");
+        assert_eq!(language_id("/repo/tsconfig.build.json"), Some("jsonc"));
+        assert_eq!(language_id("/repo/package.json"), Some("json"));
+    }
+
+    #[test]
+    fn finds_root_by_marker_priority_within_boundary() {
+        let workspace = std::env::temp_dir().join(format!("cognitive-lsp-root-{}", uuid::Uuid::new_v4()));
+        let member = workspace.join("crates").join("core");
+        std::fs::create_dir_all(member.join("src")).unwrap();
+        std::fs::write(workspace.join("Cargo.lock"), "").unwrap();
+        std::fs::write(member.join("Cargo.toml"), "").unwrap();
+
+        let markers = vec!["Cargo.lock".to_string(), "Cargo.toml".to_string()];
+        let file = member.join("src").join("lib.rs");
+        assert_eq!(find_root(&markers, &file, Some(&workspace)), Some(workspace.clone()));
+        assert_eq!(find_root(&markers[1..], &file, Some(&workspace)), Some(member.clone()));
+        assert_eq!(find_root(&["go.mod".to_string()], &file, Some(&workspace)), None);
+
+        let _ = std::fs::remove_dir_all(&workspace);
+    }
+}
@@ -0,0 +1,457 @@
+//! Applies `WorkspaceEdit`s returned by rename and code actions to files on disk.
+//!
+//! Every file is snapshotted into the timeline before it changes, and the
+//! summary carries the snapshot ids so the UI can undo the edit with
+//! `timeline_restore`.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use lsp_types::{
+    CreateFile, DeleteFile, DocumentChangeOperation, DocumentChanges, OneOf, RenameFile, ResourceOp,
+    TextEdit, WorkspaceEdit,
+};
+use serde::Serialize;
+use tauri::State;
+use crate::lsp::file_uri_to_path;
+use crate::lsp::manager::LspManager;
+use crate::timeline::timeline_save_snapshot;
+
+/// One applied change. Paths are relative to the workspace, as in the timeline.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum FileChange {
+    Edited {
+        path: String,
+        edits: usize,
+        /// Timeline entry with the content before the edit.
+        snapshot_id: String,
+    },
+    Created {
+        path: String,
+        /// Set when an existing file was overwritten.
+        snapshot_id: Option<String>,
+    },
+    Renamed {
+        from: String,
+        to: String,
+        /// Set when an existing target was overwritten.
+        snapshot_id: Option<String>,
+    },
+    Deleted {
+        path: String,
+        /// `None` for directories; the files of a recursively deleted
+        /// directory are reported, and snapshotted, one by one before it.
+        snapshot_id: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceEditSummary {
+    pub changes: Vec<FileChange>,
+    /// Set when a change failed; the changes before it stay applied.
+    pub error: Option<String>,
+}
+
+enum Step {
+    Edit { path: PathBuf, version: Option<i32>, edits: Vec<TextEdit> },
+    Create(CreateFile),
+    Rename(RenameFile),
+    Delete(DeleteFile),
+}
+
+fn steps(edit: WorkspaceEdit) -> Result<Vec<Step>, String> {
+    // `documentChanges` takes precedence over `changes` when a server sends both
+    if let Some(document_changes) = edit.document_changes {
+        let operations = match document_changes {
+            DocumentChanges::Edits(edits) => edits.into_iter().map(DocumentChangeOperation::Edit).collect(),
+            DocumentChanges::Operations(operations) => operations,
+        };
+        return operations.into_iter()
+            .map(|operation| Ok(match operation {
+                DocumentChangeOperation::Edit(edit) => Step::Edit {
+                    path: file_uri_to_path(edit.text_document.uri.as_str())?,
+                    version: edit.text_document.version,
+                    edits: edit.edits.into_iter()
+                        .map(|edit| match edit {
+                            OneOf::Left(edit) => edit,
+                            OneOf::Right(annotated) => annotated.text_edit,
+                        })
+                        .collect(),
+                },
+                DocumentChangeOperation::Op(ResourceOp::Create(op)) => Step::Create(op),
+                DocumentChangeOperation::Op(ResourceOp::Rename(op)) => Step::Rename(op),
+                DocumentChangeOperation::Op(ResourceOp::Delete(op)) => Step::Delete(op),
+            }))
+            .collect();
+    }
+
+    let mut changes: Vec<_> = edit.changes.unwrap_or_default().into_iter().collect();
+    changes.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+    changes.into_iter()
+        .map(|(uri, edits)| Ok(Step::Edit { path: file_uri_to_path(uri.as_str())?, version: None, edits }))
+        .collect()
+}
+
+/// Applies `edit` inside `workspace`. `open_document` returns the version and
+/// text last synced to the language server for open documents. Edits are
+/// applied to the files on disk, so they are refused before anything is
+/// written when computed against another version, or when the editor holds
+/// unsaved changes the server based its positions on.
+pub fn apply_workspace_edit(workspace: &Path, edit: WorkspaceEdit, open_document: impl Fn(&Path) -> Option<(i32, String)>) -> Result<WorkspaceEditSummary, String> {
+    let steps = steps(edit)?;
+
+    for step in &steps {
+        let paths: Vec<PathBuf> = match step {
+            Step::Edit { path, version, .. } => {
+                if let Some((current, text)) = open_document(path) {
+                    if let Some(expected) = version.filter(|expected| *expected != current) {
+                        return Err(format!("{} changed since the edit was computed (version {} instead of {})", path.display(), current, expected));
+                    }
+                    let on_disk = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                    if !same_text(&on_disk, &text) {
+                        return Err(format!("{} has unsaved changes; save it before applying the edit", path.display()));
+                    }
+                }
+                vec![path.clone()]
+            }
+            Step::Create(op) => vec![file_uri_to_path(op.uri.as_str())?],
+            Step::Rename(op) => vec![file_uri_to_path(op.old_uri.as_str())?, file_uri_to_path(op.new_uri.as_str())?],
+            Step::Delete(op) => vec![file_uri_to_path(op.uri.as_str())?],
+        };
+        for path in paths {
+            relative_path(workspace, &path)?;
+        }
+    }
+
+    let mut summary = WorkspaceEditSummary { changes: Vec::new(), error: None };
+    for step in steps {
+        match apply_step(workspace, step) {
+            Ok(changes) => summary.changes.extend(changes),
+            Err(e) => {
+                summary.error = Some(e);
+                break;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+fn relative_path(workspace: &Path, path: &Path) -> Result<String, String> {
+    let outside = || format!("{} is outside the workspace", path.display());
+    let relative = path.strip_prefix(workspace)
+        .ok()
+        .filter(|relative| relative.components().all(|c| matches!(c, Component::Normal(_))))
+        .ok_or_else(outside)?;
+    // A symlink inside the workspace can still point out of it; symlink_metadata
+    // so a dangling link is checked rather than skipped
+    let root = workspace.canonicalize().map_err(|e| format!("Failed to resolve workspace: {}", e))?;
+    let existing = path.ancestors()
+        .find(|p| p.symlink_metadata().is_ok())
+        .and_then(|p| p.canonicalize().ok());
+    if !existing.is_some_and(|existing| existing.starts_with(&root)) {
+        return Err(outside());
+    }
+    // Rebuilt so URI-derived forward slashes match the platform separator the editor uses
+    Ok(relative.components().collect::<PathBuf>().to_string_lossy().to_string())
+}
+
+/// Whether two texts differ at most in their line endings, which do not move LSP positions.
+fn same_text(a: &str, b: &str) -> bool {
+    a == b || a.replace("\r\n", "\n") == b.replace("\r\n", "\n")
+}
+
+/// Files under `dir`, sorted; symbolic links are removed without being followed, so they are skipped.
+fn files_under(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        let file_type = entry.file_type().map_err(|e| format!("Failed to read {}: {}", entry.path().display(), e))?;
+        if file_type.is_dir() || file_type.is_file() {
+            paths.push((entry.path(), file_type.is_dir()));
+        }
+    }
+    paths.sort();
+    for (path, is_dir) in paths {
+        if is_dir {
+            files_under(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn snapshot(workspace: &Path, path: &Path) -> Result<String, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let entry = timeline_save_snapshot(workspace.to_string_lossy().to_string(), relative_path(workspace, path)?, content)?;
+    Ok(entry.id)
+}
+
+/// Returns no changes for operations skipped by their options.
+fn apply_step(workspace: &Path, step: Step) -> Result<Vec<FileChange>, String> {
+    match step {
+        Step::Edit { path, edits, .. } => {
+            let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let updated = apply_text_edits(&content, &edits)?;
+            let snapshot_id = snapshot(workspace, &path)?;
+            fs::write(&path, updated).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            Ok(vec![FileChange::Edited { path: relative_path(workspace, &path)?, edits: edits.len(), snapshot_id }])
+        }
+        Step::Create(op) => {
+            let path = file_uri_to_path(op.uri.as_str())?;
+            let overwrite = op.options.as_ref().and_then(|o| o.overwrite) == Some(true);
+            let ignore_if_exists = op.options.as_ref().and_then(|o| o.ignore_if_exists) == Some(true);
+            let mut snapshot_id = None;
+            if path.exists() {
+                if overwrite {
+                    snapshot_id = Some(snapshot(workspace, &path)?);
+                } else if ignore_if_exists {
+                    return Ok(Vec::new());
+                } else {
+                    return Err(format!("{} already exists", path.display()));
+                }
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            }
+            fs::write(&path, "").map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+            Ok(vec![FileChange::Created { path: relative_path(workspace, &path)?, snapshot_id }])
+        }
+        Step::Rename(op) => {
+            let from = file_uri_to_path(op.old_uri.as_str())?;
+            let to = file_uri_to_path(op.new_uri.as_str())?;
+            let overwrite = op.options.as_ref().and_then(|o| o.overwrite) == Some(true);
+            let ignore_if_exists = op.options.as_ref().and_then(|o| o.ignore_if_exists) == Some(true);
+            let mut snapshot_id = None;
+            if to.exists() {
+                if overwrite {
+                    if to.is_file() {
+                        snapshot_id = Some(snapshot(workspace, &to)?);
+                    }
+                } else if ignore_if_exists {
+                    return Ok(Vec::new());
+                } else {
+                    return Err(format!("{} already exists", to.display()));
+                }
+            }
+            if from.is_file() {
+                snapshot(workspace, &from)?;
+            }
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            }
+            fs::rename(&from, &to).map_err(|e| format!("Failed to rename {}: {}", from.display(), e))?;
+            Ok(vec![FileChange::Renamed { from: relative_path(workspace, &from)?, to: relative_path(workspace, &to)?, snapshot_id }])
+        }
+        Step::Delete(op) => {
+            let path = file_uri_to_path(op.uri.as_str())?;
+            let recursive = op.options.as_ref().and_then(|o| o.recursive) == Some(true);
+            let ignore_if_not_exists = op.options.as_ref().and_then(|o| o.ignore_if_not_exists) == Some(true);
+            if !path.exists() {
+                return if ignore_if_not_exists {
+                    Ok(Vec::new())
+                } else {
+                    Err(format!("{} does not exist", path.display()))
+                };
+            }
+            if !path.is_dir() {
+                let snapshot_id = snapshot(workspace, &path)?;
+                fs::remove_file(&path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+                return Ok(vec![FileChange::Deleted { path: relative_path(workspace, &path)?, snapshot_id: Some(snapshot_id) }]);
+            }
+
+            let mut changes = Vec::new();
+            let removed = if recursive {
+                // Everything is snapshotted before anything is removed, so a file
+                // that cannot be snapshotted leaves the directory untouched
+                let mut files = Vec::new();
+                files_under(&path, &mut files)?;
+                for file in files {
+                    let snapshot_id = snapshot(workspace, &file)?;
+                    changes.push(FileChange::Deleted { path: relative_path(workspace, &file)?, snapshot_id: Some(snapshot_id) });
+                }
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_dir(&path)
+            };
+            removed.map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+            changes.push(FileChange::Deleted { path: relative_path(workspace, &path)?, snapshot_id: None });
+            Ok(changes)
+        }
+    }
+}
+
+/// Byte offset of an LSP position; `character` counts UTF-16 code units and
+/// is clamped to the end of its line.
+fn offset(text: &str, line_starts: &[usize], line: u32, character: u32) -> usize {
+    let Some(&start) = line_starts.get(line as usize) else {
+        return text.len();
+    };
+    let line_text = text[start..].split('\n').next().unwrap_or_default();
+    let line_text = line_text.strip_suffix('\r').unwrap_or(line_text);
+
+    let mut units = 0;
+    for (idx, c) in line_text.char_indices() {
+        if units >= character as usize {
+            return start + idx;
+        }
+        units += c.len_utf16();
+    }
+    start + line_text.len()
+}
+
+/// Applies text edits computed against `text`. Edits must not overlap;
+/// inserts at the same position keep their order.
+pub fn apply_text_edits(text: &str, edits: &[TextEdit]) -> Result<String, String> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(idx, _)| idx + 1))
+        .collect();
+
+    let mut spans: Vec<(usize, usize, usize, &str)> = edits.iter()
+        .enumerate()
+        .map(|(idx, edit)| {
+            let start = offset(text, &line_starts, edit.range.start.line, edit.range.start.character);
+            let end = offset(text, &line_starts, edit.range.end.line, edit.range.end.character);
+            (start, end.max(start), idx, edit.new_text.as_str())
+        })
+        .collect();
+    spans.sort_by_key(|&(start, end, idx, _)| (start, end, idx));
+
+    let mut result = String::with_capacity(text.len());
+    let mut pos = 0;
+    for (start, end, _, new_text) in spans {
+        if start < pos {
+            return Err("Workspace edit contains overlapping text edits".to_string());
+        }
+        result.push_str(&text[pos..start]);
+        result.push_str(new_text);
+        pos = end;
+    }
+    result.push_str(&text[pos..]);
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn lsp_apply_workspace_edit(state: State<LspManager>, workspace: String, edit: WorkspaceEdit) -> Result<WorkspaceEditSummary, String> {
+    apply_workspace_edit(Path::new(&workspace), edit, |path| state.open_document(&path.to_string_lossy()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Position, Range};
+
+    fn edit(start: (u32, u32), end: (u32, u32), new_text: &str) -> TextEdit {
+        TextEdit {
+            range: Range { start: Position::new(start.0, start.1), end: Position::new(end.0, end.1) },
+            new_text: new_text.to_string(),
+        }
+    }
+
+    #[test]
+    fn applies_edits_by_utf16_position() {
+        let text = "let 😀 = old;\r\nold();\n";
+        let edits = vec![
+            edit((1, 0), (1, 3), "new"),
+            // The emoji is two UTF-16 units wide
+            edit((0, 9), (0, 12), "new"),
+            edit((0, 0), (0, 0), "// a\n"),
+            edit((0, 0), (0, 0), "// b\n"),
+        ];
+        assert_eq!(apply_text_edits(text, &edits).unwrap(), "// a\n// b\nlet 😀 = new;\r\nnew();\n");
+        assert!(apply_text_edits(text, &[edit((0, 0), (0, 5), ""), edit((0, 2), (0, 3), "")]).is_err());
+    }
+
+    #[test]
+    fn refuses_stale_versions_and_paths_outside_the_workspace() {
+        let workspace = std::env::temp_dir().join(format!("cognitive-edit-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&workspace).unwrap();
+        let file = workspace.join("main.ts");
+        fs::write(&file, "const a = 1;\n").unwrap();
+        let uri: lsp_types::Uri = crate::lsp::document_uri(&file.to_string_lossy()).unwrap();
+
+        let stale = WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Edits(vec![lsp_types::TextDocumentEdit {
+                text_document: lsp_types::OptionalVersionedTextDocumentIdentifier { uri: uri.clone(), version: Some(3) },
+                edits: vec![OneOf::Left(edit((0, 6), (0, 7), "b"))],
+            }])),
+            ..Default::default()
+        };
+        let open = |version: i32, text: &str| {
+            let text = text.to_string();
+            move |_: &Path| Some((version, text.clone()))
+        };
+        assert!(apply_workspace_edit(&workspace, stale.clone(), open(4, "const a = 1;\n")).is_err());
+        // Positions were computed against the unsaved buffer, not the file
+        let err = apply_workspace_edit(&workspace, stale.clone(), open(3, "// new\nconst a = 1;\n")).unwrap_err();
+        assert!(err.contains("unsaved changes"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "const a = 1;\n");
+
+        let summary = apply_workspace_edit(&workspace, stale, open(3, "const a = 1;\r\n")).unwrap();
+        assert!(summary.error.is_none());
+        assert!(matches!(&summary.changes[0], FileChange::Edited { path, edits: 1, .. } if path == "main.ts"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "const b = 1;\n");
+
+        let outside = WorkspaceEdit {
+            changes: Some([(crate::lsp::document_uri("/etc/hosts").unwrap(), vec![edit((0, 0), (0, 0), "x")])].into_iter().collect()),
+            ..Default::default()
+        };
+        assert!(apply_workspace_edit(&workspace, outside, |_| None).is_err());
+
+        #[cfg(unix)]
+        {
+            let target = std::env::temp_dir().join(format!("cognitive-edit-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&target).unwrap();
+            std::os::unix::fs::symlink(&target, workspace.join("escape")).unwrap();
+            let through_link = WorkspaceEdit {
+                document_changes: Some(DocumentChanges::Operations(vec![DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+                    uri: crate::lsp::document_uri(&workspace.join("escape").join("new.ts").to_string_lossy()).unwrap(),
+                    options: None,
+                    annotation_id: None,
+                }))])),
+                ..Default::default()
+            };
+            assert!(apply_workspace_edit(&workspace, through_link, |_| None).is_err());
+            assert!(!target.join("new.ts").exists());
+            let _ = fs::remove_dir_all(&target);
+        }
+
+        let _ = fs::remove_dir_all(&workspace);
+    }
+
+    #[test]
+    fn snapshots_files_before_deleting_a_directory() {
+        let workspace = std::env::temp_dir().join(format!("cognitive-edit-{}", uuid::Uuid::new_v4()));
+        let dir = workspace.join("src").join("old");
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("a.ts"), "a").unwrap();
+        fs::write(dir.join("nested").join("b.ts"), "b").unwrap();
+
+        let delete = WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(vec![DocumentChangeOperation::Op(ResourceOp::Delete(DeleteFile {
+                uri: crate::lsp::document_uri(&dir.to_string_lossy()).unwrap(),
+                options: Some(lsp_types::DeleteFileOptions { recursive: Some(true), ignore_if_not_exists: None, annotation_id: None }),
+            }))])),
+            ..Default::default()
+        };
+        let summary = apply_workspace_edit(&workspace, delete, |_| None).unwrap();
+        assert!(summary.error.is_none());
+        assert!(!dir.exists());
+
+        let deleted: Vec<(String, bool)> = summary.changes.iter()
+            .map(|change| match change {
+                FileChange::Deleted { path, snapshot_id } => (path.replace('\\', "/"), snapshot_id.is_some()),
+                other => panic!("unexpected change {:?}", other),
+            })
+            .collect();
+        assert_eq!(deleted, vec![
+            ("src/old/a.ts".to_string(), true),
+            ("src/old/nested/b.ts".to_string(), true),
+            ("src/old".to_string(), false),
+        ]);
+
+        let _ = fs::remove_dir_all(&workspace);
+    }
+}
@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::{oneshot, Mutex};
+use crate::mcp::types::{McpResource, McpTool};
+
+pub const PROTOCOL_VERSION: &str = "2025-03-26";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+type PendingMap = Arc<std::sync::Mutex<HashMap<u64, oneshot::Sender<Result<Value, String>>>>>;
+
+enum Transport {
+    Stdio {
+        // Held so the server is killed when the client is dropped
+        _child: Child,
+        stdin: Arc<Mutex<ChildStdin>>,
+        pending: PendingMap,
+    },
+    Http {
+        url: String,
+        headers: HashMap<String, String>,
+        client: reqwest::Client,
+        session_id: std::sync::Mutex<Option<String>>,
+    },
+}
+
+/// JSON-RPC client for a single MCP server.
+pub struct McpClient {
+    transport: Transport,
+    next_id: AtomicU64,
+}
+
+async fn write_message(stdin: &Mutex<ChildStdin>, msg: &Value) -> Result<(), String> {
+    let mut line = msg.to_string();
+    line.push('\n');
+    let mut w = stdin.lock().await;
+    w.write_all(line.as_bytes()).await.map_err(|e| e.to_string())?;
+    w.flush().await.map_err(|e| e.to_string())
+}
+
+/// Turns a JSON-RPC response into its result, or the error message.
+fn into_result(msg: Value) -> Result<Value, String> {
+    if let Some(error) = msg.get("error") {
+        let message = error.get("message").and_then(|m| m.as_str()).unwrap_or("Unknown error");
+        return Err(format!("MCP error: {}", message));
+    }
+    Ok(msg.get("result").cloned().unwrap_or(Value::Null))
+}
+
+impl McpClient {
+    pub async fn connect_stdio(
+        command: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+        cwd: Option<&Path>,
+    ) -> Result<Self, String> {
+        #[cfg(target_os = "windows")]
+        let mut cmd = {
+            let mut c = Command::new("cmd");
+            c.arg("/C").arg(command);
+            c
+        };
+        #[cfg(not(target_os = "windows"))]
+        let mut cmd = Command::new(command);
+
+        cmd.args(args)
+            .envs(env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        if let Some(cwd) = cwd {
+            cmd.current_dir(cwd);
+        }
+
+        let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn MCP server '{}': {}", command, e))?;
+        let stdin = child.stdin.take().ok_or("Failed to open stdin")?;
+        let stdout = child.stdout.take().ok_or("Failed to open stdout")?;
+
+        let stdin = Arc::new(Mutex::new(stdin));
+        let pending: PendingMap = Arc::new(std::sync::Mutex::new(HashMap::new()));
+
+        // Stdout reader: one JSON-RPC message per line
+        let reader_stdin = stdin.clone();
+        let reader_pending = pending.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let msg = match serde_json::from_str::<Value>(line.trim()) {
+                    Ok(msg) => msg,
+                    Err(_) => continue,
+                };
+
+                let id = msg.get("id").and_then(|v| v.as_u64());
+                if let Some(method) = msg.get("method").and_then(|m| m.as_str()) {
+                    // Server-to-client request: answer pings, reject anything else
+                    if let Some(request_id) = msg.get("id").cloned() {
+                        let reply = if method == "ping" {
+                            json!({ "jsonrpc": "2.0", "id": request_id, "result": {} })
+                        } else {
+                            json!({
+                                "jsonrpc": "2.0",
+                                "id": request_id,
+                                "error": { "code": -32601, "message": format!("Method not supported: {}", method) }
+                            })
+                        };
+                        let _ = write_message(&reader_stdin, &reply).await;
+                    }
+                    continue;
+                }
+
+                if let Some(id) = id {
+                    if let Some(tx) = reader_pending.lock().unwrap().remove(&id) {
+                        let _ = tx.send(into_result(msg));
+                    }
+                }
+            }
+
+            // Server exited: fail everything still waiting
+            for (_, tx) in reader_pending.lock().unwrap().drain() {
+                let _ = tx.send(Err("MCP server exited".to_string()));
+            }
+        });
+
+        Ok(Self {
+            transport: Transport::Stdio { _child: child, stdin, pending },
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    pub fn connect_http(url: &str, headers: &HashMap<String, String>) -> Self {
+        Self {
+            transport: Transport::Http {
+                url: url.to_string(),
+                headers: headers.clone(),
+                client: reqwest::Client::new(),
+                session_id: std::sync::Mutex::new(None),
+            },
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Posts one message to a streamable HTTP endpoint. Returns the response
+    /// matching `expect_id`, read either from a JSON body or an SSE stream.
+    async fn post(&self, msg: &Value, expect_id: Option<u64>) -> Result<Option<Value>, String> {
+        let (url, headers, client, session_id) = match &self.transport {
+            Transport::Http { url, headers, client, session_id } => (url, headers, client, session_id),
+            Transport::Stdio { .. } => return Err("Not an HTTP transport".to_string()),
+        };
+
+        let mut request = client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/json, text/event-stream")
+            .json(msg);
+        for (key, value) in headers {
+            request = request.header(key.as_str(), value.as_str());
+        }
+        if let Some(id) = session_id.lock().unwrap().clone() {
+            request = request.header("Mcp-Session-Id", id);
+        }
+
+        let response = request.send().await.map_err(|e| format!("Failed to reach MCP server at {}: {}", url, e))?;
+        if let Some(id) = response.headers().get("mcp-session-id").and_then(|v| v.to_str().ok()) {
+            *session_id.lock().unwrap() = Some(id.to_string());
+        }
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("MCP HTTP error: {} (Status: {})", error_text, status));
+        }
+        let expect_id = match expect_id {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+
+        let is_sse = response.headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .map(|ct| ct.contains("text/event-stream"))
+            .unwrap_or(false);
+
+        if !is_sse {
+            let body: Value = response.json().await.map_err(|e| e.to_string())?;
+            return Ok(Some(body));
+        }
+
+        let mut stream = response.bytes_stream();
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut data: Vec<String> = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| e.to_string())?;
+            buffer.extend_from_slice(&chunk);
+
+            // Only complete lines are decoded, so a character split across chunks stays intact
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line_bytes = buffer.drain(..pos + 1).collect::<Vec<u8>>();
+                let line_str = String::from_utf8_lossy(&line_bytes);
+                let line = line_str.trim_end_matches(['\n', '\r']);
+                if let Some(value) = line.strip_prefix("data:") {
+                    data.push(value.trim_start().to_string());
+                    continue;
+                }
+                if !line.is_empty() {
+                    continue;
+                }
+                // A blank line ends the event
+                let event = data.join("\n");
+                data.clear();
+                if let Ok(msg) = serde_json::from_str::<Value>(&event) {
+                    if msg.get("id").and_then(|v| v.as_u64()) == Some(expect_id) && msg.get("method").is_none() {
+                        return Ok(Some(msg));
+                    }
+                }
+            }
+        }
+
+        Err("MCP server closed the event stream without a response".to_string())
+    }
+
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let msg = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+
+        match &self.transport {
+            Transport::Stdio { stdin, pending, .. } => {
+                let (tx, rx) = oneshot::channel();
+                pending.lock().unwrap().insert(id, tx);
+                if let Err(e) = write_message(stdin, &msg).await {
+                    pending.lock().unwrap().remove(&id);
+                    return Err(format!("Failed to write to MCP server: {}", e));
+                }
+
+                match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+                    Ok(Ok(result)) => result,
+                    Ok(Err(_)) => Err("MCP server closed the connection".to_string()),
+                    Err(_) => {
+                        pending.lock().unwrap().remove(&id);
+                        Err(format!("MCP request '{}' timed out", method))
+                    }
+                }
+            }
+            Transport::Http { .. } => {
+                let response = tokio::time::timeout(REQUEST_TIMEOUT, self.post(&msg, Some(id)))
+                    .await
+                    .map_err(|_| format!("MCP request '{}' timed out", method))??;
+                into_result(response.ok_or("Empty MCP response")?)
+            }
+        }
+    }
+
+    pub async fn notify(&self, method: &str, params: Value) -> Result<(), String> {
+        let msg = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        match &self.transport {
+            Transport::Stdio { stdin, .. } => write_message(stdin, &msg).await,
+            Transport::Http { .. } => self.post(&msg, None).await.map(|_| ()),
+        }
+    }
+
+    pub async fn initialize(&self) -> Result<Value, String> {
+        let result = self.request("initialize", json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": { "name": "cognitive", "version": env!("CARGO_PKG_VERSION") }
+        })).await?;
+        self.notify("notifications/initialized", json!({})).await?;
+        Ok(result)
+    }
+
+    pub async fn list_tools(&self) -> Result<Vec<McpTool>, String> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(c) => json!({ "cursor": c }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            if let Some(page) = result.get("tools") {
+                let page: Vec<McpTool> = serde_json::from_value(page.clone()).map_err(|e| e.to_string())?;
+                tools.extend(page);
+            }
+            cursor = result.get("nextCursor").and_then(|c| c.as_str()).map(|s| s.to_string());
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok(tools)
+    }
+
+    pub async fn list_resources(&self) -> Result<Vec<McpResource>, String> {
+        let mut resources = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(c) => json!({ "cursor": c }),
+                None => json!({}),
+            };
+            let result = self.request("resources/list", params).await?;
+            if let Some(page) = result.get("resources") {
+                let page: Vec<McpResource> = serde_json::from_value(page.clone()).map_err(|e| e.to_string())?;
+                resources.extend(page);
+            }
+            cursor = result.get("nextCursor").and_then(|c| c.as_str()).map(|s| s.to_string());
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok(resources)
+    }
+
+    /// Calls a tool and flattens its content blocks to text.
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<String, String> {
+        let result = self.request("tools/call", json!({ "name": name, "arguments": arguments })).await?;
+
+        let text = result.get("content")
+            .and_then(|c| c.as_array())
+            .map(|blocks| {
+                blocks.iter()
+                    .map(|block| match block.get("type").and_then(|t| t.as_str()) {
+                        Some("text") => block.get("text").and_then(|t| t.as_str()).unwrap_or("").to_string(),
+                        Some("resource") => block.get("resource")
+                            .and_then(|r| r.get("text"))
+                            .and_then(|t| t.as_str())
+                            .unwrap_or("[resource]")
+                            .to_string(),
+                        Some(other) => format!("[{} content omitted]", other),
+                        None => String::new(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .unwrap_or_default();
+
+        if result.get("isError").and_then(|v| v.as_bool()).unwrap_or(false) {
+            return Err(text);
+        }
+        Ok(text)
+    }
+
+    pub async fn read_resource(&self, uri: &str) -> Result<String, String> {
+        let result = self.request("resources/read", json!({ "uri": uri })).await?;
+        let contents = result.get("contents").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+        Ok(contents.iter()
+            .map(|c| match c.get("text").and_then(|t| t.as_str()) {
+                Some(text) => text.to_string(),
+                None => format!("[binary content: {}]", c.get("mimeType").and_then(|m| m.as_str()).unwrap_or("unknown")),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    /// Answers every request with `body` as `content_type` and an `Mcp-Session-Id`.
+    async fn serve(content_type: &'static str, body: String) -> String {
+        serve_parts(content_type, vec![body.into_bytes()]).await
+    }
+
+    /// Like `serve`, but writes the body in separate packets.
+    async fn serve_parts(content_type: &'static str, parts: Vec<Vec<u8>>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nMcp-Session-Id: s1\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    content_type,
+                    parts.iter().map(Vec::len).sum::<usize>()
+                );
+                let _ = socket.write_all(head.as_bytes()).await;
+                for part in &parts {
+                    let _ = socket.write_all(part).await;
+                    let _ = socket.flush().await;
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            }
+        });
+        format!("http://{}/mcp", addr)
+    }
+
+    #[tokio::test]
+    async fn reads_json_responses() {
+        let url = serve("application/json", json!({ "jsonrpc": "2.0", "id": 1, "result": { "tools": [] } }).to_string()).await;
+        let client = McpClient::connect_http(&url, &HashMap::new());
+        assert_eq!(client.request("tools/list", json!({})).await.unwrap(), json!({ "tools": [] }));
+        let Transport::Http { session_id, .. } = &client.transport else { unreachable!() };
+        assert_eq!(session_id.lock().unwrap().as_deref(), Some("s1"));
+
+        let url = serve("application/json", json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": -32601, "message": "nope" } }).to_string()).await;
+        let client = McpClient::connect_http(&url, &HashMap::new());
+        assert_eq!(client.request("tools/list", json!({})).await.unwrap_err(), "MCP error: nope");
+    }
+
+    #[tokio::test]
+    async fn reads_the_matching_response_from_an_event_stream() {
+        let body = [
+            "event: message\r\ndata: {\"jsonrpc\":\"2.0\",\"method\":\"notifications/progress\",\"params\":{}}\r\n\r\n",
+            "data: {\"jsonrpc\":\"2.0\",\"id\":7,\"result\":{}}\n\n",
+            "data: {\"jsonrpc\":\"2.0\",\n",
+            "data: \"id\":1,\"result\":{\"ok\":true}}\n\n",
+        ].concat();
+        let url = serve("text/event-stream", body).await;
+        let client = McpClient::connect_http(&url, &HashMap::new());
+        assert_eq!(client.request("ping", json!({})).await.unwrap(), json!({ "ok": true }));
+
+        // "Привет" split inside its first character
+        let event = "data: {\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{\"text\":\"Привет\"}}\n\n".as_bytes();
+        let split = event.iter().position(|&b| b >= 0x80).unwrap() + 1;
+        let url = serve_parts("text/event-stream", vec![event[..split].to_vec(), event[split..].to_vec()]).await;
+        let client = McpClient::connect_http(&url, &HashMap::new());
+        assert_eq!(client.request("ping", json!({})).await.unwrap(), json!({ "text": "Привет" }));
+
+        let url = serve("text/event-stream", ": keep-alive\n\n".to_string()).await;
+        let client = McpClient::connect_http(&url, &HashMap::new());
+        assert!(client.request("ping", json!({})).await.unwrap_err().contains("without a response"));
+    }
+}
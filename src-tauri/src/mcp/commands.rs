@@ -0,0 +1,167 @@
+use std::sync::{Arc, Mutex};
+use serde::Serialize;
+use tauri::State;
+use tokio::io::BufReader;
+use crate::agent::AgentState;
+use crate::mcp::manager::split_tool_name;
+use crate::mcp::server::McpToolServer;
+use crate::mcp::types::{ApprovalMode, McpServerStatus};
+
+struct RunningServer {
+    port: u16,
+    allow_writes: bool,
+    token: String,
+    /// Accept loop; aborting it also aborts every open connection.
+    task: tokio::task::JoinHandle<()>,
+}
+
+/// The local MCP server exposing Cognitive's own tools while the app runs.
+#[derive(Default)]
+pub struct McpServerState {
+    running: Mutex<Option<RunningServer>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpServerInfo {
+    pub running: bool,
+    pub port: Option<u16>,
+    pub allow_writes: bool,
+    /// Clients send `Bearer <token>` as their first line.
+    pub token: Option<String>,
+}
+
+impl McpServerState {
+    fn info(&self) -> McpServerInfo {
+        match self.running.lock().unwrap().as_ref() {
+            Some(server) => McpServerInfo {
+                running: true,
+                port: Some(server.port),
+                allow_writes: server.allow_writes,
+                token: Some(server.token.clone()),
+            },
+            None => McpServerInfo { running: false, port: None, allow_writes: false, token: None },
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn mcp_list_servers(state: State<'_, AgentState>) -> Result<Vec<McpServerStatus>, String> {
+    Ok(state.mcp.statuses().await)
+}
+
+/// Re-reads `.cognitive/mcp.json` and reconnects every enabled server.
+#[tauri::command]
+pub async fn mcp_reload(state: State<'_, AgentState>) -> Result<Vec<McpServerStatus>, String> {
+    let workspace = state.workspace_path.lock().unwrap().clone().ok_or("No workspace open")?;
+    state.mcp.load_workspace(&workspace).await?;
+    Ok(state.mcp.statuses().await)
+}
+
+#[tauri::command]
+pub async fn mcp_set_server_enabled(
+    state: State<'_, AgentState>,
+    name: String,
+    enabled: bool,
+) -> Result<McpServerStatus, String> {
+    state.mcp.set_enabled(&name, enabled).await
+}
+
+#[tauri::command]
+pub async fn mcp_set_server_approval(
+    state: State<'_, AgentState>,
+    name: String,
+    approval: ApprovalMode,
+) -> Result<McpServerStatus, String> {
+    state.mcp.set_approval(&name, approval).await
+}
+
+/// Calls a server tool directly from the UI; no approval is needed since the user triggered it.
+#[tauri::command]
+pub async fn mcp_call_tool(
+    state: State<'_, AgentState>,
+    server: String,
+    tool: String,
+    arguments: serde_json::Value,
+) -> Result<String, String> {
+    let qualified = crate::mcp::manager::qualified_tool_name(&server, &tool);
+    if split_tool_name(&qualified) != Some((server.as_str(), tool.as_str())) {
+        return Err(format!("Invalid MCP server name: {}", server));
+    }
+    state.mcp.call_tool(&qualified, arguments).await
+}
+
+#[tauri::command]
+pub async fn mcp_read_resource(
+    state: State<'_, AgentState>,
+    server: String,
+    uri: String,
+) -> Result<String, String> {
+    state.mcp.read_resource(&server, &uri).await
+}
+
+/// Starts serving the current workspace over newline-delimited JSON-RPC on a
+/// localhost TCP port (random if `port` is not given). Read-only unless `allow_writes`.
+/// Other local processes can reach the port, so each connection must first
+/// present the returned token.
+#[tauri::command]
+pub async fn mcp_server_start(
+    state: State<'_, AgentState>,
+    server_state: State<'_, McpServerState>,
+    port: Option<u16>,
+    allow_writes: Option<bool>,
+) -> Result<McpServerInfo, String> {
+    if server_state.running.lock().unwrap().is_some() {
+        return Ok(server_state.info());
+    }
+
+    let workspace = state.workspace_path.lock().unwrap().clone().ok_or("No workspace open")?;
+    let allow_writes = allow_writes.unwrap_or(false);
+    let server = Arc::new(McpToolServer::new(workspace, state.rag_engine.clone(), allow_writes));
+
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port.unwrap_or(0)))
+        .await
+        .map_err(|e| format!("Failed to bind MCP server: {}", e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    let connection_token = token.clone();
+    let task = tokio::spawn(async move {
+        // Dropped with this task, which aborts the connections still open
+        let mut connections = tokio::task::JoinSet::new();
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let Ok((stream, _)) = accepted else { break };
+                    let server = server.clone();
+                    let token = connection_token.clone();
+                    connections.spawn(async move {
+                        let (read_half, write_half) = stream.into_split();
+                        let _ = server.serve_with_token(BufReader::new(read_half), write_half, &token).await;
+                    });
+                }
+                Some(_) = connections.join_next() => {}
+            }
+        }
+    });
+
+    let mut running = server_state.running.lock().unwrap();
+    if let Some(previous) = running.replace(RunningServer { port, allow_writes, token, task }) {
+        previous.task.abort();
+    }
+    drop(running);
+    Ok(server_state.info())
+}
+
+#[tauri::command]
+pub fn mcp_server_stop(server_state: State<'_, McpServerState>) -> Result<McpServerInfo, String> {
+    if let Some(server) = server_state.running.lock().unwrap().take() {
+        server.task.abort();
+    }
+    Ok(server_state.info())
+}
+
+#[tauri::command]
+pub fn mcp_server_status(server_state: State<'_, McpServerState>) -> McpServerInfo {
+    server_state.info()
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::RwLock;
+use crate::mcp::client::McpClient;
+use crate::mcp::types::*;
+
+/// Prefix of agent tool names backed by MCP servers: `mcp__<server>__<tool>`.
+pub const MCP_TOOL_PREFIX: &str = "mcp__";
+
+/// Agent tool reading a resource from any connected server.
+pub const READ_RESOURCE_TOOL: &str = "read_resource";
+
+struct McpServer {
+    config: McpServerConfig,
+    client: Option<Arc<McpClient>>,
+    error: Option<String>,
+    tools: Vec<McpTool>,
+    resources: Vec<McpResource>,
+}
+
+impl McpServer {
+    fn status(&self, name: &str) -> McpServerStatus {
+        McpServerStatus {
+            name: name.to_string(),
+            transport: if self.config.url.is_some() { "http".to_string() } else { "stdio".to_string() },
+            enabled: self.config.enabled,
+            connected: self.client.is_some(),
+            error: self.error.clone(),
+            approval: self.config.approval,
+            tools: self.tools.clone(),
+            resources: self.resources.clone(),
+        }
+    }
+}
+
+pub fn qualified_tool_name(server: &str, tool: &str) -> String {
+    format!("{}{}__{}", MCP_TOOL_PREFIX, server, tool)
+}
+
+/// Splits `mcp__<server>__<tool>` into its server and tool names.
+pub fn split_tool_name(name: &str) -> Option<(&str, &str)> {
+    name.strip_prefix(MCP_TOOL_PREFIX)?.split_once("__")
+}
+
+fn config_path(workspace: &Path) -> PathBuf {
+    workspace.join(".cognitive").join("mcp.json")
+}
+
+fn read_config(workspace: &Path) -> Result<McpConfigFile, String> {
+    let path = config_path(workspace);
+    if !path.exists() {
+        return Ok(McpConfigFile::default());
+    }
+    let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read MCP config: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse MCP config: {}", e))
+}
+
+/// Parameters parsed from the model's output arrive as scalars, so nested
+/// objects and arrays are passed as JSON strings and decoded here.
+fn normalize_arguments(arguments: serde_json::Value) -> serde_json::Value {
+    match arguments {
+        serde_json::Value::Object(map) => serde_json::Value::Object(map.into_iter().map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::String(s) if s.trim_start().starts_with('{') || s.trim_start().starts_with('[') => {
+                    serde_json::from_str(&s).unwrap_or(serde_json::Value::String(s))
+                }
+                other => other,
+            };
+            (key, value)
+        }).collect()),
+        serde_json::Value::Null => serde_json::json!({}),
+        other => other,
+    }
+}
+
+async fn connect(config: &McpServerConfig, workspace: &Path) -> Result<(McpClient, Vec<McpTool>, Vec<McpResource>), String> {
+    let client = if let Some(url) = &config.url {
+        McpClient::connect_http(url, &config.headers)
+    } else if let Some(command) = &config.command {
+        McpClient::connect_stdio(command, &config.args, &config.env, Some(workspace)).await?
+    } else {
+        return Err("Server needs either a command or a url".to_string());
+    };
+
+    let info = client.initialize().await?;
+    let capabilities = info.get("capabilities").cloned().unwrap_or_default();
+
+    let tools = if capabilities.get("tools").is_some() {
+        client.list_tools().await?
+    } else {
+        Vec::new()
+    };
+    // Resources are optional; a failing listing should not take the server down
+    let resources = if capabilities.get("resources").is_some() {
+        client.list_resources().await.unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    Ok((client, tools, resources))
+}
+
+/// Owns the MCP server connections configured for the current workspace.
+pub struct McpManager {
+    servers: RwLock<HashMap<String, McpServer>>,
+    workspace: std::sync::Mutex<Option<PathBuf>>,
+    /// Bumped on every workspace switch, so a load that finishes after a
+    /// later switch drops its connections instead of installing them.
+    generation: AtomicU64,
+}
+
+impl McpManager {
+    pub fn new() -> Self {
+        Self {
+            servers: RwLock::new(HashMap::new()),
+            workspace: std::sync::Mutex::new(None),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    fn workspace(&self) -> Result<PathBuf, String> {
+        self.workspace.lock().unwrap().clone().ok_or_else(|| "No workspace open".to_string())
+    }
+
+    /// Drops existing connections and connects every enabled server from the workspace config.
+    pub async fn load_workspace(&self, workspace: &Path) -> Result<Vec<McpServerStatus>, String> {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        *self.workspace.lock().unwrap() = Some(workspace.to_path_buf());
+        self.servers.write().await.clear();
+
+        let config = read_config(workspace)?;
+        let connections = config.mcp_servers.into_iter().map(|(name, config)| async move {
+            let server = if config.enabled {
+                match connect(&config, workspace).await {
+                    Ok((client, tools, resources)) => McpServer {
+                        config,
+                        client: Some(Arc::new(client)),
+                        error: None,
+                        tools,
+                        resources,
+                    },
+                    Err(e) => McpServer { config, client: None, error: Some(e), tools: Vec::new(), resources: Vec::new() },
+                }
+            } else {
+                McpServer { config, client: None, error: None, tools: Vec::new(), resources: Vec::new() }
+            };
+            (name, server)
+        });
+        let connected = futures_util::future::join_all(connections).await;
+
+        let mut servers = self.servers.write().await;
+        // Checked under the lock: a switch bumps the generation before it clears
+        if self.generation.load(Ordering::SeqCst) != generation {
+            return Err(format!("{} is no longer the open workspace", workspace.display()));
+        }
+        servers.extend(connected);
+        Ok(servers.iter().map(|(name, s)| s.status(name)).collect())
+    }
+
+    pub async fn clear(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *self.workspace.lock().unwrap() = None;
+        self.servers.write().await.clear();
+    }
+
+    pub async fn statuses(&self) -> Vec<McpServerStatus> {
+        let servers = self.servers.read().await;
+        let mut statuses: Vec<McpServerStatus> = servers.iter().map(|(name, s)| s.status(name)).collect();
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+
+    /// Updates a server entry in `.cognitive/mcp.json`, preserving the rest of the file.
+    fn update_config<F>(&self, name: &str, update: F) -> Result<McpServerConfig, String>
+    where
+        F: FnOnce(&mut McpServerConfig),
+    {
+        let workspace = self.workspace()?;
+        let mut file = read_config(&workspace)?;
+        let entry = file.mcp_servers.get_mut(name).ok_or_else(|| format!("Unknown MCP server: {}", name))?;
+        update(entry);
+        let updated = entry.clone();
+
+        let path = config_path(&workspace);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let content = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        fs::write(&path, content).map_err(|e| format!("Failed to write MCP config: {}", e))?;
+        Ok(updated)
+    }
+
+    pub async fn set_enabled(&self, name: &str, enabled: bool) -> Result<McpServerStatus, String> {
+        let config = self.update_config(name, |c| c.enabled = enabled)?;
+        let workspace = self.workspace()?;
+
+        let server = if enabled {
+            match connect(&config, &workspace).await {
+                Ok((client, tools, resources)) => McpServer { config, client: Some(Arc::new(client)), error: None, tools, resources },
+                Err(e) => McpServer { config, client: None, error: Some(e), tools: Vec::new(), resources: Vec::new() },
+            }
+        } else {
+            McpServer { config, client: None, error: None, tools: Vec::new(), resources: Vec::new() }
+        };
+
+        let status = server.status(name);
+        self.servers.write().await.insert(name.to_string(), server);
+        Ok(status)
+    }
+
+    pub async fn set_approval(&self, name: &str, approval: ApprovalMode) -> Result<McpServerStatus, String> {
+        let config = self.update_config(name, |c| c.approval = approval)?;
+        let mut servers = self.servers.write().await;
+        let server = servers.get_mut(name).ok_or_else(|| format!("Unknown MCP server: {}", name))?;
+        server.config = config;
+        Ok(server.status(name))
+    }
+
+    /// Tools exposed to the agent, keyed by their qualified name.
+    pub async fn agent_tools(&self) -> Vec<(String, McpTool)> {
+        let servers = self.servers.read().await;
+        let mut tools: Vec<(String, McpTool)> = servers.iter()
+            .filter(|(_, s)| s.config.enabled && s.client.is_some() && s.config.approval != ApprovalMode::Deny)
+            .flat_map(|(name, s)| {
+                s.tools.iter()
+                    .filter(|t| !s.config.disabled_tools.contains(&t.name))
+                    .map(move |t| (qualified_tool_name(name, &t.name), t.clone()))
+            })
+            .collect();
+        tools.sort_by(|a, b| a.0.cmp(&b.0));
+        tools
+    }
+
+    /// Resources the agent may read, with the server they come from.
+    pub async fn agent_resources(&self) -> Vec<(String, McpResource)> {
+        let servers = self.servers.read().await;
+        let mut resources: Vec<(String, McpResource)> = servers.iter()
+            .filter(|(_, s)| s.config.enabled && s.client.is_some() && s.config.approval != ApprovalMode::Deny)
+            .flat_map(|(name, s)| s.resources.iter().map(move |r| (name.clone(), r.clone())))
+            .collect();
+        resources.sort_by(|a, b| (&a.0, &a.1.uri).cmp(&(&b.0, &b.1.uri)));
+        resources
+    }
+
+    /// Tool descriptions for the system prompt, one per line.
+    pub async fn prompt_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.agent_tools().await.into_iter().map(|(name, tool)| {
+            let params = tool.input_schema.get("properties")
+                .and_then(|p| p.as_object())
+                .map(|props| props.keys().cloned().collect::<Vec<_>>().join(", "))
+                .unwrap_or_default();
+            format!(
+                "- {}: {} Parameters: {}",
+                name,
+                tool.description.as_deref().unwrap_or("").trim(),
+                if params.is_empty() { "none".to_string() } else { params }
+            )
+        }).collect();
+
+        let resources = self.agent_resources().await;
+        if !resources.is_empty() {
+            lines.push(format!("- {}: Read a resource from an MCP server. Parameters: server, uri. Available resources:", READ_RESOURCE_TOOL));
+            lines.extend(resources.into_iter().map(|(server, resource)| {
+                let description = resource.description.as_deref().map(|d| format!(" - {}", d.trim())).unwrap_or_default();
+                format!("  - server=\"{}\" uri=\"{}\" ({}){}", server, resource.uri, resource.name, description)
+            }));
+        }
+        lines
+    }
+
+    /// Approval mode for a qualified tool name, or `None` if it is not an MCP tool.
+    pub async fn approval_for(&self, qualified: &str) -> Option<ApprovalMode> {
+        let (server, tool) = split_tool_name(qualified)?;
+        let servers = self.servers.read().await;
+        let server = servers.get(server)?;
+        if server.config.auto_approve.iter().any(|t| t == tool) && server.config.approval != ApprovalMode::Deny {
+            return Some(ApprovalMode::Auto);
+        }
+        Some(server.config.approval)
+    }
+
+    /// Approval mode for reading resources from `server`.
+    pub async fn resource_approval(&self, server: &str) -> Option<ApprovalMode> {
+        self.servers.read().await.get(server).map(|s| s.config.approval)
+    }
+
+    fn client_for(servers: &HashMap<String, McpServer>, name: &str) -> Result<Arc<McpClient>, String> {
+        let server = servers.get(name).ok_or_else(|| format!("Unknown MCP server: {}", name))?;
+        if !server.config.enabled {
+            return Err(format!("MCP server '{}' is disabled", name));
+        }
+        server.client.clone().ok_or_else(|| {
+            format!("MCP server '{}' is not connected: {}", name, server.error.as_deref().unwrap_or("unknown error"))
+        })
+    }
+
+    pub async fn call_tool(&self, qualified: &str, arguments: serde_json::Value) -> Result<String, String> {
+        let (server, tool) = split_tool_name(qualified).ok_or_else(|| format!("Not an MCP tool: {}", qualified))?;
+        let client = {
+            let servers = self.servers.read().await;
+            if servers.get(server).map(|s| s.config.disabled_tools.iter().any(|t| t == tool)).unwrap_or(false) {
+                return Err(format!("Tool '{}' is disabled for MCP server '{}'", tool, server));
+            }
+            Self::client_for(&servers, server)?
+        };
+        client.call_tool(tool, normalize_arguments(arguments)).await
+    }
+
+    pub async fn read_resource(&self, server: &str, uri: &str) -> Result<String, String> {
+        let client = {
+            let servers = self.servers.read().await;
+            Self::client_for(&servers, server)?
+        };
+        client.read_resource(uri).await
+    }
+}
+
+impl Default for McpManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_name_roundtrip() {
+        let name = qualified_tool_name("jira", "search_issues");
+        assert_eq!(name, "mcp__jira__search_issues");
+        assert_eq!(split_tool_name(&name), Some(("jira", "search_issues")));
+        assert_eq!(split_tool_name("read_file"), None);
+    }
+
+    #[test]
+    fn test_normalize_arguments_decodes_json_strings() {
+        let args = serde_json::json!({ "filter": "{\"status\": \"open\"}", "limit": 5, "query": "bug" });
+        let normalized = normalize_arguments(args);
+        assert_eq!(normalized["filter"]["status"], "open");
+        assert_eq!(normalized["limit"], 5);
+        assert_eq!(normalized["query"], "bug");
+        assert_eq!(normalize_arguments(serde_json::Value::Null), serde_json::json!({}));
+    }
+
+    #[tokio::test]
+    async fn a_superseded_load_does_not_install_its_servers() {
+        // Holds every connection for a while before closing it, so the first load is still connecting
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+                    drop(socket);
+                });
+            }
+        });
+
+        let root = std::env::temp_dir().join(format!("cognitive-mcp-{}", uuid::Uuid::new_v4()));
+        let (slow, other) = (root.join("slow"), root.join("other"));
+        fs::create_dir_all(slow.join(".cognitive")).unwrap();
+        fs::create_dir_all(&other).unwrap();
+        let config = serde_json::json!({ "mcpServers": { "slow": { "url": format!("http://{}/mcp", addr) } } });
+        fs::write(config_path(&slow), config.to_string()).unwrap();
+
+        let manager = Arc::new(McpManager::new());
+        let first = {
+            let manager = manager.clone();
+            let slow = slow.clone();
+            tokio::spawn(async move { manager.load_workspace(&slow).await })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        manager.load_workspace(&other).await.unwrap();
+
+        assert!(first.await.unwrap().is_err());
+        assert!(manager.statuses().await.is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,8 @@
+pub mod client;
+pub mod manager;
+pub mod server;
+pub mod types;
+mod commands;
+
+pub use commands::*;
+pub use manager::McpManager;
@@ -0,0 +1,345 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use crate::agent::rag::RagEngine;
+use crate::agent::tools::{ToolCall, ToolExecutor};
+use crate::mcp::client::PROTOCOL_VERSION;
+
+/// Serves Cognitive's workspace tools to external MCP clients.
+///
+/// Everything is read-only unless `allow_writes` is set, which adds `write_file`.
+pub struct McpToolServer {
+    workspace: PathBuf,
+    rag_engine: Arc<RagEngine>,
+    allow_writes: bool,
+}
+
+fn tool(name: &str, description: &str, properties: Value, required: &[&str]) -> Value {
+    json!({
+        "name": name,
+        "description": description,
+        "inputSchema": {
+            "type": "object",
+            "properties": properties,
+            "required": required
+        }
+    })
+}
+
+/// Compares in time independent of where the inputs differ.
+fn tokens_match(presented: &str, expected: &str) -> bool {
+    presented.len() == expected.len()
+        && presented.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn error_response(id: Value, code: i32, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+impl McpToolServer {
+    pub fn new(workspace: PathBuf, rag_engine: Arc<RagEngine>, allow_writes: bool) -> Self {
+        Self { workspace, rag_engine, allow_writes }
+    }
+
+    fn tool_definitions(&self) -> Vec<Value> {
+        let mut tools = vec![
+            tool(
+                "search_codebase",
+                "Search indexed symbols (functions, classes, types) in the workspace. Add \"in <path>\" to filter by path.",
+                json!({ "query": { "type": "string" } }),
+                &["query"],
+            ),
+            tool(
+                "grep",
+                "Search file contents in the workspace.",
+                json!({
+                    "query": { "type": "string" },
+                    "path": { "type": "string", "description": "Subdirectory relative to the workspace root" },
+                    "regex": { "type": "boolean" },
+                    "caseSensitive": { "type": "boolean" },
+                    "includePattern": { "type": "string" },
+                    "excludePattern": { "type": "string" }
+                }),
+                &["query"],
+            ),
+            tool(
+                "read_file",
+                "Read a file relative to the workspace root, optionally a 1-based inclusive line range.",
+                json!({
+                    "path": { "type": "string" },
+                    "start_line": { "type": "integer" },
+                    "end_line": { "type": "integer" }
+                }),
+                &["path"],
+            ),
+            tool(
+                "outline",
+                "List the symbols declared in a JavaScript or TypeScript file.",
+                json!({ "path": { "type": "string" } }),
+                &["path"],
+            ),
+            tool(
+                "git_status",
+                "List changed, staged and untracked files in the workspace repository.",
+                json!({}),
+                &[],
+            ),
+        ];
+        if self.allow_writes {
+            tools.push(tool(
+                "write_file",
+                "Write a file relative to the workspace root.",
+                json!({ "path": { "type": "string" }, "content": { "type": "string" } }),
+                &["path", "content"],
+            ));
+        }
+        tools
+    }
+
+    /// Resolves a client-supplied path, rejecting anything outside the workspace,
+    /// including through symlinks. The path itself need not exist yet.
+    fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let relative = Path::new(path);
+        if relative.is_absolute() || relative.components().any(|c| matches!(c, Component::ParentDir | Component::Prefix(_))) {
+            return Err(format!("Path must be relative to the workspace: {}", path));
+        }
+
+        let full = self.workspace.join(relative);
+        let root = self.workspace.canonicalize().map_err(|e| format!("Failed to resolve workspace: {}", e))?;
+        // symlink_metadata so a dangling link is checked rather than skipped
+        let existing = full.ancestors()
+            .find(|p| p.symlink_metadata().is_ok())
+            .and_then(|p| p.canonicalize().ok());
+        match existing {
+            Some(existing) if existing.starts_with(&root) => Ok(full),
+            _ => Err(format!("Path is outside the workspace: {}", path)),
+        }
+    }
+
+    async fn call_tool(&self, name: &str, args: Value) -> Result<String, String> {
+        let path_arg = |key: &str| -> Result<Option<PathBuf>, String> {
+            match args.get(key).and_then(|v| v.as_str()) {
+                Some(p) => self.resolve(p).map(Some),
+                None => Ok(None),
+            }
+        };
+
+        match name {
+            "search_codebase" | "grep" | "read_file" => {
+                // Validate paths before handing them to the agent executor
+                let mut params = args.clone();
+                if let Some(path) = path_arg("path")? {
+                    params["path"] = json!(path.to_string_lossy());
+                }
+                let executor = ToolExecutor::new(Some(self.workspace.clone()), self.rag_engine.clone());
+                executor.execute(ToolCall { name: name.to_string(), parameters: params })
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            "outline" => {
+                let path = path_arg("path")?.ok_or("Missing path parameter")?;
+                let symbols = crate::outline::get_outline(path.to_string_lossy().to_string())?;
+                serde_json::to_string(&symbols).map_err(|e| e.to_string())
+            }
+            "git_status" => {
+                let statuses = crate::git::git_status(self.workspace.to_string_lossy().to_string())?;
+                let changed: Vec<_> = statuses.into_iter().filter(|s| s.status != "ignored").collect();
+                serde_json::to_string(&changed).map_err(|e| e.to_string())
+            }
+            "write_file" if self.allow_writes => {
+                let path = path_arg("path")?.ok_or("Missing path parameter")?;
+                let content = args.get("content").and_then(|v| v.as_str()).ok_or("Missing content parameter")?;
+                crate::fs::write_file(path.to_string_lossy().to_string(), content.to_string())?;
+                Ok("File written successfully".to_string())
+            }
+            _ => Err(format!("Unknown tool: {}", name)),
+        }
+    }
+
+    /// Handles one JSON-RPC message; notifications produce no response.
+    pub async fn handle_message(&self, msg: Value) -> Option<Value> {
+        let id = msg.get("id").cloned()?;
+        let method = match msg.get("method").and_then(|m| m.as_str()) {
+            Some(method) => method,
+            // A response to something we never send; ignore it
+            None => return None,
+        };
+        let params = msg.get("params").cloned().unwrap_or_else(|| json!({}));
+
+        let result = match method {
+            "initialize" => json!({
+                "protocolVersion": params.get("protocolVersion").and_then(|v| v.as_str()).unwrap_or(PROTOCOL_VERSION),
+                "capabilities": { "tools": { "listChanged": false } },
+                "serverInfo": { "name": "cognitive", "version": env!("CARGO_PKG_VERSION") }
+            }),
+            "ping" => json!({}),
+            "tools/list" => json!({ "tools": self.tool_definitions() }),
+            "tools/call" => {
+                let name = params.get("name").and_then(|n| n.as_str()).unwrap_or("");
+                let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+                match self.call_tool(name, args).await {
+                    Ok(text) => json!({ "content": [{ "type": "text", "text": text }], "isError": false }),
+                    Err(e) => json!({ "content": [{ "type": "text", "text": e }], "isError": true }),
+                }
+            }
+            _ => return Some(error_response(id, -32601, &format!("Method not found: {}", method))),
+        };
+
+        Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    /// Like `serve`, for connections that must first send `Bearer <token>` on
+    /// a line of its own. Anything else closes the connection.
+    pub async fn serve_with_token<R, W>(&self, mut reader: R, mut writer: W, token: &str) -> std::io::Result<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut line = String::new();
+        reader.read_line(&mut line).await?;
+        let presented = line.trim().strip_prefix("Bearer ").unwrap_or_default();
+        if !tokens_match(presented, token) {
+            let mut out = error_response(Value::Null, -32001, "Unauthorized").to_string();
+            out.push('\n');
+            writer.write_all(out.as_bytes()).await?;
+            return writer.flush().await;
+        }
+        self.serve(reader, writer).await
+    }
+
+    /// Serves newline-delimited JSON-RPC until the reader is closed.
+    pub async fn serve<R, W>(&self, reader: R, mut writer: W) -> std::io::Result<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut lines = reader.lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(msg) => self.handle_message(msg).await,
+                Err(e) => Some(error_response(Value::Null, -32700, &format!("Parse error: {}", e))),
+            };
+            if let Some(response) = response {
+                let mut out = response.to_string();
+                out.push('\n');
+                writer.write_all(out.as_bytes()).await?;
+                writer.flush().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Entry point for `cognitive --mcp-server`: serves the workspace over stdio.
+pub fn run_stdio(workspace: PathBuf, allow_writes: bool) -> Result<(), String> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    runtime.block_on(async move {
+        let rag_engine = Arc::new(RagEngine::new());
+        let index_engine = rag_engine.clone();
+        let index_path = workspace.clone();
+        tokio::spawn(async move {
+            let _ = index_engine.load_or_index(&index_path).await;
+        });
+
+        let server = McpToolServer::new(workspace, rag_engine, allow_writes);
+        server.serve(BufReader::new(tokio::io::stdin()), tokio::io::stdout())
+            .await
+            .map_err(|e| e.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_workspace() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cognitive-mcp-server-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        dir
+    }
+
+    fn server(workspace: &Path, allow_writes: bool) -> McpToolServer {
+        McpToolServer::new(workspace.to_path_buf(), Arc::new(RagEngine::new()), allow_writes)
+    }
+
+    #[test]
+    fn resolve_rejects_paths_outside_the_workspace() {
+        let workspace = scratch_workspace();
+        let outside = scratch_workspace();
+        let server = server(&workspace, false);
+
+        assert_eq!(server.resolve("src/main.rs").unwrap(), workspace.join("src/main.rs"));
+        assert_eq!(server.resolve("src/new/file.rs").unwrap(), workspace.join("src/new/file.rs"));
+        assert!(server.resolve("../secret").is_err());
+        assert!(server.resolve("src/../../secret").is_err());
+        assert!(server.resolve(&outside.join("src/main.rs").to_string_lossy()).is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&outside, workspace.join("escape")).unwrap();
+            std::os::unix::fs::symlink(outside.join("missing"), workspace.join("dangling")).unwrap();
+            std::os::unix::fs::symlink(workspace.join("src"), workspace.join("inside")).unwrap();
+            assert!(server.resolve("escape/src/main.rs").is_err());
+            assert!(server.resolve("escape/new.rs").is_err());
+            assert!(server.resolve("dangling").is_err());
+            assert!(server.resolve("inside/main.rs").is_ok());
+        }
+
+        let _ = std::fs::remove_dir_all(&workspace);
+        let _ = std::fs::remove_dir_all(&outside);
+    }
+
+    #[tokio::test]
+    async fn handles_initialize_list_and_rejected_writes() {
+        let workspace = scratch_workspace();
+        let server = server(&workspace, false);
+
+        let response = server.handle_message(json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2024-11-05" }
+        })).await.unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+        assert_eq!(response["result"]["serverInfo"]["name"], "cognitive");
+        assert!(server.handle_message(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await.is_none());
+
+        let response = server.handle_message(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" })).await.unwrap();
+        let names: Vec<&str> = response["result"]["tools"].as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["search_codebase", "grep", "read_file", "outline", "git_status"]);
+
+        let response = server.handle_message(json!({
+            "jsonrpc": "2.0", "id": 3, "method": "tools/call",
+            "params": { "name": "write_file", "arguments": { "path": "src/main.rs", "content": "pwned" } }
+        })).await.unwrap();
+        assert_eq!(response["result"]["isError"], true);
+        assert_eq!(std::fs::read_to_string(workspace.join("src/main.rs")).unwrap(), "fn main() {}\n");
+
+        let response = server.handle_message(json!({ "jsonrpc": "2.0", "id": 4, "method": "resources/list" })).await.unwrap();
+        assert_eq!(response["error"]["code"], -32601);
+
+        let _ = std::fs::remove_dir_all(&workspace);
+    }
+
+    #[tokio::test]
+    async fn requires_the_token_before_serving() {
+        let workspace = scratch_workspace();
+        let server = server(&workspace, true);
+        let ping = "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}\n";
+
+        let mut out = Vec::new();
+        server.serve_with_token(format!("Bearer secret\n{}", ping).as_bytes(), &mut out, "secret").await.unwrap();
+        let response: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(response, json!({ "jsonrpc": "2.0", "id": 1, "result": {} }));
+
+        let mut out = Vec::new();
+        server.serve_with_token(format!("Bearer wrong\n{}", ping).as_bytes(), &mut out, "secret").await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Unauthorized") && !out.contains("\"result\""));
+
+        let _ = std::fs::remove_dir_all(&workspace);
+    }
+}
@@ -3,6 +3,9 @@ use std::collections::HashMap;
 use std::path::Path;
 use std::process::Command;
 use std::time::Instant;
+use crate::problems::store::{lsp_source, normalize, DiagnosticsStore, REVIEW_SOURCE, TSC_SOURCE};
+
+pub mod store;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Problem {
@@ -46,32 +49,19 @@ pub async fn get_problems(project_path: String) -> Result<ProblemsResult, String
         return Err(format!("Project path does not exist: {}", project_path));
     }
 
-    // Проверяем кеш
-    let cache_key = project_path.clone();
-    let cache = PROBLEMS_CACHE.lock().unwrap();
-    
-    // Проверяем, есть ли кешированный результат (кеш живет 30 секунд)
-    if let Some(cached_result) = cache.get(&cache_key) {
-        // Возвращаем кешированный результат с обновленной статистикой
-        let elapsed = start_time.elapsed().as_millis() as u64;
-        return Ok(ProblemsResult {
-            files: cached_result.files.clone(),
-            total_errors: cached_result.total_errors,
-            total_warnings: cached_result.total_warnings,
-            scan_time_ms: elapsed,
-            cache_hits: 1,
-            cache_misses: 0,
-        });
+    // CLI checkers are slow: they run once per project until the cache is invalidated.
+    // Language server and AI review problems are kept up to date in the store.
+    let root = normalize(&project_path);
+    let cached = CHECKED_PROJECTS.lock().unwrap().contains(&root);
+    if !cached {
+        let mut id_counter: u32 = 1;
+        if let Ok(ts_problems) = get_typescript_problems(&project_path, &mut id_counter) {
+            DIAGNOSTICS.lock().unwrap().set_project(&root, TSC_SOURCE, ts_problems);
+        }
+        CHECKED_PROJECTS.lock().unwrap().insert(root.clone());
     }
-    drop(cache); // Освобождаем lock перед долгой операцией
 
-    let mut all_problems: Vec<Problem> = Vec::new();
-    let mut id_counter: u32 = 1;
-
-    // Собираем проблемы TypeScript (это медленная операция)
-    if let Ok(ts_problems) = get_typescript_problems(&project_path, &mut id_counter) {
-        all_problems.extend(ts_problems);
-    }
+    let all_problems = DIAGNOSTICS.lock().unwrap().problems(&root);
 
     // Группируем проблемы по файлам
     let mut files_map: HashMap<String, Vec<Problem>> = HashMap::new();
@@ -110,20 +100,14 @@ pub async fn get_problems(project_path: String) -> Result<ProblemsResult, String
     
     let elapsed = start_time.elapsed().as_millis() as u64;
 
-    let result = ProblemsResult {
+    Ok(ProblemsResult {
         files,
         total_errors,
         total_warnings,
         scan_time_ms: elapsed,
-        cache_hits: 0,
-        cache_misses: 1,
-    };
-
-    // Сохраняем в кеш
-    let mut cache = PROBLEMS_CACHE.lock().unwrap();
-    cache.insert(cache_key, result.clone());
-    
-    Ok(result)
+        cache_hits: cached as u32,
+        cache_misses: !cached as u32,
+    })
 }
 
 fn get_typescript_problems(project_path: &str, id_counter: &mut u32) -> Result<Vec<Problem>, String> {
@@ -263,30 +247,82 @@ fn normalize_path(file_path: &str, project_path: &str) -> String {
 
 
 use std::sync::Mutex;
-use std::collections::HashMap as StdHashMap;
+use std::collections::HashSet;
 
 lazy_static::lazy_static! {
-    static ref PROBLEMS_CACHE: Mutex<StdHashMap<String, ProblemsResult>> = Mutex::new(StdHashMap::new());
+    static ref DIAGNOSTICS: Mutex<DiagnosticsStore> = Mutex::new(DiagnosticsStore::default());
+    /// Projects whose CLI checker results in `DIAGNOSTICS` are current.
+    static ref CHECKED_PROJECTS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// Replaces the AI review findings shown for `project_path`.
+pub fn set_review_problems(project_path: &str, problems: Vec<Problem>) {
+    DIAGNOSTICS.lock().unwrap().set_project(project_path, REVIEW_SOURCE, problems);
+}
+
+/// Records a language server's diagnostics for one file, replacing what it reported before.
+pub fn record_lsp_diagnostics(server_id: &str, params: &lsp_types::PublishDiagnosticsParams) {
+    let Ok(path) = crate::lsp::file_uri_to_path(params.uri.as_str()) else {
+        return;
+    };
+    let path = path.to_string_lossy().to_string();
+    let file = Path::new(&path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.clone());
+
+    let problems = params.diagnostics.iter()
+        .map(|d| Problem {
+            id: 0,
+            problem_type: match d.severity {
+                Some(lsp_types::DiagnosticSeverity::WARNING) => "warning",
+                Some(lsp_types::DiagnosticSeverity::INFORMATION) => "info",
+                Some(lsp_types::DiagnosticSeverity::HINT) => "hint",
+                _ => "error",
+            }.to_string(),
+            file: file.clone(),
+            path: path.clone(),
+            line: d.range.start.line + 1,
+            column: d.range.start.character + 1,
+            message: d.message.clone(),
+            code: d.code.as_ref().map(|code| match code {
+                lsp_types::NumberOrString::Number(n) => n.to_string(),
+                lsp_types::NumberOrString::String(s) => s.clone(),
+            }),
+            source: d.source.clone().unwrap_or_else(|| server_id.to_string()),
+        })
+        .collect();
+    DIAGNOSTICS.lock().unwrap().set_file(&path, &lsp_source(server_id), problems);
+}
+
+/// Forgets what a language server reported under `root`, once it has stopped.
+pub fn clear_lsp_diagnostics(server_id: &str, root: &Path) {
+    DIAGNOSTICS.lock().unwrap().clear_source(&root.to_string_lossy(), &lsp_source(server_id));
+}
+
+#[tauri::command]
+pub fn clear_review_problems(project_path: String) -> Result<(), String> {
+    set_review_problems(&project_path, Vec::new());
+    Ok(())
 }
 
 #[tauri::command]
 pub fn clear_problems_cache() -> Result<(), String> {
-    PROBLEMS_CACHE.lock().unwrap().clear();
+    CHECKED_PROJECTS.lock().unwrap().clear();
     Ok(())
 }
 
 #[tauri::command]
 pub fn invalidate_problems_cache(project_path: String) -> Result<(), String> {
-    let mut cache = PROBLEMS_CACHE.lock().unwrap();
-    cache.remove(&project_path);
+    CHECKED_PROJECTS.lock().unwrap().remove(&normalize(&project_path));
     Ok(())
 }
 
 #[tauri::command]
 pub fn get_problems_cache_stats() -> Result<serde_json::Value, String> {
-    let cache = PROBLEMS_CACHE.lock().unwrap();
+    let checked = CHECKED_PROJECTS.lock().unwrap();
     Ok(serde_json::json!({
-        "entries": cache.len()
+        "entries": checked.len()
     }))
 }
 
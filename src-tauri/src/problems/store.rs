@@ -0,0 +1,184 @@
+//! Problems from every source, by file.
+//!
+//! Each source replaces only its own entries: a language server (`lsp:<id>`)
+//! per published file, CLI checkers (`tsc`) and AI review (`ai-review`) per
+//! project. Reading merges them, and a finding reported by several sources is
+//! listed once, from the most precise one.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use crate::problems::Problem;
+
+pub const TSC_SOURCE: &str = "tsc";
+pub const REVIEW_SOURCE: &str = "ai-review";
+
+pub fn lsp_source(server_id: &str) -> String {
+    format!("lsp:{}", server_id)
+}
+
+/// Language servers see unsaved edits, so they win over checkers run on disk;
+/// AI review findings come last.
+fn source_rank(source: &str) -> u8 {
+    if source.starts_with("lsp:") {
+        0
+    } else if source == REVIEW_SOURCE {
+        2
+    } else {
+        1
+    }
+}
+
+/// Forward slashes, no trailing slash and an upper-case drive letter, so paths
+/// from all sources compare equal.
+pub fn normalize(path: &str) -> String {
+    let mut path = path.replace('\\', "/");
+    // `file:` URIs spell the drive `c:`, Windows paths `C:`
+    if path.as_bytes().get(1) == Some(&b':') {
+        path[..1].make_ascii_uppercase();
+    }
+    match path.trim_end_matches('/') {
+        "" => path,
+        trimmed => trimmed.to_string(),
+    }
+}
+
+fn is_absolute(path: &str) -> bool {
+    Path::new(path).is_absolute() || path.as_bytes().get(1) == Some(&b':')
+}
+
+/// Same finding from different tools: position, code without a `TS` prefix,
+/// and the first line of the message (tsc prints only that).
+fn dedup_key(problem: &Problem) -> (u32, u32, Option<String>, String) {
+    (
+        problem.line,
+        problem.column,
+        problem.code.as_ref().map(|code| code.trim_start_matches("TS").to_string()),
+        problem.message.lines().next().unwrap_or_default().trim().to_string(),
+    )
+}
+
+#[derive(Default)]
+pub struct DiagnosticsStore {
+    /// Absolute path → source → problems.
+    files: BTreeMap<String, HashMap<String, Vec<Problem>>>,
+}
+
+impl DiagnosticsStore {
+    /// Replaces what `source` reports for the file at absolute `path`.
+    pub fn set_file(&mut self, path: &str, source: &str, problems: Vec<Problem>) {
+        let path = normalize(path);
+        if problems.is_empty() {
+            if let Some(sources) = self.files.get_mut(&path) {
+                sources.remove(source);
+                if sources.is_empty() {
+                    self.files.remove(&path);
+                }
+            }
+        } else {
+            self.files.entry(path).or_default().insert(source.to_string(), problems);
+        }
+    }
+
+    /// Replaces what `source` reports anywhere under `root`. Relative problem
+    /// paths are resolved against `root`.
+    pub fn set_project(&mut self, root: &str, source: &str, problems: Vec<Problem>) {
+        self.clear_source(root, source);
+        let root = normalize(root);
+        let mut by_file: HashMap<String, Vec<Problem>> = HashMap::new();
+        for problem in problems {
+            let path = normalize(&problem.path);
+            let path = if is_absolute(&path) { path } else { format!("{}/{}", root, path) };
+            by_file.entry(path).or_default().push(problem);
+        }
+        for (path, problems) in by_file {
+            self.set_file(&path, source, problems);
+        }
+    }
+
+    /// Drops everything `source` reported under `root`.
+    pub fn clear_source(&mut self, root: &str, source: &str) {
+        let prefix = format!("{}/", normalize(root));
+        let paths: Vec<String> = self.files.keys().filter(|path| path.starts_with(&prefix)).cloned().collect();
+        for path in paths {
+            self.set_file(&path, source, Vec::new());
+        }
+    }
+
+    /// Merged problems under `root`, paths relative to it, numbered from 1.
+    pub fn problems(&self, root: &str) -> Vec<Problem> {
+        let prefix = format!("{}/", normalize(root));
+        let mut result = Vec::new();
+        for (path, sources) in self.files.range(prefix.clone()..).take_while(|(path, _)| path.starts_with(&prefix)) {
+            let relative = &path[prefix.len()..];
+            let mut sources: Vec<(&String, &Vec<Problem>)> = sources.iter().collect();
+            sources.sort_by_key(|(source, _)| (source_rank(source), source.as_str()));
+
+            let mut seen = HashSet::new();
+            let mut kept: Vec<&Problem> = sources.into_iter()
+                .flat_map(|(_, problems)| problems)
+                .filter(|problem| seen.insert(dedup_key(problem)))
+                .collect();
+            kept.sort_by_key(|problem| (problem.line, problem.column));
+            for problem in kept {
+                result.push(Problem {
+                    id: result.len() as u32 + 1,
+                    path: relative.to_string(),
+                    ..problem.clone()
+                });
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problem(path: &str, line: u32, code: Option<&str>, message: &str, source: &str) -> Problem {
+        Problem {
+            id: 0,
+            problem_type: "error".to_string(),
+            file: path.rsplit('/').next().unwrap().to_string(),
+            path: path.to_string(),
+            line,
+            column: 5,
+            message: message.to_string(),
+            code: code.map(str::to_string),
+            source: source.to_string(),
+        }
+    }
+
+    #[test]
+    fn merges_sources_and_drops_duplicates() {
+        let mut store = DiagnosticsStore::default();
+        store.set_project("/repo", TSC_SOURCE, vec![
+            problem("src/a.ts", 3, Some("TS2304"), "Cannot find name 'x'.", "ts"),
+            problem("src/b.ts", 1, Some("TS1005"), "';' expected.", "ts"),
+        ]);
+        store.set_file("/repo/src/a.ts", &lsp_source("typescript-language-server"), vec![
+            problem("/repo/src/a.ts", 3, Some("2304"), "Cannot find name 'x'.\n  Did you mean 'y'?", "typescript"),
+        ]);
+        store.set_project("/repo", REVIEW_SOURCE, vec![problem("src/a.ts", 10, None, "Possible null dereference", "ai-review")]);
+        store.set_file("/other/c.ts", &lsp_source("typescript-language-server"), vec![problem("/other/c.ts", 1, None, "x", "typescript")]);
+
+        let problems = store.problems("/repo");
+        let summary: Vec<(u32, &str, &str)> = problems.iter().map(|p| (p.id, p.path.as_str(), p.source.as_str())).collect();
+        assert_eq!(summary, vec![(1, "src/a.ts", "typescript"), (2, "src/a.ts", "ai-review"), (3, "src/b.ts", "ts")]);
+
+        // A rerun replaces the checker's findings without touching the others
+        store.set_project("/repo", TSC_SOURCE, Vec::new());
+        assert_eq!(store.problems("/repo").len(), 2);
+        store.clear_source("/repo", &lsp_source("typescript-language-server"));
+        assert_eq!(store.problems("/repo").len(), 1);
+        assert_eq!(store.problems("/other").len(), 1);
+    }
+
+    #[test]
+    fn normalizes_separators_and_drive_letters() {
+        assert_eq!(normalize("c:/repo/src/"), "C:/repo/src");
+        assert_eq!(normalize("C:\\repo\\src"), "C:/repo/src");
+        assert_eq!(normalize("/repo/src/"), "/repo/src");
+        assert_eq!(normalize("/"), "/");
+    }
+}
@@ -111,6 +111,11 @@ pub fn settings_update_section(
                 .map_err(|e| format!("Invalid AI settings: {}", e))?;
             store.update_section(&section, ai, source.clone())?;
         }
+        "lsp" => {
+            let lsp: LspSettings = serde_json::from_value(value.clone())
+                .map_err(|e| format!("Invalid LSP settings: {}", e))?;
+            store.update_section(&section, lsp, source.clone())?;
+        }
         "workspace" => {
             let workspace: WorkspaceSettings = serde_json::from_value(value.clone())
                 .map_err(|e| format!("Invalid workspace settings: {}", e))?;
@@ -268,6 +273,7 @@ pub fn settings_reset(
             store.update_section("ui", UISettings::default(), SettingsSource::User)?;
             store.update_section("editor", EditorSettings::default(), SettingsSource::User)?;
             store.update_section("ai", AISettings::default(), SettingsSource::User)?;
+            store.update_section("lsp", LspSettings::default(), SettingsSource::User)?;
         }
         "workspace" => {
             // Clear workspace settings
@@ -278,6 +284,7 @@ pub fn settings_reset(
             store.update_section("ui", UISettings::default(), SettingsSource::User)?;
             store.update_section("editor", EditorSettings::default(), SettingsSource::User)?;
             store.update_section("ai", AISettings::default(), SettingsSource::User)?;
+            store.update_section("lsp", LspSettings::default(), SettingsSource::User)?;
             store.clear_workspace();
         }
         _ => return Err("Invalid target: must be 'user', 'workspace', or 'all'".to_string()),
@@ -39,6 +39,19 @@ impl Default for AISettings {
             active_model_id: String::new(),
             active_mode: "responder".to_string(),
             stream_responses: true,
+            provider_profiles: Vec::new(),
+            web_fetch: WebFetchSettings::default(),
+            agent_git_writes: false,
+        }
+    }
+}
+
+impl Default for WebFetchSettings {
+    fn default() -> Self {
+        Self {
+            allowed_domains: Vec::new(),
+            offline: false,
+            cache_ttl_hours: 24,
         }
     }
 }
@@ -68,6 +81,7 @@ impl Default for AppSettings {
             ui: UISettings::default(),
             editor: EditorSettings::default(),
             ai: AISettings::default(),
+            lsp: LspSettings::default(),
             workspace: None,
         }
     }
@@ -1,5 +1,6 @@
 mod commands;
 mod defaults;
+mod schema;
 mod store;
 mod types;
 mod validation;
@@ -7,3 +8,6 @@ mod watcher;
 
 
 pub use commands::*;
+pub use schema::settings_schema;
+pub use store::SettingsStore;
+pub use types::{LanguageServerSettings, LspSettings, ProviderProfile, WebFetchSettings};
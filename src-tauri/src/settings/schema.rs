@@ -0,0 +1,132 @@
+//! JSON schema for `settings.json`, used by the JSON language server to
+//! validate and complete the user and workspace settings files.
+//!
+//! Ranges and enums mirror `validation.rs`; keep the two in sync.
+
+use serde_json::{json, Value};
+
+fn string_array() -> Value {
+    json!({ "type": "array", "items": { "type": "string" } })
+}
+
+fn ui_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "theme": { "type": "string" },
+            "fontFamily": { "type": "string" },
+            "fontSize": { "type": "integer", "minimum": 8, "maximum": 72 },
+            "lineHeight": { "type": "number", "minimum": 1.0, "maximum": 3.0 },
+            "minimapEnabled": { "type": "boolean" },
+            "lineNumbersEnabled": { "type": "boolean" },
+            "tabSize": { "type": "integer", "minimum": 1, "maximum": 8 },
+            "sidebarWidth": { "type": "integer", "minimum": 150, "maximum": 600 },
+            "aiPanelWidth": { "type": "integer", "minimum": 200, "maximum": 800 },
+            "zoomLevel": { "type": "number", "minimum": 0.5, "maximum": 2.0 }
+        }
+    })
+}
+
+fn editor_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "wordWrap": { "type": "boolean" },
+            "autoSave": { "type": "boolean" },
+            "autoSaveDelay": { "type": "integer", "minimum": 100, "maximum": 60000, "description": "Milliseconds" },
+            "formatOnSave": { "type": "boolean" },
+            "bracketPairColorization": { "type": "boolean" },
+            "indentGuides": { "type": "boolean" },
+            "cursorBlinking": { "enum": ["blink", "smooth", "phase", "expand", "solid"] },
+            "cursorStyle": { "enum": ["line", "block", "underline", "line-thin", "block-outline", "underline-thin"] }
+        }
+    })
+}
+
+fn ai_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "activeModelId": { "type": "string" },
+            "activeMode": { "enum": ["responder", "agent"] },
+            "streamResponses": { "type": "boolean" },
+            "providerProfiles": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["id", "name", "baseUrl"],
+                    "properties": {
+                        "id": { "type": "string", "minLength": 1 },
+                        "name": { "type": "string" },
+                        "baseUrl": { "type": "string", "pattern": "^https?://", "description": "Base URL including the API prefix, e.g. http://localhost:8000/v1" },
+                        "apiKey": { "type": ["string", "null"] },
+                        "apiKeyEnv": { "type": ["string", "null"], "description": "Environment variable holding the API key" },
+                        "headers": { "type": "object", "additionalProperties": { "type": "string" } },
+                        "models": string_array(),
+                        "discoverModels": { "type": "boolean" }
+                    }
+                }
+            },
+            "webFetch": {
+                "type": "object",
+                "properties": {
+                    "allowedDomains": string_array(),
+                    "offline": { "type": "boolean" },
+                    "cacheTtlHours": { "type": "integer", "minimum": 0 }
+                }
+            },
+            "agentGitWrites": { "type": "boolean", "description": "Let the agent stage and commit (each call still needs approval)" }
+        }
+    })
+}
+
+fn lsp_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "servers": {
+                "type": "array",
+                "description": "Additional language servers; an entry with the id of a built-in server replaces it",
+                "items": {
+                    "type": "object",
+                    "required": ["id", "command"],
+                    "properties": {
+                        "id": { "type": "string", "minLength": 1 },
+                        "languages": string_array(),
+                        "filePatterns": string_array(),
+                        "command": { "type": "string", "minLength": 1 },
+                        "args": string_array(),
+                        "env": { "type": "object", "additionalProperties": { "type": "string" } },
+                        "initializationOptions": {},
+                        "settings": { "description": "Returned to the server for workspace/configuration" },
+                        "rootMarkers": string_array()
+                    }
+                }
+            },
+            "disabled": string_array()
+        }
+    })
+}
+
+pub fn settings_schema() -> Value {
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "Cognitive settings",
+        "type": "object",
+        "required": ["ui", "editor", "ai"],
+        "properties": {
+            "ui": ui_schema(),
+            "editor": editor_schema(),
+            "ai": ai_schema(),
+            "lsp": lsp_schema(),
+            "workspace": {
+                "type": ["object", "null"],
+                "properties": {
+                    "excludePatterns": string_array(),
+                    "searchExcludePatterns": string_array(),
+                    "fileAssociations": { "type": "object", "additionalProperties": { "type": "string" } }
+                }
+            }
+        }
+    })
+}
@@ -1,6 +1,7 @@
 use std::fs;
 use std::path::PathBuf;
 use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use super::types::*;
 use super::validation::validate_settings;
@@ -15,6 +16,8 @@ pub struct SettingsStore {
     user_config_path: PathBuf,
     
     workspace_config_path: RwLock<Option<PathBuf>>,
+    /// Bumped whenever the merged settings may have changed.
+    generation: AtomicU64,
 }
 
 impl SettingsStore {
@@ -29,9 +32,20 @@ impl SettingsStore {
             workspace_settings: RwLock::new(None),
             user_config_path: config_dir.join("settings.json"),
             workspace_config_path: RwLock::new(None),
+            generation: AtomicU64::new(0),
         }
     }
 
+    /// Changes whenever the merged settings may have, so state derived from
+    /// them can be cached until then.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    fn changed(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
     
     pub fn get_config_dir(&self) -> PathBuf {
         self.user_config_path.parent()
@@ -73,6 +87,7 @@ impl SettingsStore {
         }
 
         *self.user_settings.write().unwrap() = settings;
+        self.changed();
         Ok(())
     }
 
@@ -114,6 +129,7 @@ impl SettingsStore {
         } else {
             *self.workspace_settings.write().unwrap() = None;
         }
+        self.changed();
 
         Ok(())
     }
@@ -122,6 +138,7 @@ impl SettingsStore {
     pub fn clear_workspace(&self) {
         *self.workspace_config_path.write().unwrap() = None;
         *self.workspace_settings.write().unwrap() = None;
+        self.changed();
     }
 
     
@@ -201,6 +218,7 @@ impl SettingsStore {
             }
             _ => return Err("Invalid settings source".to_string()),
         }
+        self.changed();
 
         Ok(())
     }
@@ -233,6 +251,7 @@ impl SettingsStore {
             }
             _ => return Err("Invalid settings source".to_string()),
         }
+        self.changed();
 
         Ok(())
     }
@@ -257,6 +276,10 @@ impl SettingsStore {
                 settings.ai = serde_json::from_value(value)
                     .map_err(|e| format!("Invalid AI settings: {}", e))?;
             }
+            "lsp" => {
+                settings.lsp = serde_json::from_value(value)
+                    .map_err(|e| format!("Invalid LSP settings: {}", e))?;
+            }
             "workspace" => {
                 settings.workspace = serde_json::from_value(value)
                     .map_err(|e| format!("Invalid workspace settings: {}", e))?;
@@ -308,6 +331,7 @@ impl SettingsStore {
             ui: workspace.ui.clone(),
             editor: workspace.editor.clone(),
             ai: workspace.ai.clone(),
+            lsp: workspace.lsp.clone(),
             workspace: workspace.workspace.clone().or_else(|| user.workspace.clone()),
         }
     }
@@ -328,6 +352,7 @@ impl SettingsStore {
                 *self.workspace_settings.write().unwrap() = Some(settings);
             }
         }
+        self.changed();
 
         Ok(SettingsChangeEvent {
             section: "all".to_string(),
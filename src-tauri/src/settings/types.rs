@@ -37,6 +37,60 @@ pub struct AISettings {
     pub active_model_id: String,
     pub active_mode: String,
     pub stream_responses: bool,
+    #[serde(default)]
+    pub provider_profiles: Vec<ProviderProfile>,
+    #[serde(default)]
+    pub web_fetch: WebFetchSettings,
+    /// Let the agent stage and commit (each call still needs approval).
+    #[serde(default)]
+    pub agent_git_writes: bool,
+}
+
+/// Settings for the agent's `fetch_url` tool.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WebFetchSettings {
+    /// Domains the tool may fetch from, subdomains included. Empty allows any domain.
+    pub allowed_domains: Vec<String>,
+    /// Serve only pages already in the workspace cache.
+    pub offline: bool,
+    /// How long a cached page is served before it is fetched again.
+    pub cache_ttl_hours: u64,
+}
+
+/// A named OpenAI-compatible endpoint (vLLM, LM Studio, llama.cpp server, gateways).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderProfile {
+    pub id: String,
+    pub name: String,
+    /// Base URL including the API prefix, e.g. `http://localhost:8000/v1`.
+    pub base_url: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Environment variable to read the key from, so it stays out of settings files.
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    #[serde(default)]
+    pub headers: std::collections::HashMap<String, String>,
+    /// Models to offer in addition to (or instead of) the discovered ones.
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// Query `GET {baseUrl}/models` for the model list.
+    #[serde(default = "default_true")]
+    pub discover_models: bool,
+}
+
+impl ProviderProfile {
+    pub fn resolved_api_key(&self) -> Option<String> {
+        self.api_key.clone()
+            .filter(|k| !k.is_empty())
+            .or_else(|| self.api_key_env.as_ref().and_then(|var| std::env::var(var).ok()))
+    }
+}
+
+fn default_true() -> bool {
+    true
 }
 
 
@@ -49,6 +103,42 @@ pub struct WorkspaceSettings {
 }
 
 
+/// Language servers, merged with the built-in registry by id.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LspSettings {
+    /// Additional servers; an entry with the id of a built-in server replaces it.
+    pub servers: Vec<LanguageServerSettings>,
+    /// Ids of servers that should never be started.
+    pub disabled: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LanguageServerSettings {
+    pub id: String,
+    /// LSP language ids the server handles, e.g. `rust` or `typescriptreact`.
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Globs matched against the document path; they take precedence over `languages`.
+    #[serde(default)]
+    pub file_patterns: Vec<String>,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: std::collections::HashMap<String, String>,
+    #[serde(default)]
+    pub initialization_options: Option<serde_json::Value>,
+    /// Returned to the server for `workspace/configuration`, keyed by section.
+    #[serde(default)]
+    pub settings: Option<serde_json::Value>,
+    /// Files marking a project root, in priority order, e.g. `Cargo.toml`.
+    #[serde(default)]
+    pub root_markers: Vec<String>,
+}
+
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct AppSettings {
@@ -56,6 +146,8 @@ pub struct AppSettings {
     pub editor: EditorSettings,
     pub ai: AISettings,
     #[serde(default)]
+    pub lsp: LspSettings,
+    #[serde(default)]
     pub workspace: Option<WorkspaceSettings>,
 }
 
@@ -107,6 +107,94 @@ pub fn validate_ai_settings(settings: &AISettings) -> ValidationResult {
         });
     }
 
+    let mut profile_ids = std::collections::HashSet::new();
+    for (i, profile) in settings.provider_profiles.iter().enumerate() {
+        if profile.id.trim().is_empty() {
+            errors.push(ValidationError {
+                path: format!("ai.providerProfiles[{}].id", i),
+                message: "Profile id must not be empty".to_string(),
+            });
+        } else if !profile_ids.insert(profile.id.as_str()) {
+            errors.push(ValidationError {
+                path: format!("ai.providerProfiles[{}].id", i),
+                message: format!("Duplicate profile id: {}", profile.id),
+            });
+        }
+
+        if !profile.base_url.starts_with("http://") && !profile.base_url.starts_with("https://") {
+            errors.push(ValidationError {
+                path: format!("ai.providerProfiles[{}].baseUrl", i),
+                message: "Base URL must start with http:// or https://".to_string(),
+            });
+        }
+
+        for name in profile.headers.keys() {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                errors.push(ValidationError {
+                    path: format!("ai.providerProfiles[{}].headers", i),
+                    message: format!("Invalid header name: {:?}", name),
+                });
+            }
+        }
+    }
+
+    for (i, domain) in settings.web_fetch.allowed_domains.iter().enumerate() {
+        if domain.is_empty() || domain.contains("://") || domain.contains('/') {
+            errors.push(ValidationError {
+                path: format!("ai.webFetch.allowedDomains[{}]", i),
+                message: format!("Expected a bare domain such as docs.rs, got {:?}", domain),
+            });
+        }
+    }
+
+    ValidationResult {
+        valid: errors.is_empty(),
+        errors,
+    }
+}
+
+
+pub fn validate_lsp_settings(settings: &LspSettings) -> ValidationResult {
+    let mut errors = Vec::new();
+
+    let mut server_ids = std::collections::HashSet::new();
+    for (i, server) in settings.servers.iter().enumerate() {
+        if server.id.trim().is_empty() {
+            errors.push(ValidationError {
+                path: format!("lsp.servers[{}].id", i),
+                message: "Server id must not be empty".to_string(),
+            });
+        } else if !server_ids.insert(server.id.as_str()) {
+            errors.push(ValidationError {
+                path: format!("lsp.servers[{}].id", i),
+                message: format!("Duplicate server id: {}", server.id),
+            });
+        }
+
+        if server.command.trim().is_empty() {
+            errors.push(ValidationError {
+                path: format!("lsp.servers[{}].command", i),
+                message: "Command must not be empty".to_string(),
+            });
+        }
+
+        if server.languages.is_empty() && server.file_patterns.is_empty() {
+            errors.push(ValidationError {
+                path: format!("lsp.servers[{}]", i),
+                message: "Server must list languages or file patterns".to_string(),
+            });
+        }
+
+        for pattern in &server.file_patterns {
+            if let Err(e) = globset::Glob::new(pattern) {
+                errors.push(ValidationError {
+                    path: format!("lsp.servers[{}].filePatterns", i),
+                    message: format!("Invalid glob {:?}: {}", pattern, e),
+                });
+            }
+        }
+    }
+
     ValidationResult {
         valid: errors.is_empty(),
         errors,
@@ -126,6 +214,9 @@ pub fn validate_settings(settings: &AppSettings) -> ValidationResult {
     let ai_result = validate_ai_settings(&settings.ai);
     all_errors.extend(ai_result.errors);
 
+    let lsp_result = validate_lsp_settings(&settings.lsp);
+    all_errors.extend(lsp_result.errors);
+
     ValidationResult {
         valid: all_errors.is_empty(),
         errors: all_errors,
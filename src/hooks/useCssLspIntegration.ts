@@ -7,7 +7,7 @@ import {
   cssLspDidChange,
   cssLspDidClose,
   onCssDiagnostics,
-  isCssFile,
+  isWebLanguageFile,
   type CssPublishDiagnosticsParams,
 } from '../lib/tauri/css-lsp';
 
@@ -39,7 +39,10 @@ export function useCssLspIntegration() {
   useEffect(() => {
     const unsubscribe = onCssDiagnostics((params: CssPublishDiagnosticsParams) => {
       // Конвертируем URI обратно в путь
-      const path = params.uri.replace(/^file:\/\/\//, '').replace(/^file:\/\//, '');
+      let path = decodeURIComponent(params.uri.replace(/^file:\/\//, ''));
+      if (/^\/[a-zA-Z]:/.test(path)) {
+        path = path.slice(1);
+      }
       
       // Преобразуем диагностику в формат store
       const diagnostics = params.diagnostics.map((diag, index) => ({
@@ -66,7 +69,7 @@ export function useCssLspIntegration() {
 
   // Отслеживание открытия/изменения файлов
   useEffect(() => {
-    if (!activeFile || !initializedRef.current || !isCssFile(activeFile)) {
+    if (!activeFile || !initializedRef.current || !isWebLanguageFile(activeFile)) {
       return;
     }
 
@@ -97,7 +100,7 @@ export function useCssLspIntegration() {
   // Очистка при размонтировании
   useEffect(() => {
     return () => {
-      if (!activeFile || !initializedRef.current || !isCssFile(activeFile)) {
+      if (!activeFile || !initializedRef.current || !isWebLanguageFile(activeFile)) {
         return;
       }
 
@@ -1,6 +1,7 @@
 import { useEffect, useRef } from 'react';
 import { listenToLspDiagnostics, lspInitialize, lspDidOpen, lspDidChange, type LspDiagnosticsPayload } from '../lib/tauri/lsp';
 import { useDiagnosticsStore, type MonacoDiagnostic } from '../store/diagnosticsStore';
+import { isWebLanguageFile } from '../lib/tauri/css-lsp';
 
 interface UseLspIntegrationProps {
     currentWorkspace: string | null;
@@ -62,6 +63,13 @@ export function useLspIntegration({
                     filePath = filePath.substring(7); // Remove 'file://'
                 }
                 
+                filePath = decodeURIComponent(filePath);
+
+                // CSS/HTML/JSON диагностику обрабатывает useCssLspIntegration
+                if (isWebLanguageFile(filePath)) {
+                    return;
+                }
+
                 // Нормализуем путь (заменяем / на \\ для Windows)
                 filePath = filePath.replace(/\//g, '\\');
                 
@@ -160,9 +168,9 @@ export function useLspIntegration({
     useEffect(() => {
         if (!lspInitializedRef.current || !activeFile || !fileContent) return;
 
-        // Проверяем, поддерживается ли файл LSP (TypeScript/JavaScript/Python)
-        const ext = activeFile.split('.').pop()?.toLowerCase();
-        if (!ext || !['ts', 'tsx', 'js', 'jsx', 'py'].includes(ext)) {
+        // CSS/HTML/JSON открывает useCssLspIntegration; для остальных файлов
+        // сервер выбирает бэкенд, а файлы без сервера он просто пропускает
+        if (isWebLanguageFile(activeFile)) {
             return;
         }
 
@@ -182,8 +190,7 @@ export function useLspIntegration({
         if (!lspInitializedRef.current || !activeFile || !fileContent) return;
         if (!openFilesRef.current.has(activeFile)) return;
 
-        const ext = activeFile.split('.').pop()?.toLowerCase();
-        if (!ext || !['ts', 'tsx', 'js', 'jsx', 'py'].includes(ext)) {
+        if (isWebLanguageFile(activeFile)) {
             return;
         }
 
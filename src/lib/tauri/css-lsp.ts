@@ -50,13 +50,16 @@ export async function cssLspDidClose(path: string): Promise<void> {
 }
 
 /**
- * Подписывается на диагностику от CSS LSP
+ * Подписывается на диагностику CSS/HTML/JSON серверов.
+ * Они работают через общий LSP клиент, поэтому событие общее — фильтруем по типу файла.
  */
 export function onCssDiagnostics(
   callback: (params: CssPublishDiagnosticsParams) => void
 ): () => void {
-  const unlisten = listen<CssPublishDiagnosticsParams>('css-lsp:diagnostics', (event) => {
-    callback(event.payload);
+  const unlisten = listen<CssPublishDiagnosticsParams>('lsp:diagnostics', (event) => {
+    if (isWebLanguageFile(decodeURIComponent(event.payload.uri))) {
+      callback(event.payload);
+    }
   });
 
   return () => {
@@ -71,3 +74,11 @@ export function isCssFile(path: string): boolean {
   const ext = path.toLowerCase().split('.').pop();
   return ext === 'css' || ext === 'scss' || ext === 'less';
 }
+
+/**
+ * Проверяет, обслуживается ли файл серверами vscode-langservers-extracted (CSS/HTML/JSON)
+ */
+export function isWebLanguageFile(path: string): boolean {
+  const ext = path.toLowerCase().split('.').pop();
+  return isCssFile(path) || ext === 'html' || ext === 'htm' || ext === 'json' || ext === 'jsonc';
+}
@@ -2,7 +2,7 @@ import { invoke } from '@tauri-apps/api/core';
 
 export type Problem = {
     id: number;
-    type: 'error' | 'warning';
+    type: 'error' | 'warning' | 'info' | 'hint';
     file: string;
     path: string;
     line: number;
@@ -18,9 +18,95 @@ export interface LspDiagnosticsPayload {
   diagnostics: LspDiagnostic[];
 }
 
-export const lspInitialize = (projectPath: string, serverType?: string) => invoke<void>('lsp_initialize', { projectPath, serverType: serverType || 'typescript' });
+export const lspInitialize = (projectPath: string, serverType?: string) => invoke<void>('lsp_initialize', { projectPath, serverType });
 export const lspDidOpen = (path: string, content: string) => invoke<void>('lsp_did_open', { path, content });
 export const lspDidChange = (path: string, content: string, version: number) => invoke<void>('lsp_did_change', { path, content, version });
+export const lspDidClose = (path: string) => invoke<void>('lsp_did_close', { path });
+export const lspDidSave = (path: string, content?: string) => invoke<void>('lsp_did_save', { path, content });
+export const lspCloseWorkspace = (projectPath: string) => invoke<void>('lsp_close_workspace', { projectPath });
+export const lspRestartServer = (serverId: string) => invoke<void>('lsp_restart_server', { serverId });
+// Results arrive through listenToLspDiagnostics
+export const lspPullWorkspaceDiagnostics = (projectPath: string) => invoke<void>('lsp_pull_workspace_diagnostics', { projectPath });
+
+export type LspServerStatus = 'starting' | 'running' | 'exited' | 'restarting' | 'failed' | 'stopped';
+
+/** Work-done progress from a server, e.g. indexing; `title` is only sent with `begin`. */
+export interface LspProgressEvent {
+  serverId: string;
+  token: string | number;
+  kind: 'begin' | 'report' | 'end';
+  title?: string;
+  message?: string;
+  percentage?: number;
+  cancellable?: boolean;
+}
+
+export interface LspServerStatusEvent {
+  serverId: string;
+  root: string;
+  status: LspServerStatus;
+  message: string | null;
+}
+
+export interface LspServerAvailability {
+  id: string;
+  command: string;
+  languages: string[];
+  /** Where the command resolves on PATH; null when it is not installed. */
+  resolvedPath: string | null;
+}
+
+export const lspListServers = () => invoke<LspServerAvailability[]>('lsp_list_servers');
+export const lspCheckServer = (serverId: string) => invoke<LspServerAvailability>('lsp_check_server', { serverId });
+
+export interface LspPosition {
+  line: number;
+  character: number;
+}
+
+export interface LspRange {
+  start: LspPosition;
+  end: LspPosition;
+}
+
+export interface LspLocation {
+  uri: string;
+  range: LspRange;
+}
+
+export interface LspTextEdit {
+  range: LspRange;
+  newText: string;
+}
+
+export interface LspWorkspaceEdit {
+  changes?: Record<string, LspTextEdit[]>;
+  documentChanges?: unknown[];
+}
+
+// Positions are zero-based lines and UTF-16 character offsets (Monaco column - 1).
+export const lspCompletion = (path: string, line: number, character: number) => invoke<unknown | null>('lsp_completion', { path, line, character });
+export const lspHover = (path: string, line: number, character: number) => invoke<{ contents: unknown; range?: LspRange } | null>('lsp_hover', { path, line, character });
+export const lspSignatureHelp = (path: string, line: number, character: number) => invoke<unknown | null>('lsp_signature_help', { path, line, character });
+export const lspDefinition = (path: string, line: number, character: number) => invoke<unknown | null>('lsp_definition', { path, line, character });
+export const lspTypeDefinition = (path: string, line: number, character: number) => invoke<unknown | null>('lsp_type_definition', { path, line, character });
+export const lspReferences = (path: string, line: number, character: number, includeDeclaration = true) => invoke<LspLocation[] | null>('lsp_references', { path, line, character, includeDeclaration });
+export const lspDocumentHighlight = (path: string, line: number, character: number) => invoke<{ range: LspRange; kind?: number }[] | null>('lsp_document_highlight', { path, line, character });
+export const lspRename = (path: string, line: number, character: number, newName: string) => invoke<LspWorkspaceEdit | null>('lsp_rename', { path, line, character, newName });
+export type LspFileChange =
+  | { kind: 'edited'; path: string; edits: number; snapshotId: string }
+  | { kind: 'created'; path: string; snapshotId: string | null }
+  | { kind: 'renamed'; from: string; to: string; snapshotId: string | null }
+  | { kind: 'deleted'; path: string; snapshotId: string | null };
+
+export interface LspWorkspaceEditSummary {
+  changes: LspFileChange[];
+  error: string | null;
+}
+
+// Snapshot ids are timeline entries; `timeline_restore` undoes edited and deleted files.
+export const lspApplyWorkspaceEdit = (workspace: string, edit: LspWorkspaceEdit) => invoke<LspWorkspaceEditSummary>('lsp_apply_workspace_edit', { workspace, edit });
+export const lspCodeAction = (path: string, range: LspRange, diagnostics?: LspDiagnostic[], only?: string[]) => invoke<unknown[] | null>('lsp_code_action', { path, range, diagnostics, only });
 
 /**
  * Listen to LSP diagnostics events from the backend
@@ -32,3 +118,25 @@ export async function listenToLspDiagnostics(
     callback(event.payload);
   });
 }
+
+/**
+ * Listen to language server lifecycle changes (start, crash, restart, stop)
+ */
+export async function listenToLspStatus(
+  callback: (event: LspServerStatusEvent) => void
+): Promise<UnlistenFn> {
+  return listen<LspServerStatusEvent>('lsp:status', (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen to work-done progress (indexing, loading) from language servers
+ */
+export async function listenToLspProgress(
+  callback: (event: LspProgressEvent) => void
+): Promise<UnlistenFn> {
+  return listen<LspProgressEvent>('lsp:progress', (event) => {
+    callback(event.payload);
+  });
+}
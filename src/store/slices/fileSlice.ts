@@ -3,6 +3,7 @@ import { invoke } from '@tauri-apps/api/core';
 import { tauriApi } from '../../lib/tauri-api';
 import { useDiagnosticsStore } from '../diagnosticsStore';
 import { useEditorStore } from '../editorStore';
+import { lspCloseWorkspace, lspDidClose, lspDidSave } from '../../lib/tauri/lsp';
 
 export interface FileEntry {
     name: string;
@@ -181,6 +182,11 @@ export const createFileSlice: StateCreator<
         try {
             const structure = await invoke<FileEntry[]>('read_dir', { path });
             
+            const previousWorkspace = get().currentWorkspace;
+            if (previousWorkspace && previousWorkspace !== path) {
+                lspCloseWorkspace(previousWorkspace).catch(() => {});
+            }
+
             localStorage.setItem('lastWorkspace', path);
             
             const { recentProjects } = get();
@@ -205,8 +211,13 @@ export const createFileSlice: StateCreator<
 
     closeWorkspace: async () => {
         
-        const { stopFileWatcher } = get();
+        const { stopFileWatcher, currentWorkspace } = get();
         await stopFileWatcher();
+
+        // Shut language servers down cleanly instead of leaving them to idle out
+        if (currentWorkspace) {
+            lspCloseWorkspace(currentWorkspace).catch(() => {});
+        }
         
         
         localStorage.removeItem('lastWorkspace');
@@ -420,6 +431,9 @@ export const createFileSlice: StateCreator<
         diagnosticsStore.clearFileDiagnostics(path);
         diagnosticsStore.clearLspDiagnostics(path);
 
+        // Language servers can release the document
+        lspDidClose(path).catch(() => {});
+
         // Update the state with new open files list
         set({
             openFiles: newOpenFiles,
@@ -709,6 +723,8 @@ export const createFileSlice: StateCreator<
             try {
                 await invoke('write_file', { path: filePath, content });
                 get().markFileAsSaved(filePath);
+                // On-save analysis (linters, type checks) in language servers
+                lspDidSave(filePath, content).catch(() => {});
                 
                 const newOriginalContents = { ...originalContents };
                 delete newOriginalContents[filePath];